@@ -1,13 +1,23 @@
 //! CLI argument parsing types
 
 use std::io;
+use std::num::NonZeroUsize;
 use std::path::{self, Component, Path, PathBuf};
+use std::sync::OnceLock;
 
-use clap::Parser;
+use clap::{ArgAction, Parser, ValueEnum};
 use once_cell::sync::Lazy;
 use tokio::fs;
 
 use crate::manifest::{self, PrequeryManifest};
+use crate::query::STDIN;
+
+/// The separator between paths in environment variables, e.g. `TYPST_FONT_PATHS`
+#[cfg(windows)]
+const ENV_PATH_SEP: char = ';';
+/// The separator between paths in environment variables, e.g. `TYPST_FONT_PATHS`
+#[cfg(not(windows))]
+const ENV_PATH_SEP: char = ':';
 
 /// Map of preprocessors defined in this crate
 pub static ARGS: Lazy<CliArguments> = Lazy::new(CliArguments::parse);
@@ -19,27 +29,383 @@ pub struct CliArguments {
     #[clap(long, value_name = "EXE", default_value = "typst")]
     pub typst: PathBuf,
 
-    /// Configures the project root (for absolute paths)
+    /// Configures the project root (for absolute paths). By default, this is the directory of the
+    /// nearest `typst.toml` file upwards from the input file
     #[clap(long = "root", value_name = "DIR", env = "TYPST_ROOT")]
     pub root: Option<PathBuf>,
 
+    /// Reads the prequery manifest from this file instead of the `tool.prequery` section of the
+    /// nearest `typst.toml` file. The file contains a top-level `prequery` section, in TOML or,
+    /// with a `.json` extension, in JSON
+    #[clap(long, value_name = "PATH", conflicts_with = "init")]
+    pub manifest: Option<PathBuf>,
+
+    /// The project root detected if none is configured
+    #[clap(skip)]
+    detected_root: OnceLock<PathBuf>,
+
+    /// Adds additional directories that are recursively searched for fonts during queries, in
+    /// addition to those configured by each job
+    #[clap(
+        long = "font-path",
+        value_name = "DIR",
+        env = "TYPST_FONT_PATHS",
+        value_delimiter = ENV_PATH_SEP,
+    )]
+    pub font_paths: Vec<PathBuf>,
+
+    /// Configures the directory in which local packages are looked up during queries, unless a
+    /// job configures its own `package_path`
+    #[clap(long, value_name = "DIR", env = "TYPST_PACKAGE_PATH")]
+    pub package_path: Option<PathBuf>,
+
+    /// Configures the directory in which downloaded packages are cached during queries, unless a
+    /// job configures its own `package_cache_path`
+    #[clap(long, value_name = "DIR", env = "TYPST_PACKAGE_CACHE_PATH")]
+    pub package_cache_path: Option<PathBuf>,
+
+    /// Aborts downloads once the total number of downloaded bytes across all jobs exceeds this
+    /// limit
+    #[clap(long, value_name = "BYTES")]
+    pub max_total_bytes: Option<u64>,
+
+    /// Instead of downloading or writing any files, prints which files would be added, changed or
+    /// removed compared to what is currently on disk and in the index. With `--dry-run=check`, a
+    /// `HEAD` request is also sent for every resource, and broken links are reported
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "print"
+    )]
+    pub dry_run: Option<DryRun>,
+
+    /// Instead of downloading anything, performs a TLS handshake with every host resources would
+    /// be downloaded from, and reports the validity and expiry of their certificates
+    #[clap(long, conflicts_with = "dry_run")]
+    pub verify_tls_only: bool,
+
+    /// Instead of running the jobs, prints a shell script containing the `typst query` commands
+    /// they would execute. With `downloads`, the queries are executed, and `curl` commands for
+    /// downloading the resulting resources are included as well.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "COMMANDS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "queries",
+        conflicts_with_all = ["dry_run", "verify_tls_only", "log_format", "watch"],
+    )]
+    pub dump_commands: Option<DumpCommands>,
+
+    /// Instead of running the jobs, adds a commented starter `[tool.prequery]` configuration to
+    /// `typst.toml`. With `print`, the configuration is printed instead. An existing configuration
+    /// is only replaced with `--force`
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "write",
+        conflicts_with_all = ["dry_run", "verify_tls_only", "dump_commands", "watch"],
+    )]
+    pub init: Option<InitMode>,
+
+    /// Instead of running the jobs, lists the available job kinds (including those loaded from
+    /// `--plugins`) with a description of each
+    #[clap(long, conflicts_with_all = ["init", "dry_run", "dump_commands", "watch"])]
+    pub list_kinds: bool,
+
+    /// Instead of downloading anything, checks that the file of every resource of the web-resource
+    /// jobs exists and, if the job keeps an index, was downloaded from the resource's current URL.
+    /// Missing files are reported and make the run fail
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "dry_run",
+            "verify_tls_only",
+            "dump_commands",
+            "print_resolved_paths",
+            "watch",
+        ],
+    )]
+    pub check_complete: bool,
+
+    /// Turns warnings into errors, e.g. for resources that would be downloaded over insecure HTTP,
+    /// for corrupt indices that would be restored from their backups, or for unknown keys in a
+    /// job's query config
+    #[clap(long)]
+    pub warnings_as_errors: bool,
+
+    /// Replaces an existing `[tool.prequery]` configuration with `--init`
+    #[clap(long, requires = "init")]
+    pub force: bool,
+
+    /// Instead of running the jobs, prints the absolute path each resource would be saved to,
+    /// alongside its URL. The queries are executed, but nothing is downloaded or written
+    #[clap(
+        long,
+        conflicts_with_all = ["dry_run", "verify_tls_only", "dump_commands", "log_format", "watch"],
+    )]
+    pub print_resolved_paths: bool,
+
+    /// Makes the run reproducible: the time given by `SOURCE_DATE_EPOCH` (or the Unix epoch, if
+    /// unset) is passed to queries as `--creation-timestamp`, unless a job configures its own
+    /// `creation_timestamp`, and is used instead of the current time for the cache lifetimes
+    /// recorded in the index.
+    #[clap(long)]
+    pub reproducible: bool,
+
+    /// The fixed time used by `--reproducible`, in seconds since the Unix epoch
+    #[clap(long, value_name = "SECONDS", env = "SOURCE_DATE_EPOCH", hide = true)]
+    pub source_date_epoch: Option<u64>,
+
+    /// After running all jobs, watches the input document, the manifest and the files the jobs
+    /// depend on, and runs affected jobs again when these change
+    #[clap(long)]
+    pub watch: bool,
+
+    /// Ignores the remaining work persisted by interrupted runs, and starts over instead of
+    /// resuming. Only web-resource jobs with an index persist their remaining work
+    #[clap(long)]
+    pub no_resume: bool,
+
+    /// Gives an input to the queried document in all jobs' queries, like `typst query --input`.
+    /// Can be given multiple times. Inputs set by a job's query config or its preprocessor take
+    /// precedence
+    #[clap(long = "input", value_name = "KEY=VALUE", value_parser = parse_input)]
+    pub inputs: Vec<(String, String)>,
+
+    /// Doesn't set the fallback input (by default `prequery-fallback=true`) during queries. Queried
+    /// documents then can't tell that they are being queried, and e.g. try to read files that
+    /// haven't been downloaded yet, unless they are configured with other inputs to handle this
+    #[clap(long)]
+    pub no_default_fallback: bool,
+
+    /// Only downloads resources whose files are missing, and keeps all existing files without
+    /// checking whether they are up to date. This is the fastest way to fill in missing files, but
+    /// changed URLs, expired cache lifetimes and resources changed on the server are not noticed
+    #[clap(long)]
+    pub only_missing: bool,
+
+    /// How the concurrency of downloads from hosts without a configured `per_host_concurrency` is
+    /// limited. With `auto`, it starts low and is tuned for each host: it increases while
+    /// downloads succeed quickly, and decreases when a host responds with `429 Too Many Requests`
+    /// or server errors, or times out. The chosen concurrencies are included in the `--report`
+    #[clap(long, value_enum, value_name = "MODE", default_value_t = HostConcurrency::Unlimited)]
+    pub concurrency_per_host: HostConcurrency,
+
+    /// The maximum number of `typst query` processes running at the same time, across all jobs.
+    /// Defaults to the number of CPUs
+    #[clap(long, value_name = "N")]
+    pub query_concurrency: Option<NonZeroUsize>,
+
+    /// Keeps the results of queries in `.prequery-query-cache.json` in the project root, and reuses
+    /// them in later runs while the document is unchanged. With `imports`, the files the document
+    /// imports, includes or loads data from are found by parsing it, and only changes to these
+    /// files invalidate the cache; paths that are computed at runtime can't be found this way,
+    /// making the cache stale when such files change. With `directory`, any change to a file in
+    /// the project root invalidates the cache. This is always correct, but files written by jobs
+    /// count as changes too, so the cache is only effective in runs that don't write any files
+    #[clap(
+        long,
+        value_enum,
+        value_name = "MODE",
+        env = "PREQUERY_QUERY_CACHE",
+        default_value_t = QueryCacheMode::Off
+    )]
+    pub query_cache: QueryCacheMode,
+
+    /// Runs all queries even if `--query-cache` has their results, e.g. when a file the document
+    /// depends on could not be found by `--query-cache imports`. The cache is still updated with
+    /// the new results
+    #[clap(long)]
+    pub no_query_cache: bool,
+
+    /// The directory, relative to the project root, in which the results of jobs with a
+    /// `cache_key` are stored. Keeping this directory between runs, e.g. with a CI cache action,
+    /// lets these jobs be skipped while their keys and inputs are unchanged
+    #[clap(
+        long,
+        value_name = "DIR",
+        env = "PREQUERY_JOB_CACHE",
+        default_value = ".prequery-job-cache"
+    )]
+    pub job_cache: PathBuf,
+
+    /// Displays the aggregate progress of each job's downloads instead of a line per file. On a
+    /// terminal, the progress is updated in place; otherwise, summary lines are printed
+    /// periodically
+    #[clap(long)]
+    pub progress: bool,
+
+    /// Configures the output format. With `json`, status messages go to stderr and a summary of
+    /// all jobs' results is printed to stdout as JSON
+    #[clap(long, value_enum, value_name = "FORMAT", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Reduces the output for clean logs. With `-q`, status messages are not printed, so that
+    /// successful jobs produce no output and only warnings and errors are shown. With `-qq`, the
+    /// summary printed with `--log-format json` is omitted as well
+    #[clap(short, long, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Prints additional diagnostic messages, such as the detected project root
+    #[clap(short, long, conflicts_with = "quiet")]
+    pub verbose: bool,
+
+    /// Writes a report on all jobs' results (statuses, counts, timings and errors) to this file,
+    /// even if some jobs failed
+    #[clap(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Configures the format of the `--report` file. By default, the format is chosen according to
+    /// the file's extension: `.toml` for TOML, `.md` for Markdown, and JSON otherwise
+    #[clap(long, value_enum, value_name = "FORMAT", requires = "report")]
+    pub report_format: Option<ReportFormat>,
+
+    /// Writes all errors as a JSON list to this file, even if some jobs failed. Each error names
+    /// the job and, for failed downloads, the resource, so that CI tools can annotate failures
+    #[clap(long, value_name = "PATH")]
+    pub errors: Option<PathBuf>,
+
+    /// Exports traces of the run (jobs, queries and downloads) to this OTLP endpoint, e.g.
+    /// `http://localhost:4317`
+    #[cfg(feature = "otel")]
+    #[clap(long, value_name = "URL", env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Loads additional preprocessors from the dynamic libraries in this directory
+    #[cfg(feature = "plugins")]
+    #[clap(long, value_name = "DIR", env = "PREQUERY_PLUGINS")]
+    pub plugins: Option<PathBuf>,
+
     /// Path to input Typst file. `prequery-preprocess` will look for a `typst.toml` file in
-    /// directories upwards from that file to determine queries.
+    /// directories upwards from that file to determine queries. With `-`, the document is read
+    /// from stdin and the search starts in the current directory; as the document is not a file,
+    /// changes to it are not noticed by `--watch`.
+    #[clap(
+        required_unless_present = "list_kinds",
+        // only a placeholder for `--list-kinds`; an empty path would be rejected by the parser
+        default_value = STDIN,
+        hide_default_value = true
+    )]
     pub input: PathBuf,
 }
 
+/// Output formats
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable status messages
+    Text,
+    /// Machine-readable JSON summary
+    Json,
+}
+
+/// Formats of the `--report` file
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A JSON document
+    Json,
+    /// A TOML document
+    Toml,
+    /// A Markdown table, e.g. for a CI job summary
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Determines the format of a report file from its extension.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("md" | "markdown") => Self::Markdown,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// What `--init` does with the starter configuration
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitMode {
+    /// Adds the configuration to `typst.toml`
+    Write,
+    /// Prints the configuration
+    Print,
+}
+
+/// How the concurrency of downloads per host is limited
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostConcurrency {
+    /// Only the job's `concurrency` limits downloads from a host
+    Unlimited,
+    /// The concurrency is tuned for each host according to its responses
+    Auto,
+}
+
+/// How the query cache decides whether the document has changed
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryCacheMode {
+    /// Query results are not cached across runs
+    Off,
+    /// The document and the files it imports, includes or loads data from are compared
+    Imports,
+    /// All files in the project root are compared
+    Directory,
+}
+
+/// What `--dry-run` does in addition to printing the intended changes
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DryRun {
+    /// Only prints the intended changes
+    Print,
+    /// Also checks that every resource's URL is reachable, without downloading it
+    Check,
+}
+
+/// Commands included by `--dump-commands`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpCommands {
+    /// Only the `typst query` commands
+    Queries,
+    /// The `typst query` commands, and `curl` commands for downloads
+    Downloads,
+}
+
 impl CliArguments {
-    /// Returns the path of the `typst.toml` file that is closest to the input file.
+    /// Returns whether stdout is reserved for machine-readable output, so that status messages
+    /// need to go to stderr.
+    pub fn is_stdout_reserved(&self) -> bool {
+        self.log_format == LogFormat::Json
+            || self.dump_commands.is_some()
+            || self.print_resolved_paths
+    }
+
+    /// Returns the fixed time to use instead of the current time, if `--reproducible` was given.
+    pub fn reproducible_time(&self) -> Option<u64> {
+        self.reproducible
+            .then(|| self.source_date_epoch.unwrap_or(0))
+    }
+
+    /// Returns the path of the manifest file: the one given with `--manifest`, or otherwise the
+    /// `typst.toml` file that is closest to the input file.
     pub async fn resolve_typst_toml(&self) -> io::Result<PathBuf> {
         const TYPST_TOML: &str = "typst.toml";
 
+        if let Some(manifest) = &self.manifest {
+            return path::absolute(manifest);
+        }
+
         let input = path::absolute(&self.input)?;
         let mut p = input.clone();
 
         // the input path needs to refer to a file. refer to typst.toml instead
         p.set_file_name(TYPST_TOML);
         // repeat as long as the path does not point to an accessible regular file
-        while !fs::metadata(&p).await.map_or(false, |m| m.is_file()) {
+        while !fs::metadata(&p).await.is_ok_and(|m| m.is_file()) {
             // remove the file name
             let result = p.pop();
             assert!(
@@ -60,7 +426,7 @@ impl CliArguments {
         Ok(p)
     }
 
-    /// Reads the `typst.toml` file that is closest to the input file.
+    /// Reads the manifest file, see [Self::resolve_typst_toml].
     pub async fn read_typst_toml(&self) -> manifest::Result<PrequeryManifest> {
         let typst_toml = ARGS
             .resolve_typst_toml()
@@ -70,22 +436,60 @@ impl CliArguments {
         Ok(config)
     }
 
-    /// returns the root path. This is either the explicitly given root or the directory in which
-    /// the input file is located. If the input file path only consists of a file name, the current
-    /// directory (`"."`) is the root. In general, this function does not return an absolute path.
+    /// returns the root path. This is either the explicitly given root or the directory of the
+    /// nearest `typst.toml` file upwards from the input file, see [Self::detect_root]. In general,
+    /// this function does not return an absolute path.
     pub fn resolve_root(&self) -> &Path {
         if let Some(root) = &self.root {
             // a root was explicitly given
             root
-        } else if let Some(root) = self.input.parent() {
-            // the root is the directory of the input file
-            root
         } else {
-            // the root is the directory of the input file, which is the current directory
-            Path::new(".")
+            self.detected_root.get_or_init(|| self.detect_root())
+        }
+    }
+
+    /// Detects the project root: the nearest directory upwards from the input file that contains
+    /// a `typst.toml` file. The search starts with the input file's path as given, so that a
+    /// relative root is found if possible, and continues above the current directory. If no
+    /// `typst.toml` file is found, the root is the directory of the input file; if the input file
+    /// path only consists of a file name, that is the current directory (`"."`).
+    fn detect_root(&self) -> PathBuf {
+        let input_dir = self
+            .input
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let has_typst_toml = |dir: &Path| dir.join("typst.toml").is_file();
+        let relative = input_dir.ancestors().find(|dir| has_typst_toml(dir));
+        match relative {
+            Some(dir) if dir.as_os_str().is_empty() => PathBuf::from("."),
+            Some(dir) => dir.to_path_buf(),
+            None => path::absolute(input_dir)
+                .ok()
+                .and_then(|dir| {
+                    dir.ancestors()
+                        .find(|dir| has_typst_toml(dir))
+                        .map(Path::to_path_buf)
+                })
+                .unwrap_or_else(|| input_dir.to_path_buf()),
         }
     }
 
+    /// Returns the root to pass to Typst as `--root` when querying the given file: the explicitly
+    /// given root, or the detected root if it differs from Typst's default, the directory of the
+    /// queried file.
+    pub fn typst_root(&self, input: &Path) -> Option<&Path> {
+        if let Some(root) = &self.root {
+            return Some(root);
+        }
+        let root = self.resolve_root();
+        let default = input
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        (root != default).then_some(root)
+    }
+
     /// Resolve the virtual path relative to an actual file system root
     /// (where the project or package resides).
     ///
@@ -112,3 +516,11 @@ impl CliArguments {
         Some(out)
     }
 }
+
+/// Parses an input given on the command line as `KEY=VALUE`.
+fn parse_input(input: &str) -> Result<(String, String), String> {
+    let (key, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected `KEY=VALUE`, found `{input}`"))?;
+    Ok((key.to_string(), value.to_string()))
+}
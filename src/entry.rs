@@ -1,34 +1,287 @@
 //! Contains the executable's entry point
 
-use crate::args::ARGS;
-use crate::error::{MultiplePreprocessorExecutionError, Result};
-use crate::utils;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use typst_syntax::package::PackageVersion;
+
+use crate::args::{LogFormat, ReportFormat, ARGS};
+use crate::error::{Error as CrateError, MultiplePreprocessorExecutionError, Result};
+use crate::preprocessor::{BoxedPreprocessor, ExecutionError, Mode, Preprocessor};
+use crate::report::{Counts, ErrorRecord, JobReport, RunReport};
+use crate::utils::status;
+use crate::{init, preprocessor, query, watch, web_resource};
 
 /// Entry point; reads the command line arguments, determines the input files and jobs to run, and
 /// then executes the jobs.
 #[tokio::main]
 pub async fn main() -> Result<()> {
+    #[cfg(feature = "otel")]
+    let _telemetry = ARGS
+        .otlp_endpoint
+        .as_deref()
+        .map(crate::telemetry::Telemetry::init)
+        .transpose()?;
+
+    if ARGS.list_kinds {
+        for (kind, description) in preprocessor::kinds() {
+            println!("{kind:<16} {description}");
+        }
+        return Ok(());
+    }
+
+    if let Some(mode) = ARGS.init {
+        init::init(mode).await?;
+        return Ok(());
+    }
+
+    if ARGS.verbose && ARGS.root.is_none() {
+        status!("Detected project root {}", ARGS.resolve_root().display());
+    }
+
+    let jobs = match configure_jobs().await {
+        Ok(jobs) => jobs,
+        Err(error) => {
+            if let Some(path) = &ARGS.errors {
+                ErrorRecord::write_all(&config_error_records(&error), path).await?;
+            }
+            return Err(error);
+        }
+    };
+    if ARGS.dump_commands.is_some() {
+        println!("#!/bin/sh");
+        println!("set -e");
+    }
+    let outcome = run_jobs(jobs).await;
+
+    if let Some(path) = &ARGS.report {
+        let format = ARGS
+            .report_format
+            .unwrap_or_else(|| ReportFormat::from_path(path));
+        outcome.report.write(path, format).await?;
+    }
+
+    if let Some(path) = &ARGS.errors {
+        ErrorRecord::write_all(&outcome.error_records, path).await?;
+    }
+
+    if ARGS.log_format == LogFormat::Json && ARGS.quiet < 2 {
+        let summary = serde_json::to_string_pretty(&outcome.report)
+            .expect("the run report should be serializable");
+        println!("{summary}");
+    }
+
+    if ARGS.watch {
+        let result = watch::watch(outcome.jobs).await;
+        query::remove_stdin_document().await;
+        return result;
+    }
+    query::remove_stdin_document().await;
+
+    if !outcome.errors.is_empty() {
+        return Err(MultiplePreprocessorExecutionError::new(outcome.errors).into());
+    }
+
+    Ok(())
+}
+
+/// Reads the manifest and configures the jobs defined in it.
+pub(crate) async fn configure_jobs() -> Result<Vec<BoxedPreprocessor>> {
     let config = ARGS.read_typst_toml().await?;
+    if let Some(required) = config.min_typst_version {
+        // dumped commands are run later, possibly elsewhere
+        if ARGS.dump_commands.is_none() {
+            check_typst_version(required).await?;
+        }
+    }
     let jobs = config.get_preprocessors()?;
+    Ok(jobs)
+}
+
+/// Checks that the installed Typst is at least the required version.
+async fn check_typst_version(required: PackageVersion) -> Result<()> {
+    let detected = query::probe_typst_version()
+        .await
+        .map_err(|source| CrateError::TypstVersionProbe { required, source })?;
+    if detected < required {
+        return Err(CrateError::TypstVersion { detected, required });
+    }
+    Ok(())
+}
+
+/// Creates the `--errors` records for a failure to configure the jobs.
+fn config_error_records(error: &CrateError) -> Vec<ErrorRecord> {
+    match error {
+        CrateError::PreprocessorConfig(errors) => errors
+            .errors()
+            .iter()
+            .map(|(name, error)| ErrorRecord::new(Some(name.as_str()), "configuration", error))
+            .collect(),
+        error => vec![ErrorRecord::new(None, "manifest", error)],
+    }
+}
+
+/// The outcome of running a set of jobs
+pub(crate) struct RunOutcome {
+    /// The jobs, so that they can be run again. Jobs that panicked are not included.
+    pub jobs: Vec<BoxedPreprocessor>,
+    /// The errors of all jobs that failed
+    pub errors: Vec<ExecutionError>,
+    /// The report on all jobs' results
+    pub report: RunReport,
+    /// The records of all errors, for the `--errors` file
+    pub error_records: Vec<ErrorRecord>,
+}
 
-    let jobs = jobs.into_iter().map(|mut job| async move {
-        println!("[{}] beginning job...", job.name());
-        let result = job.run().await;
-        match &result {
-            Ok(()) => {
-                println!("[{}] job finished", job.name());
+/// Runs the given jobs concurrently and collects their results. A job is only started once the
+/// jobs it [depends on](crate::preprocessor::Preprocessor::depends_on) have finished, and is not
+/// run if one of them failed. Dependencies on jobs that are not given, e.g. because they are
+/// disabled or not affected by a change, are ignored.
+pub(crate) async fn run_jobs(jobs: Vec<BoxedPreprocessor>) -> RunOutcome {
+    let start = Instant::now();
+    // limits such as `--max-total-bytes` apply to each run
+    web_resource::reset_run_state();
+    // the document may have changed since the last run
+    query::clear_cache().await;
+
+    let spawn = |mut job: BoxedPreprocessor| {
+        let name = job.name().to_string();
+        let span = tracing::info_span!("job", name = %name);
+        let task = async move {
+            status!("[{}] beginning job...", job.name());
+            let start = Instant::now();
+            let mode = Mode::current();
+            let result = if mode == Mode::Run || job.supports(mode) {
+                job.run().await
+            } else {
+                skip_job(&*job, mode);
+                Ok(())
+            };
+            match &result {
+                Ok(()) => {
+                    status!("[{}] job finished", job.name());
+                }
+                Err(error) => {
+                    eprintln!("[{}] job failed: {error:?}", job.name());
+                }
             }
-            Err(error) => {
-                eprintln!("[{}] job failed: {error:?}", job.name());
+            let error = result
+                .as_ref()
+                .err()
+                .map(|error| error as &(dyn Error + 'static));
+            let mut report =
+                JobReport::new(job.name().to_string(), start.elapsed(), job.counts(), error);
+            report.host_concurrency = job.host_concurrency();
+            (job, result, report)
+        };
+        tokio::spawn(task.instrument(span))
+    };
+
+    // the number of jobs of each name that have not finished yet
+    let mut unfinished: HashMap<String, usize> = HashMap::new();
+    for job in &jobs {
+        *unfinished.entry(job.name().to_string()).or_default() += 1;
+    }
+    let mut failed = HashSet::new();
+    // the results by the jobs' original positions, so that they are reported in order
+    let mut results = BTreeMap::new();
+    let mut pending: Vec<_> = jobs.into_iter().enumerate().collect();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut running = 0;
+    loop {
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, job)| {
+            let depends_on = job.depends_on();
+            !depends_on.iter().any(|name| unfinished.contains_key(name))
+        });
+        pending = waiting;
+        for (index, job) in ready {
+            let name = job.name().to_string();
+            running += 1;
+            let failed_dependency = job
+                .depends_on()
+                .iter()
+                .find(|dependency| failed.contains(*dependency));
+            if let Some(dependency) = failed_dependency {
+                eprintln!("[{name}] job skipped: the job `{dependency}` it depends on failed");
+                let error = ExecutionError::Dependency(dependency.clone());
+                let report = JobReport::new(
+                    name.clone(),
+                    Duration::ZERO,
+                    Counts::default(),
+                    Some(&error),
+                );
+                let _ = tx.send((index, name, Ok((job, Err(error), report))));
+                continue;
             }
+            let handle = spawn(job);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _ = tx.send((index, name, handle.await));
+            });
+        }
+        // dependency cycles are rejected when configuring the jobs, so all jobs are eventually run
+        if running == 0 {
+            break;
         }
-        result
-    });
-    let errors = utils::spawn_set(jobs).await;
+        let (index, name, result) = rx.recv().await.expect("the channel should not be closed");
+        running -= 1;
+        if !matches!(&result, Ok((_, Ok(()), _))) {
+            failed.insert(name.clone());
+        }
+        if let Some(count) = unfinished.get_mut(&name) {
+            *count -= 1;
+            if *count == 0 {
+                unfinished.remove(&name);
+            }
+        }
+        results.insert(index, (name, result));
+    }
 
-    if !errors.is_empty() {
-        return Err(MultiplePreprocessorExecutionError::new(errors).into());
+    let mut outcome = RunOutcome {
+        jobs: Vec::new(),
+        errors: Vec::new(),
+        report: RunReport::default(),
+        error_records: Vec::new(),
+    };
+    for (name, result) in results.into_values() {
+        match result {
+            Ok((job, result, report)) => {
+                outcome.jobs.push(job);
+                outcome.report.jobs.push(report);
+                if let Err(error) = result {
+                    let records = ErrorRecord::from_job_error(&name, &error);
+                    outcome.error_records.extend(records);
+                    outcome.errors.push(error);
+                }
+            }
+            Err(error) => {
+                eprintln!("[{name}] job panicked: {error:?}");
+                let record = ErrorRecord::new(Some(name.as_str()), "panic", &error);
+                outcome.error_records.push(record);
+                let report = JobReport::panicked(name, start.elapsed(), &error);
+                outcome.report.jobs.push(report);
+                outcome.errors.push(error.into());
+            }
+        }
     }
+    outcome
+}
 
-    Ok(())
+/// Reports that a job has nothing to do in a mode it doesn't support.
+fn skip_job(job: &(dyn Preprocessor + Send), mode: Mode) {
+    let name = job.name();
+    match mode {
+        Mode::Run => unreachable!("all jobs support running normally"),
+        Mode::DryRun => status!("[{name}] dry run: the job is not run"),
+        Mode::DumpCommands => match job.shell_command() {
+            Some(command) => println!("\n# {name}\n{command}"),
+            None => println!("\n# {name}: no commands to dump"),
+        },
+        Mode::PrintResolvedPaths => status!("[{name}] No paths to resolve"),
+        Mode::VerifyTls => status!("[{name}] Nothing to verify"),
+        Mode::CheckComplete => status!("[{name}] Nothing to check"),
+    }
 }
@@ -4,20 +4,52 @@ use std::fmt;
 
 use thiserror::Error;
 
-use crate::{manifest, preprocessor};
+use typst_syntax::package::PackageVersion;
+
+use crate::{init, manifest, preprocessor, query, report};
 
 /// Indicates that the query config is not valid for web-resource
 #[derive(Error, Debug)]
 pub enum Error {
-    /// The typst.toml file could not be read
-    #[error("prequery configuration could not be read from typst.toml")]
+    /// The manifest file could not be read
+    #[error("prequery configuration could not be read")]
     Manifest(#[from] manifest::Error),
+    /// The installed Typst is older than the manifest's `min_typst_version`
+    #[error("Typst {detected} is installed, but the manifest requires at least Typst {required}")]
+    TypstVersion {
+        /// The installed version
+        detected: PackageVersion,
+        /// The required version
+        required: PackageVersion,
+    },
+    /// The installed Typst's version could not be determined, e.g. because it is missing
+    #[error("the Typst version could not be determined, but at least {required} is required")]
+    TypstVersionProbe {
+        /// The required version
+        required: PackageVersion,
+        /// The reason the version could not be determined
+        #[source]
+        source: query::Error,
+    },
     /// A preprocessor is not configured correctly
     #[error(transparent)]
     PreprocessorConfig(#[from] MultiplePreprocessorConfigError),
     /// A preprocessor's execution failed
     #[error(transparent)]
     PreprocessorExecution(#[from] MultiplePreprocessorExecutionError),
+    /// The report file could not be written
+    #[error("the run report could not be written")]
+    Report(#[from] report::Error),
+    /// The starter configuration could not be added
+    #[error("the starter configuration could not be added")]
+    Init(#[from] init::Error),
+    /// Watching for changes failed
+    #[error("watching for changes failed")]
+    Watch(#[from] notify::Error),
+    /// Exporting traces could not be set up
+    #[cfg(feature = "otel")]
+    #[error("exporting traces could not be set up")]
+    Telemetry(#[from] opentelemetry::trace::TraceError),
 }
 
 /// One or more preprocessors were not configured correctly
@@ -31,6 +63,11 @@ impl MultiplePreprocessorConfigError {
     pub fn new(errors: Vec<(String, preprocessor::ConfigError)>) -> Self {
         Self { errors }
     }
+
+    /// The names of the jobs that were not configured correctly, with their errors
+    pub fn errors(&self) -> &[(String, preprocessor::ConfigError)] {
+        &self.errors
+    }
 }
 
 impl fmt::Display for MultiplePreprocessorConfigError {
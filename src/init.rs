@@ -0,0 +1,103 @@
+//! Scaffolding a starter configuration with `--init`
+
+use tokio::fs;
+use toml_edit::DocumentMut;
+
+use crate::args::{InitMode, ARGS};
+use crate::manifest::PrequeryManifest;
+use crate::utils::status;
+
+pub use error::*;
+
+/// A commented starter configuration with a single `web-resource` job
+pub const STARTER: &str = r#"
+# Configuration of prequery-preprocess: each job processes the results of one query of the document
+[tool.prequery]
+
+[[tool.prequery.jobs]]
+# The job's name, used in status messages
+name = "download"
+# web-resource jobs download the files the document references via `prequery.image` and similar
+kind = "web-resource"
+# The query can be customized, e.g. to use a different label
+# query.selector = "<web-resource>"
+# Keeps track of downloaded files, so that changed URLs are noticed
+index = true
+# Deletes files that the document doesn't reference anymore; this requires the index
+# evict = true
+"#;
+
+/// Writes or prints the starter configuration, depending on the mode.
+pub async fn init(mode: InitMode) -> Result<()> {
+    check_starter();
+
+    if mode == InitMode::Print {
+        print!("{}", STARTER.trim_start());
+        return Ok(());
+    }
+
+    let path = ARGS.resolve_typst_toml().await?;
+    let content = fs::read_to_string(&path).await?;
+    let mut document: DocumentMut = content.parse()?;
+    let tool = document
+        .get_mut("tool")
+        .and_then(|tool| tool.as_table_like_mut());
+    if let Some(tool) = tool {
+        if tool.contains_key("prequery") {
+            if !ARGS.force {
+                return Err(Error::Exists);
+            }
+            tool.remove("prequery");
+        }
+    }
+
+    let mut content = document.to_string();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(STARTER);
+    fs::write(&path, content).await?;
+
+    let path_str = path.to_string_lossy();
+    status!("Added a starter configuration to {path_str}");
+    Ok(())
+}
+
+/// Checks that the starter configuration is valid, by configuring its jobs like those of any
+/// other configuration.
+fn check_starter() {
+    let starter: toml::Table =
+        toml::from_str(STARTER).expect("the starter configuration should be valid TOML");
+    let manifest: PrequeryManifest = starter["tool"]["prequery"]
+        .clone()
+        .try_into()
+        .expect("the starter configuration should be valid");
+    manifest
+        .get_preprocessors()
+        .expect("the starter configuration's jobs should be valid");
+}
+
+mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    /// Error while scaffolding a configuration
+    #[derive(Error, Debug)]
+    pub enum Error {
+        /// `typst.toml` could not be read or written
+        #[error("typst.toml could not be read or written")]
+        Io(#[from] io::Error),
+        /// `typst.toml` is not valid TOML
+        #[error("typst.toml is not valid TOML")]
+        Toml(#[from] toml_edit::TomlError),
+        /// `typst.toml` already contains a configuration
+        #[error(
+            "typst.toml already contains a prequery configuration (use --force to replace it)"
+        )]
+        Exists,
+    }
+
+    /// Result type alias that defaults error to [Error].
+    pub type Result<T, E = Error> = std::result::Result<T, E>;
+}
@@ -4,11 +4,18 @@
 pub mod args;
 pub mod entry;
 pub mod error;
+pub mod init;
 pub mod manifest;
+#[cfg(feature = "plugins")]
+pub mod plugin;
 pub mod preprocessor;
 mod preprocessors;
 pub mod query;
+pub mod report;
+#[cfg(feature = "otel")]
+pub mod telemetry;
 mod utils;
+mod watch;
 
 // re-export the actual preprocessors from the top level
 pub use preprocessors::*;
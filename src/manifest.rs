@@ -1,18 +1,24 @@
 //! Configuration types
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error as StdError;
+use std::ffi::OsStr;
 use std::fmt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use itertools::{Either, Itertools};
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer};
 use tokio::fs;
 use toml::Table;
-use typst_syntax::package::PackageManifest;
+use typst_syntax::package::{PackageManifest, PackageVersion};
 
+use crate::args::ARGS;
 use crate::error::MultiplePreprocessorConfigError;
 use crate::preprocessor::{self, BoxedPreprocessor};
+use crate::preprocessors::web_resource::WebResourceFactory;
+use crate::query::{NumberMode, QueryFormat};
+use crate::utils::status;
 
 pub use error::*;
 
@@ -20,10 +26,35 @@ pub use error::*;
 /// Usually, that section will be defined as multiple `[[tool.prequery.jobs]]` entries.
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct PrequeryManifest {
+    /// The input that is set during all jobs' queries, unless a job configures its own. If
+    /// `false`, no fallback input is set by default.
+    #[serde(default, deserialize_with = "deserialize_fallback")]
+    pub fallback: Option<Option<FallbackInput>>,
+    /// The minimum version of Typst the jobs' queries need, e.g. `"0.12.0"`. If given, the version
+    /// reported by `typst --version` is checked before any jobs are run.
+    pub min_typst_version: Option<PackageVersion>,
+    /// Manifest fragments whose jobs are appended to `jobs`, e.g. `["jobs/fonts.toml"]`. Each
+    /// fragment is a TOML file containing `[[jobs]]` entries and may include further fragments.
+    /// Included paths are relative to the including file; relative paths in the included jobs are
+    /// still resolved against the directory containing `typst.toml`.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
     /// The preprocessing jobs to execute
+    #[serde(default)]
     pub jobs: Vec<Job>,
 }
 
+/// A manifest fragment, included from `typst.toml` or from another fragment
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+struct ManifestFragment {
+    /// Further manifest fragments to include, relative to this one
+    #[serde(default)]
+    include: Vec<PathBuf>,
+    /// The preprocessing jobs defined in this fragment
+    #[serde(default)]
+    jobs: Vec<Job>,
+}
+
 /// A single preprocessing job. A job normally consists of executing the configured query and then
 /// processing the result in some way, usually writing to files in the project root.
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -32,9 +63,41 @@ pub struct Job {
     pub name: String,
     /// Identifier of the preprocessor that should be run
     pub kind: String,
-    /// The query the preprocessor needs to run
+    /// Change this to false to skip the job without removing it from the manifest
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// The names of jobs that need to finish before this job is run. If one of them fails, this
+    /// job is not run. Jobs without dependencies between them are run concurrently.
     #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// The query the preprocessor needs to run
+    #[serde(default, deserialize_with = "deserialize_query")]
     pub query: Query,
+    /// The data format the job works with end-to-end. This is the default for the query's
+    /// `format`; configuring a different format for the query is an error.
+    pub format: Option<QueryFormat>,
+    /// Environment variables set for the job's `typst query` processes, e.g. to control the
+    /// `TYPST_*` variables. They only apply to the query processes, not to prequery itself or
+    /// the job's other work such as downloads. The query's own `env` takes precedence.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Change this to true to not pass prequery's own environment on to the job's `typst query`
+    /// processes, so that they only see the variables given in `env`. Note that this includes
+    /// `PATH`, so `--typst` may need to be an absolute path.
+    #[serde(default)]
+    pub clear_env: bool,
+    /// The maximum time in seconds the whole job may take, including its query and all its
+    /// downloads. When exceeded, the job's remaining work is cancelled and the job fails. This is
+    /// distinct from the per-download or per-query timeouts a preprocessor may have.
+    pub job_timeout: Option<u64>,
+    /// A key identifying the job's results, e.g. `"fonts-v1-{{inputs.variant}}"`. This is a
+    /// Handlebars template that can reference the query's inputs as `inputs.NAME` and environment
+    /// variables as `env.NAME`. If given, the files the job writes are stored in the job cache
+    /// (`--job-cache`), and later runs with the same key, the same job configuration and
+    /// unchanged input files (such as templates) restore them instead of running the job. To
+    /// invalidate the cache, change the key, e.g. by including a version, or delete the cache
+    /// directory.
+    pub cache_key: Option<String>,
     /// Arbitrary additional manifest for the job
     #[serde(flatten)]
     pub manifest: Table,
@@ -43,58 +106,292 @@ pub struct Job {
 /// Query configuration. All fields here are optional, as preprocessors can define their own
 /// defaults.
 #[derive(Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
 pub struct Query {
-    /// The selector to be queried, e.g. `<label>`
-    pub selector: Option<String>,
+    /// The selector to be queried, e.g. `<label>`. If several selectors are given, each is queried
+    /// and their results are concatenated in order.
+    #[serde(default, deserialize_with = "deserialize_selector")]
+    pub selector: Option<Vec<String>>,
     /// The field (`--field`) to be queried from the selector (with metadata elements, this is
-    /// usually `value`)
+    /// usually `value`). A dotted field such as `value.url` selects nested fields from each
+    /// result. If `false`, whole elements are queried; metadata elements are then unwrapped to
+    /// their values, so that both forms result in the same data.
     #[serde(default, deserialize_with = "deserialize_field")]
     pub field: Option<Option<String>>,
     /// Whether only one (`--one`) query result is expected and should be returned
     pub one: Option<bool>,
     /// Any additional inputs (`--input`) to be given to the queried document. Regardless of these
-    /// settings, `prequery-fallback` is always set to `true` during queries.
+    /// settings, the fallback input (by default `prequery-fallback=true`) is set during queries
+    /// unless disabled.
+    #[serde(default)]
+    pub inputs: Inputs,
+    /// The document to query instead of the input file given on the command line, e.g. a
+    /// sub-document. The path is resolved against the project root and must not be outside it.
+    pub input: Option<PathBuf>,
+    /// The directory in which local packages are looked up (`--package-path`). Relative paths are
+    /// resolved against the directory containing `typst.toml`. Defaults to the directory given on
+    /// the command line, if any.
+    pub package_path: Option<PathBuf>,
+    /// The directory in which downloaded packages are cached (`--package-cache-path`). Relative
+    /// paths are resolved against the directory containing `typst.toml`. Defaults to the
+    /// directory given on the command line, if any.
+    pub package_cache_path: Option<PathBuf>,
+    /// Additional directories that are recursively searched for fonts (`--font-path`). Relative
+    /// paths are resolved against the directory containing `typst.toml`. Directories given on
+    /// the command line are searched as well.
+    #[serde(default)]
+    pub font_paths: Vec<PathBuf>,
+    /// The document's creation time (`--creation-timestamp`), in seconds since the Unix epoch.
+    /// With `--reproducible`, this defaults to `SOURCE_DATE_EPOCH`.
+    pub creation_timestamp: Option<u64>,
+    /// How numbers in the query result are handled
     #[serde(default)]
-    pub inputs: HashMap<String, String>,
+    pub number_mode: NumberMode,
+    /// The format of the query result: `json` (the default), `ndjson` or `yaml`
+    pub format: Option<QueryFormat>,
+    /// Environment variables set for the `typst query` process, e.g. `{ LANG = "de" }`. They only
+    /// apply to this job's queries; neither other jobs nor this process see them.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Doesn't pass prequery's own environment on to the `typst query` process, so that it only
+    /// sees the variables given in `env`.
+    #[serde(default)]
+    pub clear_env: bool,
+    /// Runs multiple queries and zips their results positionally into records, e.g.
+    /// `{ url = "<res-url>", path = "<res-path>" }` results in records with `url` and `path`
+    /// fields. All queries must have the same number of results. When zipping, `selector` is not
+    /// queried and doesn't need to be given.
+    #[serde(default)]
+    pub zip: BTreeMap<String, String>,
+    /// A JSON Schema file the query result must be valid against; otherwise, the job fails with
+    /// the validation errors. Relative paths are resolved against the directory containing
+    /// `typst.toml`.
+    pub schema: Option<PathBuf>,
+    /// How long, in seconds, a `typst query` process may run before it is killed and the job
+    /// fails. By default, queries may run indefinitely.
+    pub timeout: Option<u64>,
+    /// The number of times a failed `typst query` process is run again, e.g. because a package
+    /// download failed. Invalid query results are not retried. Defaults to 0.
+    #[serde(default)]
+    pub retries: u32,
+    /// The delay in milliseconds before the first retry of a failed query; each further retry
+    /// waits twice as long. Defaults to 1000.
+    pub retry_delay: Option<u64>,
+    /// The minimum number of results the query must have; with fewer, e.g. because of a typo in
+    /// the selector, the job fails instead of silently doing nothing. Can't be combined with
+    /// `one = true`.
+    pub min_results: Option<usize>,
+    /// The input that is set during queries so that documents can tell that they are being
+    /// queried. If `false`, no fallback input is set. Defaults to the top-level `fallback`
+    /// setting.
+    #[serde(default, deserialize_with = "deserialize_fallback")]
+    pub fallback: Option<Option<FallbackInput>>,
+    /// The keys of the query config that are not recognized, e.g. because of a typo. They are
+    /// split out before deserializing, so that they can be reported instead of silently ignored.
+    #[serde(skip)]
+    pub unknown_keys: Vec<String>,
+}
+
+/// The input that is set during queries so that documents can tell that they are being queried;
+/// prequery uses it to avoid e.g. reading files that haven't been downloaded yet. The default is
+/// `prequery-fallback=true`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct FallbackInput {
+    /// The name of the input
+    pub key: String,
+    /// The value of the input
+    pub value: String,
+}
+
+impl Default for FallbackInput {
+    fn default() -> Self {
+        Self {
+            key: "prequery-fallback".to_string(),
+            value: "true".to_string(),
+        }
+    }
+}
+
+/// Inputs for the queried document, given inline and/or read from a data file
+#[derive(Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub struct Inputs {
+    /// A JSON or TOML file (depending on the extension) containing a table of inputs. Values that
+    /// are not strings are converted to JSON strings. Relative paths are resolved against the
+    /// directory containing `typst.toml`. Because of this key, there can't be an inline input
+    /// named `file`.
+    pub file: Option<PathBuf>,
+    /// The inline inputs, which take precedence over those read from the file
+    #[serde(flatten)]
+    pub values: HashMap<String, String>,
 }
 
 impl PrequeryManifest {
-    /// Given the contents of a `typst.toml` file, parses the `[tool.prequery]` section.
+    /// Given the contents of a `typst.toml` file, parses the `[tool.prequery]` section. String
+    /// values in that section may reference environment variables as `${env:VAR}` and the
+    /// project root as `${root}`; a literal `$` is written as `$$`. Included manifest fragments
+    /// are only read by [Self::read].
     pub fn parse(content: &str) -> Result<Self> {
+        let mut config = Self::parse_section(content)?;
+        config.apply_defaults();
+        Ok(config)
+    }
+
+    /// Parses the `[tool.prequery]` section without applying the settings that affect all jobs,
+    /// so that the jobs of included fragments can be added first.
+    fn parse_section(content: &str) -> Result<Self> {
         let mut config: PackageManifest = toml::from_str(content)?;
         let config = config
             .tool
             .sections
             .remove("prequery")
-            .ok_or(Error::Missing)?
-            .try_into::<Self>()
-            .map_err(Error::from)?;
+            .ok_or(Error::Missing)?;
+        Self::from_section(toml::Value::Table(config))
+    }
+
+    /// Parses the top-level `prequery` section of a standalone manifest file, see
+    /// [Self::parse_section]. The file contains TOML, or JSON if `json` is true; in JSON, `null`
+    /// values are treated as if they were absent.
+    fn parse_standalone_section(content: &str, json: bool) -> Result<Self> {
+        let config = if json {
+            json_to_toml(serde_json::from_str(content)?)
+        } else {
+            toml::from_str(content)?
+        };
+        let config = match config {
+            toml::Value::Table(mut config) => config.remove("prequery"),
+            _ => None,
+        };
+        let config = config.ok_or(Error::MissingStandalone)?;
+        Self::from_section(config)
+    }
+
+    /// Interpolates and deserializes the given prequery section.
+    fn from_section(mut config: toml::Value) -> Result<Self> {
+        interpolate_value(&mut config, &mut String::new())?;
+        let config = config.try_into::<Self>().map_err(Error::from)?;
         Ok(config)
     }
 
-    /// Resolves and reads the given `typst.toml` file.
+    /// Applies the settings that depend on all jobs, see [Self::apply_fallback] and
+    /// [WebResourceFactory::derive_index_paths].
+    fn apply_defaults(&mut self) {
+        self.apply_fallback();
+        WebResourceFactory::derive_index_paths(&mut self.jobs);
+    }
+
+    /// Applies the top-level fallback input to all jobs that don't configure their own.
+    pub fn apply_fallback(&mut self) {
+        let Some(fallback) = &self.fallback else {
+            return;
+        };
+        for job in &mut self.jobs {
+            job.query.fallback.get_or_insert_with(|| fallback.clone());
+        }
+    }
+
+    /// Resolves and reads the given manifest file, including the manifest fragments it
+    /// references. If the file is a `typst.toml` file, the manifest is its `[tool.prequery]`
+    /// section; otherwise, it is the top-level `prequery` section of a standalone TOML or, with a
+    /// `.json` extension, JSON file. Relative paths in the manifest are resolved against the
+    /// directory containing that file.
     pub async fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let config = fs::read_to_string(path).await?;
-        let config = Self::parse(&config)?;
+        let mut config = if path.file_name() == Some(OsStr::new("typst.toml")) {
+            Self::parse_section(&config)?
+        } else {
+            let json = path.extension() == Some(OsStr::new("json"));
+            Self::parse_standalone_section(&config, json)?
+        };
+        config.read_includes(path).await?;
+        config.apply_defaults();
+        if let Some(dir) = path.parent() {
+            config.resolve_paths(dir);
+        }
+        config.load_inputs().await?;
         Ok(config)
     }
 
+    /// Reads the manifest fragments included by this manifest, which was read from the given
+    /// file, and appends their jobs. Fragments are read depth-first, so that each fragment's jobs
+    /// come before those of the next fragment it's included alongside. Fails if a fragment
+    /// includes itself, directly or through other fragments.
+    async fn read_includes(&mut self, path: &Path) -> Result<()> {
+        let path = fs::canonicalize(path).await?;
+        // the fragments still to read, each with the files that included it
+        let mut pending: Vec<(PathBuf, Vec<PathBuf>)> = Vec::new();
+        let include = |pending: &mut Vec<_>, includes: &[PathBuf], chain: Vec<PathBuf>| {
+            let dir = chain.last().and_then(|file| file.parent());
+            let dir = dir.unwrap_or(Path::new(""));
+            for include in includes.iter().rev() {
+                pending.push((dir.join(include), chain.clone()));
+            }
+        };
+        include(&mut pending, &self.include, vec![path]);
+
+        while let Some((path, mut chain)) = pending.pop() {
+            let io_error = |source| Error::IncludeIo {
+                path: path.clone(),
+                source,
+            };
+            let canonical = fs::canonicalize(&path).await.map_err(io_error)?;
+            if chain.contains(&canonical) {
+                return Err(Error::RecursiveInclude(path));
+            }
+            let content = fs::read_to_string(&path).await.map_err(io_error)?;
+            let fragment =
+                ManifestFragment::parse(&content).map_err(|source| Error::IncludeParse {
+                    path: path.clone(),
+                    source,
+                })?;
+            self.jobs.extend(fragment.jobs);
+            chain.push(canonical);
+            include(&mut pending, &fragment.include, chain);
+        }
+        Ok(())
+    }
+
+    /// Reads the inputs files of all jobs' queries, see [Inputs::load_file].
+    pub async fn load_inputs(&mut self) -> Result<()> {
+        for job in &mut self.jobs {
+            job.query.inputs.load_file().await?;
+        }
+        Ok(())
+    }
+
+    /// Resolves relative paths in the manifest against the given directory, which should usually
+    /// be the one containing `typst.toml`.
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        for job in &mut self.jobs {
+            job.query.resolve_paths(dir);
+        }
+    }
+
     /// Tries to configure all preprocessors in this manifest. Fails if any preprocessors can not be
     /// configured.
     pub fn get_preprocessors(
         self,
     ) -> Result<Vec<BoxedPreprocessor>, MultiplePreprocessorConfigError> {
+        let dependency_errors = self.check_dependencies();
         let jobs: Vec<_> = self
             .jobs
             .into_iter()
+            .filter(|job| {
+                if !job.enabled {
+                    status!("[{}] Skipped (disabled)", job.name);
+                }
+                job.enabled
+            })
             .map(preprocessor::get_preprocessor)
             .collect();
 
-        let (jobs, errors): (Vec<_>, Vec<_>) =
+        let (jobs, mut errors): (Vec<_>, Vec<_>) =
             jobs.into_iter().partition_map(|result| match result {
                 Ok(value) => Either::Left(value),
                 Err(err) => Either::Right(err),
             });
+        errors.extend(dependency_errors);
 
         if !errors.is_empty() {
             return Err(MultiplePreprocessorConfigError::new(errors));
@@ -102,6 +399,142 @@ impl PrequeryManifest {
 
         Ok(jobs)
     }
+
+    /// Checks that jobs only depend on jobs that exist, and that no job depends on itself,
+    /// directly or through other jobs. Disabled jobs count as existing.
+    fn check_dependencies(&self) -> Vec<(String, preprocessor::ConfigError)> {
+        let mut graph: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for job in &self.jobs {
+            let dependencies = job.depends_on.iter().map(String::as_str);
+            graph.entry(&job.name).or_default().extend(dependencies);
+        }
+
+        let mut errors = Vec::new();
+        for job in &self.jobs {
+            for dependency in &job.depends_on {
+                if !graph.contains_key(dependency.as_str()) {
+                    let error = preprocessor::ConfigError::UnknownDependency(dependency.clone());
+                    errors.push((job.name.clone(), error));
+                }
+            }
+        }
+
+        let mut visited = BTreeSet::new();
+        for &name in graph.keys() {
+            let mut path = Vec::new();
+            if let Some(cycle) = find_cycle(&graph, name, &mut path, &mut visited) {
+                // each cycle is only reported once
+                visited.extend(&path);
+                let cycle = cycle.iter().map(|name| format!("`{name}`")).join(" -> ");
+                let error = preprocessor::ConfigError::DependencyCycle(cycle);
+                errors.push((name.to_string(), error));
+            }
+        }
+        errors
+    }
+}
+
+impl ManifestFragment {
+    /// Given the contents of a manifest fragment, parses it. String values are interpolated like
+    /// in the `[tool.prequery]` section, see [PrequeryManifest::parse].
+    fn parse(content: &str) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        let mut fragment: toml::Value = toml::from_str(content)?;
+        interpolate_value(&mut fragment, &mut String::new())?;
+        let fragment = fragment.try_into::<Self>()?;
+        Ok(fragment)
+    }
+}
+
+/// Searches the dependency graph depth-first for a cycle reachable from the given job. `path`
+/// contains the jobs leading to this one; `visited` the jobs from which no cycle is reachable.
+fn find_cycle<'a>(
+    graph: &BTreeMap<&'a str, Vec<&'a str>>,
+    name: &'a str,
+    path: &mut Vec<&'a str>,
+    visited: &mut BTreeSet<&'a str>,
+) -> Option<Vec<&'a str>> {
+    if let Some(start) = path.iter().position(|&job| job == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name);
+        return Some(cycle);
+    }
+    if visited.contains(name) {
+        return None;
+    }
+    path.push(name);
+    for &dependency in graph.get(name).into_iter().flatten() {
+        if let Some(cycle) = find_cycle(graph, dependency, path, visited) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    visited.insert(name);
+    None
+}
+
+impl Query {
+    /// Resolves relative paths in the query configuration against the given directory.
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        let paths = [
+            &mut self.package_path,
+            &mut self.package_cache_path,
+            &mut self.inputs.file,
+            &mut self.schema,
+        ];
+        for path in paths.into_iter().flatten() {
+            *path = dir.join(&*path);
+        }
+        for path in &mut self.font_paths {
+            *path = dir.join(&*path);
+        }
+    }
+}
+
+impl Inputs {
+    /// Reads the inputs file, if one is configured, and adds its inputs to the inline inputs.
+    /// Inline inputs with the same name take precedence.
+    pub async fn load_file(&mut self) -> Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+
+        let content = fs::read_to_string(path)
+            .await
+            .map_err(|source| Error::InputsIo {
+                path: path.clone(),
+                source,
+            })?;
+        let inputs: Result<serde_json::Value, Box<dyn StdError + Send + Sync>> = if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            toml::from_str(&content).map_err(Into::into)
+        } else {
+            serde_json::from_str(&content).map_err(Into::into)
+        };
+        let inputs = inputs.map_err(|source| Error::InputsParse {
+            path: path.clone(),
+            source,
+        })?;
+        let serde_json::Value::Object(inputs) = inputs else {
+            return Err(Error::InputsFormat(path.clone()));
+        };
+
+        for (key, value) in inputs {
+            // Typst inputs are always strings
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            self.values.entry(key).or_insert(value);
+        }
+
+        Ok(())
+    }
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 /// Deserializes the `field` config: if given, must be either a string or `false`.
@@ -153,23 +586,331 @@ where
     deserializer.deserialize_any(FieldVisitor)
 }
 
+/// Deserializes the `selector` config: if given, must be either a string or an array of strings.
+fn deserialize_selector<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct SelectorVisitor;
+
+    impl<'de> Visitor<'de> for SelectorVisitor {
+        type Value = Option<Vec<String>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a string or an array of strings")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.visit_string(v.to_owned())
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(Some(vec![v]))
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            let selectors = Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+            Ok(Some(selectors))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(SelectorVisitor)
+}
+
+/// Deserializes the `fallback` config: if given, must be either a table or `false`.
+fn deserialize_fallback<'de, D>(deserializer: D) -> Result<Option<Option<FallbackInput>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct FallbackVisitor;
+
+    impl<'de> Visitor<'de> for FallbackVisitor {
+        type Value = Option<Option<FallbackInput>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("`false` or a table")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            if v {
+                return Err(E::invalid_value(de::Unexpected::Bool(v), &self));
+            }
+            Ok(Some(None))
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::MapAccess<'de>,
+        {
+            let fallback = FallbackInput::deserialize(de::value::MapAccessDeserializer::new(map))?;
+            Ok(Some(Some(fallback)))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(FallbackVisitor)
+}
+
+/// Deserializes the `query` config. Keys that [Query] doesn't recognize are split out first and
+/// recorded in [Query::unknown_keys].
+fn deserialize_query<'de, D>(deserializer: D) -> Result<Query, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut table = Table::deserialize(deserializer)?;
+    let fields = field_names::<Query>();
+    let unknown_keys: Vec<_> = table
+        .keys()
+        .filter(|key| !fields.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    for key in &unknown_keys {
+        table.remove(key);
+    }
+    let mut query = Query::deserialize(toml::Value::Table(table)).map_err(de::Error::custom)?;
+    query.unknown_keys = unknown_keys;
+    Ok(query)
+}
+
+/// Returns the names of the fields a struct is deserialized from, by deserializing it from a
+/// deserializer that only records the fields it is asked for.
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            Err(de::Error::custom("only structs have field names"))
+        }
+
+        fn deserialize_struct<V>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            *self.0 = fields;
+            Err(de::Error::custom("the field names were recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+            ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    // deserializing always fails, after recording the field names
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Converts a JSON value into the equivalent TOML value. As TOML has no `null`, null values in
+/// objects and arrays are left out.
+fn json_to_toml(value: serde_json::Value) -> toml::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => toml::Value::Table(toml::Table::new()),
+        Value::Bool(value) => toml::Value::Boolean(value),
+        Value::Number(number) => {
+            if let Some(number) = number.as_i64() {
+                toml::Value::Integer(number)
+            } else if let Some(number) = number.as_f64() {
+                toml::Value::Float(number)
+            } else {
+                toml::Value::String(number.to_string())
+            }
+        }
+        Value::String(value) => toml::Value::String(value),
+        Value::Array(array) => {
+            let array = array.into_iter().filter(|value| !value.is_null());
+            toml::Value::Array(array.map(json_to_toml).collect())
+        }
+        Value::Object(object) => {
+            let object = object.into_iter().filter(|(_, value)| !value.is_null());
+            let object = object.map(|(key, value)| (key, json_to_toml(value)));
+            toml::Value::Table(object.collect())
+        }
+    }
+}
+
+/// Expands the references in all strings inside the given TOML value, see
+/// [PrequeryManifest::parse]. `key` is the path to the value, used in error messages.
+fn interpolate_value(value: &mut toml::Value, key: &mut String) -> Result<()> {
+    match value {
+        toml::Value::String(string) => *string = interpolate(string, key)?,
+        toml::Value::Array(array) => {
+            for (i, value) in array.iter_mut().enumerate() {
+                let len = key.len();
+                key.push_str(&format!("[{i}]"));
+                interpolate_value(value, key)?;
+                key.truncate(len);
+            }
+        }
+        toml::Value::Table(table) => interpolate_table(table, key)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expands the references in all string values of a table, see [interpolate_value].
+fn interpolate_table(table: &mut toml::Table, key: &mut String) -> Result<()> {
+    for (name, value) in table.iter_mut() {
+        let len = key.len();
+        if !key.is_empty() {
+            key.push('.');
+        }
+        key.push_str(name);
+        interpolate_value(value, key)?;
+        key.truncate(len);
+    }
+    Ok(())
+}
+
+/// Expands the references in a single string, see [PrequeryManifest::parse].
+fn interpolate(string: &str, key: &str) -> Result<String> {
+    let mut result = String::with_capacity(string.len());
+    let mut rest = string;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let unresolved = |reference: &str| Error::Interpolation {
+                key: key.to_string(),
+                reference: reference.to_string(),
+            };
+            let end = after.find('}').ok_or_else(|| unresolved(after))?;
+            let reference = &after[..end];
+            let expanded = if reference == "root" {
+                ARGS.resolve_root().to_string_lossy().into_owned()
+            } else if let Some(var) = reference.strip_prefix("env:") {
+                std::env::var(var).map_err(|_| unresolved(reference))?
+            } else {
+                return Err(unresolved(reference));
+            };
+            result.push_str(&expanded);
+            rest = &after[end + 1..];
+        } else {
+            // a `$` that doesn't start a reference is kept as is
+            result.push('$');
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
 mod error {
+    use std::error::Error as StdError;
     use std::io;
+    use std::path::PathBuf;
 
     use thiserror::Error;
 
     /// Errors that can occur when reading a prequery manifest
     #[derive(Error, Debug)]
     pub enum Error {
-        /// An I/O error occurred reading the typst.toml file
-        #[error("typst.toml file could not be read")]
+        /// An I/O error occurred reading the manifest file
+        #[error("manifest file could not be read")]
         Io(#[from] io::Error),
         /// The prequery section is missing in typst.toml
         #[error("typst.toml does not contain `tool.prequery` section")]
         Missing,
+        /// The `prequery` section is missing in a standalone manifest file
+        #[error("manifest file does not contain a top-level `prequery` section")]
+        MissingStandalone,
+        /// A standalone JSON manifest file is not valid JSON
+        #[error("manifest file is not valid JSON")]
+        Json(#[from] serde_json::Error),
+        /// A string in the prequery section references an unknown or unset value
+        #[error("`{key}` contains the unresolved reference `${{{reference}}}`")]
+        Interpolation {
+            /// The key of the string containing the reference
+            key: String,
+            /// The reference, e.g. `env:VAR`
+            reference: String,
+        },
+        /// A manifest fragment could not be read
+        #[error("manifest fragment {} could not be read", .path.display())]
+        IncludeIo {
+            /// The included file
+            path: PathBuf,
+            /// The I/O error
+            #[source]
+            source: io::Error,
+        },
+        /// A manifest fragment is not a valid list of jobs
+        #[error("manifest fragment {} does not contain a valid list of jobs", .path.display())]
+        IncludeParse {
+            /// The included file
+            path: PathBuf,
+            /// The parsing error
+            #[source]
+            source: Box<dyn StdError + Send + Sync>,
+        },
+        /// A manifest fragment includes itself, directly or through other fragments
+        #[error("manifest fragment {} includes itself", .0.display())]
+        RecursiveInclude(PathBuf),
         /// The prequery section contains invalid config data
-        #[error("typst.toml contains `tool.prequery` key, but it's not a valid preprocessor configuration")]
+        #[error("the prequery section is not a valid preprocessor configuration")]
         Invalid(#[from] toml::de::Error),
+        /// An inputs file could not be read
+        #[error("inputs file {} could not be read", .path.display())]
+        InputsIo {
+            /// The inputs file
+            path: PathBuf,
+            /// The I/O error
+            #[source]
+            source: io::Error,
+        },
+        /// An inputs file is not valid JSON or TOML
+        #[error("inputs file {} is not valid JSON or TOML", .path.display())]
+        InputsParse {
+            /// The inputs file
+            path: PathBuf,
+            /// The parsing error
+            #[source]
+            source: Box<dyn StdError + Send + Sync>,
+        },
+        /// An inputs file does not contain a table of inputs
+        #[error("inputs file {} does not contain a table of inputs", .0.display())]
+        InputsFormat(PathBuf),
     }
 
     /// Result type alias that defaults error to [Error].
@@ -0,0 +1,310 @@
+//! Loading preprocessors from dynamic libraries
+//!
+//! Plugins are dynamic libraries in the directory given by `--plugins`. Each plugin exports a
+//! [PluginDeclaration] under the symbol `prequery_plugin_declaration`. The declaration's
+//! `abi_version` must be [ABI_VERSION]; plugins with a different version are skipped with a
+//! warning, as are libraries that can't be loaded or don't export a declaration.
+//!
+//! A plugin job's query is executed by `prequery-preprocess`; the plugin receives the job's name,
+//! its configuration and the query result, each as a null-terminated UTF-8 string (the latter two
+//! in JSON format).
+//!
+//! Plugins only implement actual runs: with `--dry-run`, `--dump-commands`, `--verify-tls-only` or
+//! `--print-resolved-paths`, the plugin is not called, and the job is handled by
+//! `prequery-preprocess` as far as possible.
+//!
+//! # Safety and stability
+//!
+//! Loading a plugin executes arbitrary code in this process, so only plugins from trusted sources
+//! should be loaded. The ABI is not checked beyond its version number: a plugin that declares the
+//! right version but was built against a different definition of the declaration has undefined
+//! behavior. The ABI only uses C types, so plugins don't need to be built with the same Rust
+//! compiler version as this tool, or with Rust at all. Plugins are never unloaded.
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString, OsStr};
+use std::path::Path;
+
+use async_trait::async_trait;
+use libloading::Library;
+use once_cell::sync::Lazy;
+
+use crate::args::ARGS;
+use crate::manifest;
+use crate::preprocessor::{
+    self, BoxedPreprocessor, ConfigResult, ManifestError, Preprocessor, PreprocessorFactory,
+};
+use crate::query::Query;
+
+pub use error::*;
+
+/// The version of the plugin ABI implemented by this version of `prequery-preprocess`. It is
+/// incremented whenever [PluginDeclaration] changes.
+pub const ABI_VERSION: u32 = 1;
+
+/// The name of the symbol under which a plugin exports its [PluginDeclaration]
+pub const DECLARATION_SYMBOL: &[u8] = b"prequery_plugin_declaration";
+
+/// The declaration of a plugin's preprocessor. All strings are null-terminated and UTF-8 encoded.
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// Must be [ABI_VERSION]
+    pub abi_version: u32,
+    /// The preprocessor's identifier, referenced by the [manifest::Job::kind] field
+    pub kind: *const c_char,
+    /// The selector queried if the job doesn't configure one; may be null if there is no default
+    pub default_selector: *const c_char,
+    /// Executes a job, given the job's name, its configuration and the query result. Returns null
+    /// on success, or an error message that is later passed to `free_error`. This function may be
+    /// called concurrently from multiple threads.
+    pub run: unsafe extern "C" fn(
+        name: *const c_char,
+        config: *const c_char,
+        data: *const c_char,
+    ) -> *mut c_char,
+    /// Frees an error message returned by `run`
+    pub free_error: unsafe extern "C" fn(error: *mut c_char),
+}
+
+/// A loaded plugin
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    kind: &'static str,
+    default_selector: Option<String>,
+    run: unsafe extern "C" fn(*const c_char, *const c_char, *const c_char) -> *mut c_char,
+    free_error: unsafe extern "C" fn(*mut c_char),
+}
+
+/// All plugins from the plugins directory, by kind
+static PLUGINS: Lazy<HashMap<&'static str, Plugin>> = Lazy::new(|| {
+    let Some(dir) = &ARGS.plugins else {
+        return HashMap::new();
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            let dir_str = dir.to_string_lossy();
+            eprintln!("warning: plugins directory {dir_str} could not be read: {error}");
+            return HashMap::new();
+        }
+    };
+
+    let mut plugins = HashMap::new();
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if !is_library(&path) {
+            continue;
+        }
+        match Plugin::load(&path) {
+            Ok(plugin) => {
+                plugins.insert(plugin.kind, plugin);
+            }
+            Err(error) => {
+                let path_str = path.to_string_lossy();
+                eprintln!("warning: plugin {path_str} was not loaded: {error}");
+            }
+        }
+    }
+    plugins
+});
+
+/// Looks up the plugin for the given job kind.
+pub fn get(kind: &str) -> Option<&'static (dyn PreprocessorFactory + Sync)> {
+    let plugin = PLUGINS.get(kind)?;
+    Some(plugin)
+}
+
+/// Returns the kinds of all loaded plugins.
+pub fn kinds() -> impl Iterator<Item = &'static str> {
+    PLUGINS.keys().copied()
+}
+
+/// Checks whether the file looks like a dynamic library on this platform.
+fn is_library(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(std::env::consts::DLL_EXTENSION))
+}
+
+impl Plugin {
+    /// Loads the plugin from the given library, checking its ABI version.
+    fn load(path: &Path) -> Result<Self, LoadError> {
+        // SAFETY: loading a library runs its initialization code; plugins must be trusted
+        let library = unsafe { Library::new(path)? };
+        // plugins are never unloaded, so the declaration stays valid
+        let library: &'static Library = Box::leak(Box::new(library));
+
+        // SAFETY: the symbol is required to have this type; only its version can be checked
+        let declaration = unsafe {
+            let symbol = library.get::<*const PluginDeclaration>(DECLARATION_SYMBOL)?;
+            &**symbol
+        };
+        if declaration.abi_version != ABI_VERSION {
+            return Err(LoadError::Version(declaration.abi_version));
+        }
+
+        // SAFETY: the declaration's strings are required to be null-terminated
+        let string = |ptr: *const c_char| -> Result<Option<String>, LoadError> {
+            if ptr.is_null() {
+                return Ok(None);
+            }
+            let string = unsafe { CStr::from_ptr(ptr) }.to_str()?;
+            Ok(Some(string.to_string()))
+        };
+        let kind = string(declaration.kind)?.ok_or(LoadError::Kind)?;
+        let default_selector = string(declaration.default_selector)?;
+
+        Ok(Self {
+            kind: Box::leak(kind.into_boxed_str()),
+            default_selector,
+            run: declaration.run,
+            free_error: declaration.free_error,
+        })
+    }
+
+    /// Calls the plugin's `run` function.
+    fn run(&self, name: &str, config: &str, data: &str) -> Result<(), PluginError> {
+        let name = CString::new(name)?;
+        let config = CString::new(config)?;
+        let data = CString::new(data)?;
+
+        // SAFETY: the arguments are valid strings that outlive the call
+        let error = unsafe { (self.run)(name.as_ptr(), config.as_ptr(), data.as_ptr()) };
+        if error.is_null() {
+            return Ok(());
+        }
+
+        // SAFETY: the plugin returned a valid string, which is freed exactly once
+        let message = unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { (self.free_error)(error) };
+        Err(PluginError::Failed(message))
+    }
+}
+
+impl PreprocessorFactory for Plugin {
+    fn configure(
+        &self,
+        name: String,
+        manifest: toml::Table,
+        query: manifest::Query,
+    ) -> ConfigResult<BoxedPreprocessor> {
+        let inner = || {
+            let mut builder = Query::builder()
+                .job(&name)
+                .default_field(Some("value".to_string()))
+                .default_one(false);
+            if let Some(selector) = &self.default_selector {
+                builder = builder.default_selector(selector.clone());
+            }
+            let query = builder.build(query)?;
+            let config = serde_json::to_string(&manifest)?;
+            Ok::<_, ConfigError>(PluginPreprocessor {
+                name,
+                plugin: self.clone(),
+                config,
+                query,
+            })
+        };
+        let preprocessor = inner().map_err(|error| ManifestError::new(self.kind, error))?;
+        Ok(Box::new(preprocessor))
+    }
+}
+
+/// A job executed by a plugin
+#[derive(Debug)]
+struct PluginPreprocessor {
+    name: String,
+    plugin: Plugin,
+    config: String,
+    query: Query,
+}
+
+impl PluginPreprocessor {
+    async fn run_impl(&self) -> Result<(), PluginError> {
+        let data = self.query.query_value().await?;
+        let data = data.to_string();
+
+        let plugin = self.plugin.clone();
+        let name = self.name.clone();
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || plugin.run(&name, &config, &data)).await??;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Preprocessor for PluginPreprocessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        // plugins can't report the changes they would make, their network access or the files
+        // they write, so they don't support any other modes
+        Some(self.query.shell_command())
+    }
+
+    async fn run(&mut self) -> preprocessor::ExecutionResult<()> {
+        self.run_impl()
+            .await
+            .map_err(preprocessor::ExecutionError::new)?;
+        Ok(())
+    }
+}
+
+mod error {
+    use std::ffi::NulError;
+    use std::str::Utf8Error;
+
+    use thiserror::Error;
+    use tokio::task::JoinError;
+
+    use crate::query;
+
+    /// An error loading a plugin
+    #[derive(Error, Debug)]
+    pub enum LoadError {
+        /// The library could not be loaded, or does not export a declaration
+        #[error("the library or its declaration could not be loaded")]
+        Library(#[from] libloading::Error),
+        /// The plugin was built for a different ABI version
+        #[error(
+            "the plugin has ABI version {0}, but version {} is required",
+            super::ABI_VERSION
+        )]
+        Version(u32),
+        /// The plugin's declaration contains a string that is not valid UTF-8
+        #[error("the plugin declaration contains invalid UTF-8")]
+        Utf8(#[from] Utf8Error),
+        /// The plugin's declaration does not specify a kind
+        #[error("the plugin declaration does not specify a kind")]
+        Kind,
+    }
+
+    /// A problem with the configuration of a plugin job
+    #[derive(Error, Debug)]
+    pub enum ConfigError {
+        /// An option without a default value was not given
+        #[error("invalid plugin query configuration")]
+        Query(#[from] query::QueryBuilderError),
+        /// The configuration could not be passed to the plugin
+        #[error("the plugin configuration could not be converted to JSON")]
+        Json(#[from] serde_json::Error),
+    }
+
+    /// An error during a plugin job's execution
+    #[derive(Error, Debug)]
+    pub enum PluginError {
+        /// An error while executing the job's query
+        #[error(transparent)]
+        Query(#[from] query::Error),
+        /// A string passed to the plugin contained a null byte
+        #[error("a string passed to the plugin contains a null byte")]
+        Nul(#[from] NulError),
+        /// The plugin reported an error
+        #[error("the plugin failed: {0}")]
+        Failed(String),
+        /// An error while waiting for the plugin to finish
+        #[error("waiting for the plugin failed")]
+        Join(#[from] JoinError),
+    }
+}
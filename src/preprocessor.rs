@@ -1,22 +1,110 @@
 //! APIs for the implementation of preprocessors, and preprocessor management
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use itertools::Itertools;
 use once_cell::sync::Lazy;
 
+use crate::args::ARGS;
 use crate::manifest;
+use crate::report::Counts;
+use cache::CachedPreprocessor;
 pub use error::{ConfigError, ConfigResult, ExecutionError, ExecutionResult, ManifestError};
 
+mod cache;
+
+/// What running the jobs does, as selected by the command line arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// The jobs are run normally
+    Run,
+    /// `--dry-run`: the jobs report the changes they would make
+    DryRun,
+    /// `--dump-commands`: the jobs print shell commands that do their work
+    DumpCommands,
+    /// `--print-resolved-paths`: the jobs print the paths of the files they write
+    PrintResolvedPaths,
+    /// `--verify-tls-only`: the jobs check the TLS certificates of the hosts they access
+    VerifyTls,
+    /// `--check-complete`: the jobs check that the files they write are present
+    CheckComplete,
+}
+
+impl Mode {
+    /// The mode selected by the command line arguments. The options selecting the modes conflict
+    /// with each other, so at most one of them is given.
+    pub fn current() -> Self {
+        if ARGS.dry_run.is_some() {
+            Self::DryRun
+        } else if ARGS.dump_commands.is_some() {
+            Self::DumpCommands
+        } else if ARGS.print_resolved_paths {
+            Self::PrintResolvedPaths
+        } else if ARGS.verify_tls_only {
+            Self::VerifyTls
+        } else if ARGS.check_complete {
+            Self::CheckComplete
+        } else {
+            Self::Run
+        }
+    }
+}
+
 /// A configured preprocessor that can be executed for its side effect
 #[async_trait]
 pub trait Preprocessor {
     /// this preprocessor's name, which normally comes from [manifest::Job::name].
     fn name(&self) -> &str;
 
-    /// Executes this preprocessor
+    /// Executes this preprocessor. Apart from [Mode::Run], this is only called in the modes the
+    /// preprocessor [supports](Preprocessor::supports).
     async fn run(&mut self) -> ExecutionResult<()>;
+
+    /// Whether this preprocessor does anything in the given mode other than [Mode::Run]. If not,
+    /// the job is not run and only reports that there is nothing to do, or for
+    /// [Mode::DumpCommands], prints its [shell_command](Preprocessor::shell_command).
+    fn supports(&self, _mode: Mode) -> bool {
+        false
+    }
+
+    /// The shell command that does this preprocessor's work, for `--dump-commands`. This is not
+    /// used if the preprocessor supports [Mode::DumpCommands] itself.
+    fn shell_command(&self) -> Option<String> {
+        None
+    }
+
+    /// The files this preprocessor depends on, such as templates or the files it writes. These
+    /// don't need to include the queried document or the manifest. When watching, the job is run
+    /// again when one of these files changes. The result may change after running the job.
+    fn dependencies(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Counts of the items processed during the last run, for reporting.
+    fn counts(&self) -> Counts {
+        Counts::default()
+    }
+
+    /// The concurrency chosen for each host by adaptive tuning (`--concurrency-per-host auto`),
+    /// for reporting.
+    fn host_concurrency(&self) -> BTreeMap<String, usize> {
+        BTreeMap::new()
+    }
+
+    /// The names of the jobs that need to finish before this one is run, from
+    /// [manifest::Job::depends_on].
+    fn depends_on(&self) -> &[String] {
+        &[]
+    }
+
+    /// Cleans up after a run that was cancelled, e.g. because the job exceeded its
+    /// [manifest::Job::job_timeout]. The run's work in progress has already been interrupted, and
+    /// may have left e.g. partially written files behind.
+    async fn abort(&mut self) {}
 }
 
 /// A dynamically dispatched, boxed preprocessor
@@ -56,6 +144,9 @@ pub trait PreprocessorDefinition {
     /// The identifier of the preprocessor, referenced by the [manifest::Job::kind] field
     const NAME: &'static str;
 
+    /// A one-line description of what the preprocessor does, shown by `--list-kinds`
+    const DESCRIPTION: &'static str;
+
     /// The specific error type for this preprocessor
     type Error: Error + Send + Sync + 'static;
 
@@ -77,21 +168,56 @@ pub trait PreprocessorDefinition {
         manifest: toml::Table,
         query: manifest::Query,
     ) -> Result<BoxedPreprocessor, Self::Error>;
+
+    /// Transforms the raw query result before it is deserialized into the preprocessor's data
+    /// type. This can accommodate documents whose metadata has a slightly different shape, e.g.
+    /// a single object instead of an array. By default, the data is not changed.
+    fn transform_query_data(data: serde_json::Value) -> serde_json::Value {
+        data
+    }
+}
+
+/// A preprocessor in the [PREPROCESSORS] map
+#[derive(Clone, Copy)]
+struct Registration {
+    description: &'static str,
+    factory: &'static (dyn PreprocessorFactory + Sync),
 }
 
-type PreprocessorMap = HashMap<&'static str, &'static (dyn PreprocessorFactory + Sync)>;
+type PreprocessorMap = HashMap<&'static str, Registration>;
 
 /// Map of preprocessors defined in this crate
 static PREPROCESSORS: Lazy<PreprocessorMap> = Lazy::new(|| {
     fn register<T: PreprocessorDefinition + 'static>(map: &mut PreprocessorMap) {
-        map.insert(T::NAME, &T::configure);
+        let registration = Registration {
+            description: T::DESCRIPTION,
+            factory: &T::configure,
+        };
+        map.insert(T::NAME, registration);
     }
 
     let mut map = HashMap::new();
+    register::<crate::bibliography::BibliographyFactory>(&mut map);
+    register::<crate::template::TemplateFactory>(&mut map);
+    register::<crate::typst_export::TypstExportFactory>(&mut map);
+    register::<crate::web_resource::FontsFactory>(&mut map);
     register::<crate::web_resource::WebResourceFactory>(&mut map);
     map
 });
 
+/// Returns the kinds of all available preprocessors, including those loaded from plugins, with a
+/// description of each. The kinds are sorted by name.
+pub fn kinds() -> Vec<(&'static str, &'static str)> {
+    let kinds = PREPROCESSORS
+        .iter()
+        .map(|(&kind, registration)| (kind, registration.description));
+    #[cfg(feature = "plugins")]
+    let kinds = kinds.chain(crate::plugin::kinds().map(|kind| (kind, "(plugin)")));
+    let mut kinds: Vec<_> = kinds.collect();
+    kinds.sort_unstable();
+    kinds
+}
+
 /// looks up the preprocessor according to [manifest::Job::kind] and returns the name and result of
 /// creating the preprocessor. The creation may fail if the kind is not recognized, or some part of
 /// the manifest was not valid for that kind.
@@ -99,25 +225,180 @@ pub fn get_preprocessor(job: manifest::Job) -> Result<BoxedPreprocessor, (String
     let manifest::Job {
         name,
         kind,
-        query,
+        enabled: _,
+        depends_on,
+        mut query,
+        format,
+        env,
+        clear_env,
+        job_timeout,
+        cache_key,
         manifest,
     } = job;
     let inner = || {
-        let Some(preprocessor) = PREPROCESSORS.get(kind.as_str()) else {
+        match (format, query.format) {
+            (Some(format), Some(query_format)) if format != query_format => {
+                return Err(ConfigError::Format {
+                    format,
+                    query_format,
+                });
+            }
+            (Some(format), _) => query.format = Some(format),
+            _ => {}
+        }
+        for (key, value) in env {
+            query.env.entry(key).or_insert(value);
+        }
+        query.clear_env |= clear_env;
+
+        let unknown_keys = std::mem::take(&mut query.unknown_keys);
+        if !unknown_keys.is_empty() {
+            let keys = unknown_keys.iter().map(|key| format!("`{key}`")).join(", ");
+            if ARGS.warnings_as_errors {
+                return Err(ConfigError::UnknownQueryKeys(keys));
+            }
+            eprintln!("[{name}] warning: unknown query keys {keys}");
+        }
+
+        let preprocessor = PREPROCESSORS
+            .get(kind.as_str())
+            .map(|registration| registration.factory);
+        #[cfg(feature = "plugins")]
+        let preprocessor = preprocessor.or_else(|| crate::plugin::get(&kind));
+        let Some(preprocessor) = preprocessor else {
             return Err(ConfigError::Unknown(kind));
         };
-        let preprocessor = preprocessor.configure(name.clone(), manifest, query)?;
+        let fingerprint = cache_key
+            .map(|key| cache::fingerprint(&name, &kind, &key, &query, &manifest))
+            .transpose()?;
+        let mut preprocessor = preprocessor.configure(name.clone(), manifest, query)?;
+        if let Some(timeout) = job_timeout {
+            let timeout = Duration::from_secs(timeout);
+            preprocessor = Box::new(TimedPreprocessor {
+                inner: preprocessor,
+                timeout,
+            });
+        }
+        if let Some(fingerprint) = fingerprint {
+            preprocessor = Box::new(CachedPreprocessor::new(preprocessor, fingerprint));
+        }
+        if !depends_on.is_empty() {
+            preprocessor = Box::new(DependentPreprocessor {
+                inner: preprocessor,
+                depends_on,
+            });
+        }
         Ok(preprocessor)
     };
     inner().map_err(|error| (name, error))
 }
 
+/// A preprocessor whose runs are bounded by the job's [manifest::Job::job_timeout]
+struct TimedPreprocessor {
+    inner: BoxedPreprocessor,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl Preprocessor for TimedPreprocessor {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn run(&mut self) -> ExecutionResult<()> {
+        match tokio::time::timeout(self.timeout, self.inner.run()).await {
+            Ok(result) => result,
+            Err(_) => {
+                // the run's future was dropped, which cancelled its remaining work
+                self.inner.abort().await;
+                Err(ExecutionError::Timeout(self.timeout))
+            }
+        }
+    }
+
+    fn supports(&self, mode: Mode) -> bool {
+        self.inner.supports(mode)
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        self.inner.shell_command()
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.inner.dependencies()
+    }
+
+    fn counts(&self) -> Counts {
+        self.inner.counts()
+    }
+
+    fn host_concurrency(&self) -> BTreeMap<String, usize> {
+        self.inner.host_concurrency()
+    }
+
+    fn depends_on(&self) -> &[String] {
+        self.inner.depends_on()
+    }
+
+    async fn abort(&mut self) {
+        self.inner.abort().await;
+    }
+}
+
+/// A preprocessor that is only run after the jobs in [manifest::Job::depends_on]
+struct DependentPreprocessor {
+    inner: BoxedPreprocessor,
+    depends_on: Vec<String>,
+}
+
+#[async_trait]
+impl Preprocessor for DependentPreprocessor {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn run(&mut self) -> ExecutionResult<()> {
+        self.inner.run().await
+    }
+
+    fn supports(&self, mode: Mode) -> bool {
+        self.inner.supports(mode)
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        self.inner.shell_command()
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.inner.dependencies()
+    }
+
+    fn counts(&self) -> Counts {
+        self.inner.counts()
+    }
+
+    fn host_concurrency(&self) -> BTreeMap<String, usize> {
+        self.inner.host_concurrency()
+    }
+
+    fn depends_on(&self) -> &[String] {
+        &self.depends_on
+    }
+
+    async fn abort(&mut self) {
+        self.inner.abort().await;
+    }
+}
+
 mod error {
     use std::error::Error;
+    use std::time::Duration;
 
     use thiserror::Error;
     use tokio::task::JoinError;
 
+    use crate::query::QueryFormat;
+
     /// A problem with the preprocessor's configuration.
     #[derive(Error, Debug)]
     pub enum ConfigError {
@@ -127,6 +408,26 @@ mod error {
         /// The manifest is invalid for the specific preprocessor
         #[error("invalid job config")]
         Manifest(#[from] ManifestError),
+        /// The job's format doesn't match the format configured for its query
+        #[error("the job's format is {format:?}, but its query's format is {query_format:?}")]
+        Format {
+            /// The job's format
+            format: QueryFormat,
+            /// The query's format
+            query_format: QueryFormat,
+        },
+        /// The job depends on a job that doesn't exist
+        #[error("the job depends on the unknown job `{0}`")]
+        UnknownDependency(String),
+        /// The job depends on itself, directly or through other jobs
+        #[error("the job's dependencies form a cycle: {0}")]
+        DependencyCycle(String),
+        /// The job's query config contains keys that are not recognized
+        #[error("the job's query config contains the unknown keys {0}")]
+        UnknownQueryKeys(String),
+        /// The job's `cache_key` could not be rendered
+        #[error("the job's cache key could not be rendered")]
+        CacheKey(#[from] handlebars::RenderError),
     }
 
     /// A problem with the preprocessor's configuration
@@ -155,6 +456,12 @@ mod error {
         /// An error while waiting for the job to finish
         #[error("waiting for a job failed")]
         Join(#[from] JoinError),
+        /// The job exceeded its `job_timeout` and was cancelled
+        #[error("the job did not finish within {0:?}")]
+        Timeout(Duration),
+        /// The job was not run because a job it depends on failed
+        #[error("the job was not run because the job `{0}` it depends on failed")]
+        Dependency(String),
     }
 
     impl ExecutionError {
@@ -162,6 +469,11 @@ mod error {
         pub fn new<E: Error + Send + Sync + 'static>(source: E) -> Self {
             Self::Execution(Box::new(source))
         }
+
+        /// Returns true if the job was cancelled because it exceeded its `job_timeout`
+        pub fn is_timeout(&self) -> bool {
+            matches!(self, Self::Timeout(_))
+        }
     }
 
     /// A result with a config error in it
@@ -0,0 +1,251 @@
+//! A cache of jobs' results on disk (`cache_key`), so that jobs can be skipped in later runs, e.g.
+//! in CI with a restored cache directory.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::args::ARGS;
+use crate::manifest;
+use crate::report::Counts;
+use crate::utils::status;
+
+use super::{BoxedPreprocessor, ConfigError, ExecutionResult, Mode, Preprocessor};
+
+/// The name of the file describing a cache entry, in the entry's directory
+const ENTRY_FILE: &str = "entry.json";
+
+/// The directory containing the cached files, in the entry's directory
+const FILES_DIR: &str = "files";
+
+/// The data available when rendering a cache key
+#[derive(Serialize)]
+struct KeyData<'a> {
+    inputs: &'a BTreeMap<String, String>,
+    env: HashMap<String, String>,
+}
+
+/// A cached result of a job
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Entry {
+    /// The files the job read, relative to the project root, with the SHA-256 hashes of their
+    /// contents. The entry is only used while these are unchanged.
+    inputs: BTreeMap<PathBuf, String>,
+    /// The files the job wrote, relative to the project root. Their contents are stored in the
+    /// entry's `files` directory.
+    outputs: Vec<PathBuf>,
+}
+
+/// Renders the job's cache key and combines it with the job's configuration, so that changing
+/// either uses a different cache entry.
+pub(super) fn fingerprint(
+    name: &str,
+    kind: &str,
+    key: &str,
+    query: &manifest::Query,
+    manifest: &toml::Table,
+) -> Result<String, ConfigError> {
+    // the inputs are sorted, so that the fingerprint is the same in every run. Like in queries,
+    // inputs from the command line have the lowest precedence
+    let mut query = query.clone();
+    let mut inputs: BTreeMap<_, _> = ARGS.inputs.iter().cloned().collect();
+    inputs.extend(std::mem::take(&mut query.inputs.values));
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let data = KeyData {
+        inputs: &inputs,
+        env: std::env::vars().collect(),
+    };
+    let key = handlebars.render_template(key, &data)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "{name}\0{kind}\0{key}\0{query:?}\0{inputs:?}\0{manifest}"
+    ));
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// A preprocessor whose results are cached under the job's [manifest::Job::cache_key]
+pub(super) struct CachedPreprocessor {
+    inner: BoxedPreprocessor,
+    /// Identifies the job's cache key and configuration
+    fingerprint: String,
+    /// The counts of the last run, if its result was restored from the cache
+    restored: Option<Counts>,
+}
+
+impl CachedPreprocessor {
+    pub fn new(inner: BoxedPreprocessor, fingerprint: String) -> Self {
+        Self {
+            inner,
+            fingerprint,
+            restored: None,
+        }
+    }
+
+    /// The directory of the job's cache entry
+    fn location(&self) -> PathBuf {
+        ARGS.resolve_root()
+            .join(&ARGS.job_cache)
+            .join(&self.fingerprint)
+    }
+
+    /// The job's dependencies inside the project root, relative to it
+    fn relative_dependencies(&self) -> Vec<PathBuf> {
+        let root = ARGS.resolve_root();
+        self.inner
+            .dependencies()
+            .into_iter()
+            .filter_map(|path| Some(path.strip_prefix(root).ok()?.to_path_buf()))
+            .collect()
+    }
+}
+
+/// Hashes the given files relative to the project root, skipping files that don't exist.
+async fn hash_files(paths: Vec<PathBuf>) -> BTreeMap<PathBuf, String> {
+    let root = ARGS.resolve_root();
+    let mut hashes = BTreeMap::new();
+    for path in paths {
+        if let Some(hash) = hash_file(&root.join(&path)).await {
+            hashes.insert(path, hash);
+        }
+    }
+    hashes
+}
+
+/// Restores the files of the cache entry at the given location, if there is an entry whose input
+/// files are unchanged. Returns the number of restored files.
+async fn restore(location: &Path) -> Option<usize> {
+    let entry = fs::read(location.join(ENTRY_FILE)).await.ok()?;
+    let entry: Entry = serde_json::from_slice(&entry).ok()?;
+    let root = ARGS.resolve_root();
+    for (path, hash) in &entry.inputs {
+        if hash_file(&root.join(path)).await.as_ref() != Some(hash) {
+            return None;
+        }
+    }
+    for path in &entry.outputs {
+        let target = root.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await.ok()?;
+        }
+        fs::copy(location.join(FILES_DIR).join(path), &target)
+            .await
+            .ok()?;
+    }
+    Some(entry.outputs.len())
+}
+
+/// Stores the files the job wrote in the cache entry at the given location, i.e. the job's
+/// dependencies that were created or changed since the given hashes were taken. The other
+/// dependencies are recorded as the job's inputs.
+async fn store(
+    location: &Path,
+    dependencies: Vec<PathBuf>,
+    before: &BTreeMap<PathBuf, String>,
+) -> io::Result<()> {
+    let root = ARGS.resolve_root();
+    // an existing entry is outdated, since its inputs didn't match
+    if fs::try_exists(location).await? {
+        fs::remove_dir_all(location).await?;
+    }
+    let mut entry = Entry::default();
+    for path in dependencies {
+        let Some(hash) = hash_file(&root.join(&path)).await else {
+            continue;
+        };
+        if before.get(&path) == Some(&hash) {
+            entry.inputs.insert(path, hash);
+            continue;
+        }
+        let cached = location.join(FILES_DIR).join(&path);
+        if let Some(parent) = cached.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::copy(root.join(&path), cached).await?;
+        entry.outputs.push(path);
+    }
+    let entry = serde_json::to_vec_pretty(&entry).expect("the entry should be serializable");
+    fs::create_dir_all(location).await?;
+    fs::write(location.join(ENTRY_FILE), entry).await
+}
+
+async fn hash_file(path: &Path) -> Option<String> {
+    let content = fs::read(path).await.ok()?;
+    Some(format!("{:x}", Sha256::digest(content)))
+}
+
+#[async_trait]
+impl Preprocessor for CachedPreprocessor {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn run(&mut self) -> ExecutionResult<()> {
+        self.restored = None;
+        // in these modes, the job doesn't produce its usual results
+        if Mode::current() != Mode::Run {
+            return self.inner.run().await;
+        }
+
+        let location = self.location();
+        if let Some(restored) = restore(&location).await {
+            status!(
+                "[{}] Restored {restored} files from the job cache",
+                self.name()
+            );
+            self.restored = Some(Counts {
+                skipped: restored,
+                ..Counts::default()
+            });
+            return Ok(());
+        }
+
+        let before = hash_files(self.relative_dependencies()).await;
+        self.inner.run().await?;
+        // failing to store the result is not an error; the job is simply run again next time
+        let dependencies = self.relative_dependencies();
+        if let Err(error) = store(&location, dependencies, &before).await {
+            eprintln!(
+                "warning: storing the result of job {} in the job cache failed: {error}",
+                self.name()
+            );
+        }
+        Ok(())
+    }
+
+    fn supports(&self, mode: Mode) -> bool {
+        self.inner.supports(mode)
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        self.inner.shell_command()
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.inner.dependencies()
+    }
+
+    fn counts(&self) -> Counts {
+        self.restored.unwrap_or_else(|| self.inner.counts())
+    }
+
+    fn host_concurrency(&self) -> BTreeMap<String, usize> {
+        self.inner.host_concurrency()
+    }
+
+    fn depends_on(&self) -> &[String] {
+        self.inner.depends_on()
+    }
+
+    async fn abort(&mut self) {
+        self.inner.abort().await;
+    }
+}
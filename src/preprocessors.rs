@@ -1,3 +1,6 @@
 //! The actual preprocessors
 
+pub mod bibliography;
+pub mod template;
+pub mod typst_export;
 pub mod web_resource;
@@ -0,0 +1,294 @@
+//! The `bibliography` preprocessor
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{self, PathBuf};
+
+use async_trait::async_trait;
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+use tokio::fs;
+use url::Url;
+
+use crate::args::ARGS;
+use crate::preprocessor::{self, Mode, Preprocessor, PreprocessorDefinition};
+use crate::query::{self, Query};
+use crate::report::{error_chain, Counts};
+use crate::utils::status;
+use crate::web_resource::CLIENT;
+
+mod error;
+mod factory;
+mod index;
+mod manifest;
+
+use index::*;
+use manifest::*;
+
+pub use error::*;
+pub use factory::BibliographyFactory;
+
+/// Prefixes with which DOIs are commonly written, which are removed before resolving them
+const DOI_PREFIXES: &[&str] = &[
+    "https://doi.org/",
+    "http://doi.org/",
+    "https://dx.doi.org/",
+    "http://dx.doi.org/",
+    "doi:",
+];
+
+/// The `bibliography` preprocessor
+#[derive(Debug)]
+pub struct Bibliography {
+    name: String,
+    manifest: Manifest,
+    query: Query,
+    counts: Counts,
+}
+
+impl Bibliography {
+    pub(crate) fn new(name: String, manifest: Manifest, query: Query) -> Self {
+        Self {
+            name,
+            manifest,
+            query,
+            counts: Counts::default(),
+        }
+    }
+
+    /// Queries the document for DOIs. Common prefixes are removed, and each DOI is only returned
+    /// once, in the order of first occurrence.
+    async fn query_dois(&self) -> ExecutionResult<Vec<String>> {
+        let data = self.query.query_value().await?;
+        let data = BibliographyFactory::transform_query_data(data);
+        let data = Vec::<String>::deserialize(data).map_err(query::Error::from)?;
+        let mut dois = Vec::new();
+        for doi in data {
+            let doi = doi.trim();
+            let doi = DOI_PREFIXES
+                .iter()
+                .find_map(|prefix| doi.strip_prefix(prefix))
+                .unwrap_or(doi)
+                .to_string();
+            if !dois.contains(&doi) {
+                dois.push(doi);
+            }
+        }
+        Ok(dois)
+    }
+
+    async fn read_index(&self) -> ExecutionResult<Option<Index>> {
+        let Some(location) = self.manifest.resolve_index_path().await else {
+            return Ok(None);
+        };
+        let location = location.map_err(IndexError::from)?;
+        let index = if fs::try_exists(&location).await.map_err(IndexError::from)? {
+            let (index, restored) = Index::read(location).await?;
+            if let Some(restored) = restored {
+                if ARGS.warnings_as_errors {
+                    return Err(restored.error.into());
+                }
+                eprintln!("[{}] warning: {restored}", self.name);
+            }
+            index
+        } else {
+            Index::new(location)
+        };
+        Ok(Some(index))
+    }
+
+    /// Returns the URL for resolving the given DOI. The DOI's parts are appended to the resolver's
+    /// path as segments, so that characters such as `#` or `?` are percent-encoded.
+    fn url(&self, doi: &str) -> Url {
+        let mut url =
+            Url::parse(&self.manifest.resolver).expect("the resolver should have been validated");
+        url.path_segments_mut()
+            .expect("the resolver should be an HTTP(S) URL")
+            .pop_if_empty()
+            .extend(doi.split('/'));
+        url
+    }
+
+    /// Fetches the entry for the given DOI from the resolver.
+    async fn fetch(&self, doi: &str) -> Result<String, FetchError> {
+        let url = self.url(doi);
+        let format = self.manifest.format;
+        let request_error = |error: reqwest::Error| FetchError::Request {
+            doi: doi.to_string(),
+            reason: error_chain(&error),
+        };
+        let response = CLIENT
+            .get(url)
+            .header(ACCEPT, format.media_type())
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+        if !status.is_success() {
+            let doi = doi.to_string();
+            return Err(FetchError::Status { doi, status });
+        }
+        let content = response.text().await.map_err(request_error)?;
+        if format == Format::CslJson {
+            match serde_json::from_str(&content) {
+                Ok(serde_json::Value::Object(_) | serde_json::Value::Array(_)) => {}
+                _ => {
+                    let doi = doi.to_string();
+                    return Err(FetchError::Json { doi });
+                }
+            }
+        }
+        Ok(content)
+    }
+
+    /// Combines the entries into the content of the bibliography file.
+    fn combine(&self, entries: &[&str]) -> String {
+        match self.manifest.format {
+            Format::Bibtex => {
+                let mut output = String::new();
+                for entry in entries {
+                    if !output.is_empty() {
+                        output.push('\n');
+                    }
+                    output.push_str(entry.trim());
+                    output.push('\n');
+                }
+                output
+            }
+            Format::CslJson => {
+                let mut items = Vec::new();
+                for entry in entries {
+                    // entries were validated when they were fetched
+                    match serde_json::from_str(entry) {
+                        Ok(serde_json::Value::Array(entry)) => items.extend(entry),
+                        Ok(entry) => items.push(entry),
+                        Err(_) => {}
+                    }
+                }
+                let output = serde_json::to_string_pretty(&items)
+                    .expect("JSON values should be serializable");
+                output + "\n"
+            }
+        }
+    }
+
+    async fn run_impl(&self, counts: &mut Counts) -> ExecutionResult<()> {
+        let name = &self.name;
+
+        let output_path = ARGS.resolve(&self.manifest.output).ok_or_else(|| {
+            let path_str = self.manifest.output.to_string_lossy();
+            let msg = format!("{path_str} is outside the project root");
+            io::Error::new(io::ErrorKind::PermissionDenied, msg)
+        })?;
+
+        if ARGS.print_resolved_paths {
+            let output_path = path::absolute(&output_path)?;
+            let output_str = output_path.to_string_lossy();
+            println!("[{name}] bibliography -> {output_str}");
+            return Ok(());
+        }
+
+        let dois = self.query_dois().await?;
+        let mut index = self.read_index().await?;
+
+        let mut fetched = BTreeMap::new();
+        let mut errors = Vec::new();
+        for doi in &dois {
+            let cached = index
+                .as_ref()
+                .and_then(|index| index.get(doi, self.manifest.format));
+            if cached.is_some() && !self.manifest.overwrite {
+                counts.skipped += 1;
+                continue;
+            }
+            if ARGS.dry_run.is_some() {
+                status!("[{name}] Would fetch {doi}");
+                continue;
+            }
+            status!("[{name}] Fetching {doi}...");
+            match self.fetch(doi).await {
+                Ok(content) => {
+                    counts.processed += 1;
+                    fetched.insert(doi.clone(), content);
+                }
+                Err(error) => {
+                    status!("[{name}] Fetching {doi} failed: {error}");
+                    counts.failed += 1;
+                    errors.push(error);
+                }
+            }
+        }
+        if ARGS.dry_run.is_some() {
+            return Ok(());
+        }
+
+        let format = self.manifest.format;
+        if let Some(index) = &mut index {
+            for (doi, content) in &fetched {
+                index.insert(doi.clone(), format, content.clone());
+            }
+        }
+        // entries that could not be fetched again are still taken from the index
+        let entries: Vec<_> = dois
+            .iter()
+            .filter_map(|doi| {
+                let fetched = fetched.get(doi).map(String::as_str);
+                fetched.or_else(|| index.as_ref()?.get(doi, format))
+            })
+            .collect();
+        let output = self.combine(&entries);
+
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&output_path, output).await?;
+        if let Some(index) = &index {
+            index.write().await?;
+        }
+
+        let output_str = output_path.to_string_lossy();
+        status!(
+            "[{name}] Wrote {} bibliography entries to {output_str}",
+            entries.len()
+        );
+
+        if !errors.is_empty() {
+            return Err(MultipleFetchError::new(errors).into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Preprocessor for Bibliography {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supports(&self, mode: Mode) -> bool {
+        matches!(mode, Mode::DryRun | Mode::PrintResolvedPaths)
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        Some(self.query.shell_command())
+    }
+
+    async fn run(&mut self) -> preprocessor::ExecutionResult<()> {
+        let mut counts = Counts::default();
+        let result = self.run_impl(&mut counts).await;
+        if result.is_err() && counts == Counts::default() {
+            counts.failed = 1;
+        }
+        self.counts = counts;
+        result.map_err(preprocessor::ExecutionError::new)?;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        ARGS.resolve(&self.manifest.output).into_iter().collect()
+    }
+
+    fn counts(&self) -> Counts {
+        self.counts
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::io;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use crate::query;
+
+/// A problem with the preprocessor's configuration
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    /// The provided configuration is not valid for a bibliography job
+    #[error("invalid bibliography configuration")]
+    Manifest(#[from] toml::de::Error),
+    /// An option without a default value was not given
+    #[error("invalid bibliography query configuration")]
+    Query(#[from] query::QueryBuilderError),
+    /// The configured resolver is not an absolute HTTP(S) URL
+    #[error("invalid resolver URL `{0}`")]
+    Resolver(String),
+}
+
+/// A problem with using the index of fetched entries
+#[derive(Error, Debug)]
+pub enum IndexError {
+    /// I/O error while accessing the index file
+    #[error("bibliography index file could not be read or written")]
+    Io(#[from] io::Error),
+    /// The index file has the wrong version
+    #[error("expected bibliography index file version 1, was {0}")]
+    Version(usize),
+    /// The index file could not be parsed
+    #[error("invalid bibliography index file content")]
+    Parse(#[from] toml::de::Error),
+    /// The index file could not be serialized
+    #[error("bibliography index: TOML writing error")]
+    Write(#[from] toml::ser::Error),
+}
+
+/// A DOI's entry could not be fetched from the resolver
+#[derive(Error, Debug)]
+pub enum FetchError {
+    /// The resolver responded with an error status
+    #[error("{doi}: {status}")]
+    Status {
+        /// The DOI that was resolved
+        doi: String,
+        /// The response's status
+        status: StatusCode,
+    },
+    /// The request failed, e.g. because the resolver could not be reached
+    #[error("{doi}: {reason}")]
+    Request {
+        /// The DOI that was resolved
+        doi: String,
+        /// The reason for the failure, including its causes
+        reason: String,
+    },
+    /// The resolver's response is not a CSL-JSON item or array of items
+    #[error("{doi}: the response is not valid CSL-JSON")]
+    Json {
+        /// The DOI that was resolved
+        doi: String,
+    },
+}
+
+/// One or more DOIs could not be resolved
+#[derive(Error, Debug)]
+pub struct MultipleFetchError {
+    errors: Vec<FetchError>,
+}
+
+impl MultipleFetchError {
+    /// Creates a new error
+    pub fn new(errors: Vec<FetchError>) -> Self {
+        Self { errors }
+    }
+}
+
+impl fmt::Display for MultipleFetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at least one DOI could not be resolved:")?;
+        for error in &self.errors {
+            writeln!(f)?;
+            write!(f, "  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An error during the bibliography job's execution
+#[derive(Error, Debug)]
+pub enum ExecutionError {
+    /// An error while executing the job's query
+    #[error(transparent)]
+    Query(#[from] query::Error),
+    /// An error accessing the index
+    #[error(transparent)]
+    Index(#[from] IndexError),
+    /// Some DOIs could not be resolved; the others were still written
+    #[error(transparent)]
+    Fetch(#[from] MultipleFetchError),
+    /// An error writing the output file
+    #[error("output file could not be written")]
+    Io(#[from] io::Error),
+}
+
+/// A result with a config error in it
+pub type ManifestResult<T> = Result<T, ManifestError>;
+
+/// A result with an execution error in it
+pub type ExecutionResult<T> = Result<T, ExecutionError>;
@@ -0,0 +1,64 @@
+use url::Url;
+
+use crate::manifest;
+use crate::preprocessor::{BoxedPreprocessor, PreprocessorDefinition};
+use crate::query::Query;
+
+use super::{Bibliography, Manifest, ManifestError, ManifestResult};
+
+/// The `bibliography` preprocessor factory
+#[derive(Debug, Clone, Copy)]
+pub struct BibliographyFactory;
+
+impl BibliographyFactory {
+    fn parse_config(config: toml::Table) -> ManifestResult<Manifest> {
+        let config: Manifest = config.try_into()?;
+        match Url::parse(&config.resolver) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err(ManifestError::Resolver(config.resolver)),
+        }
+        Ok(config)
+    }
+
+    fn build_query(name: &str, config: manifest::Query) -> ManifestResult<Query> {
+        let config = Query::builder()
+            .job(name)
+            .default_selector("<doi>".to_string())
+            .default_field(Some("value".to_string()))
+            .fixed_one(false)
+            .build(config)?;
+        Ok(config)
+    }
+}
+
+impl PreprocessorDefinition for BibliographyFactory {
+    const NAME: &'static str = "bibliography";
+    const DESCRIPTION: &'static str = "Fetches bibliography entries for the DOIs in the document";
+
+    type Error = ManifestError;
+
+    fn configure_impl(
+        name: String,
+        config: toml::Table,
+        query: manifest::Query,
+    ) -> ManifestResult<BoxedPreprocessor> {
+        let config = Self::parse_config(config)?;
+        let query = Self::build_query(&name, query)?;
+        let instance = Bibliography::new(name, config, query);
+        Ok(Box::new(instance))
+    }
+
+    fn transform_query_data(data: serde_json::Value) -> serde_json::Value {
+        // each element may be a single DOI or an array of DOIs
+        let serde_json::Value::Array(elements) = data else {
+            return data;
+        };
+        elements
+            .into_iter()
+            .flat_map(|element| match element {
+                serde_json::Value::Array(dois) => dois,
+                doi => vec![doi],
+            })
+            .collect()
+    }
+}
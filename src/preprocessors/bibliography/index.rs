@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::utils::{self, Restored};
+
+use super::{Format, IndexError};
+
+/// Represents an index of fetched bibliography entries.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    #[serde(skip)]
+    location: PathBuf,
+    /// a file format version number. Should be 1.
+    pub version: usize,
+    /// The entries in the index, by DOI.
+    #[serde(default, rename = "entry", skip_serializing_if = "BTreeMap::is_empty")]
+    pub entries: BTreeMap<String, Entry>,
+}
+
+/// An entry in the index, recording a fetched bibliography entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The format the entry was fetched in.
+    pub format: Format,
+    /// The entry as returned by the resolver.
+    pub content: String,
+}
+
+impl Index {
+    pub fn new(location: PathBuf) -> Self {
+        Self {
+            location,
+            version: 1,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Reads an index from a file. If the index can't be parsed, the backup from the last
+    /// successful write is read instead, which is returned alongside the index so that it can be
+    /// reported.
+    pub async fn read(
+        location: PathBuf,
+    ) -> Result<(Self, Option<Restored<IndexError>>), IndexError> {
+        let (mut index, restored) = match Self::read_file(&location).await {
+            Err(error @ IndexError::Parse(_)) => {
+                let backup = utils::backup_path(&location);
+                let Ok(index) = Self::read_file(&backup).await else {
+                    return Err(error);
+                };
+                let restored = Restored {
+                    location: location.clone(),
+                    backup,
+                    error,
+                };
+                (index, Some(restored))
+            }
+            result => (result?, None),
+        };
+        index.location = location;
+        Ok((index, restored))
+    }
+
+    async fn read_file(location: &Path) -> Result<Self, IndexError> {
+        let index = fs::read_to_string(location).await?;
+        let index: Self = toml::from_str(&index)?;
+        if index.version != 1 {
+            return Err(IndexError::Version(index.version));
+        }
+        Ok(index)
+    }
+
+    /// Writes the index to a file, without ever leaving it partially written.
+    pub async fn write(&self) -> Result<(), IndexError> {
+        let index = toml::to_string(self)?;
+        utils::write_with_backup(&self.location, index.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Returns the entry for the given DOI, if it was fetched in the given format.
+    pub fn get(&self, doi: &str, format: Format) -> Option<&str> {
+        self.entries
+            .get(doi)
+            .filter(|entry| entry.format == format)
+            .map(|entry| entry.content.as_str())
+    }
+
+    /// Records the entry fetched for the given DOI.
+    pub fn insert(&mut self, doi: String, format: Format, content: String) {
+        self.entries.insert(doi, Entry { format, content });
+    }
+}
@@ -0,0 +1,77 @@
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::utils;
+
+/// Auxilliary configuration for the preprocessor
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The bibliography file to write the fetched entries to, relative to the project root, e.g.
+    /// `refs.bib`. The document can load it with Typst's `bibliography` function.
+    pub output: PathBuf,
+
+    /// The format of the entries and the output file: `bibtex` or `csl-json`. Defaults to
+    /// `bibtex`.
+    #[serde(default)]
+    pub format: Format,
+
+    /// The resolver that DOIs are appended to; entries are fetched from it using content
+    /// negotiation. Defaults to "https://doi.org/".
+    #[serde(default = "default_resolver")]
+    pub resolver: String,
+
+    /// Always fetches all entries, even those that are already in the index.
+    #[serde(default)]
+    pub overwrite: bool,
+
+    /// Change this to true or a file path given as a string to enable the index, which caches the
+    /// fetched entries so that they are not fetched again in later runs. If true, the default path
+    /// is "bibliography-index.toml".
+    #[serde(default, deserialize_with = "deserialize_index")]
+    pub index: Option<PathBuf>,
+}
+
+/// The format of bibliography entries
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// BibTeX entries, combined into a `.bib` file
+    #[default]
+    Bibtex,
+    /// CSL-JSON items, combined into a JSON array
+    CslJson,
+}
+
+impl Format {
+    /// The media type requested from the resolver
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Self::Bibtex => "application/x-bibtex",
+            Self::CslJson => "application/vnd.citationstyles.csl+json",
+        }
+    }
+}
+
+fn default_resolver() -> String {
+    "https://doi.org/".to_string()
+}
+
+impl Manifest {
+    pub async fn resolve_index_path(&self) -> Option<io::Result<PathBuf>> {
+        if let Some(index) = &self.index {
+            Some(utils::resolve_manifest_path(index).await)
+        } else {
+            None
+        }
+    }
+}
+
+/// Deserializes the `index` config: if given, must be either a boolean or string.
+fn deserialize_index<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    utils::deserialize_index(deserializer, "bibliography-index.toml")
+}
@@ -0,0 +1,217 @@
+//! The `template` preprocessor
+
+use std::io;
+use std::path::{self, Path, PathBuf};
+
+use async_trait::async_trait;
+use handlebars::Handlebars;
+use tokio::fs;
+
+use crate::args::ARGS;
+use crate::preprocessor::{self, Mode, Preprocessor, PreprocessorDefinition};
+use crate::query::Query;
+use crate::report::Counts;
+use crate::utils::status;
+
+mod error;
+mod factory;
+mod index;
+mod manifest;
+
+use index::*;
+use manifest::*;
+
+pub use error::*;
+pub use factory::TemplateFactory;
+
+/// The `template` preprocessor
+#[derive(Debug)]
+pub struct Template {
+    name: String,
+    manifest: Manifest,
+    query: Query,
+    counts: Counts,
+}
+
+impl Template {
+    pub(crate) fn new(name: String, manifest: Manifest, query: Query) -> Self {
+        Self {
+            name,
+            manifest,
+            query,
+            counts: Counts::default(),
+        }
+    }
+
+    fn render(&self, template: &str, data: &serde_json::Value) -> ExecutionResult<String> {
+        match self.manifest.engine {
+            Engine::Handlebars => {
+                let mut handlebars = Handlebars::new();
+                // the output is not necessarily HTML, so nothing should be escaped
+                handlebars.register_escape_fn(handlebars::no_escape);
+                let output = handlebars.render_template(template, data)?;
+                Ok(output)
+            }
+        }
+    }
+
+    async fn read_index(&self) -> ExecutionResult<Option<Index>> {
+        let Some(location) = self.manifest.resolve_index_path().await else {
+            return Ok(None);
+        };
+        let location = location.map_err(IndexError::from)?;
+        let index = if fs::try_exists(&location).await.map_err(IndexError::from)? {
+            Index::read(location).await?
+        } else {
+            Index::new(location)
+        };
+        Ok(Some(index))
+    }
+
+    /// Prints the changes that rendering the template would make to the files currently on disk:
+    /// whether the output file would be added or changed, and whether the file the template was
+    /// previously rendered to would be evicted.
+    async fn print_diff(
+        &self,
+        output_path: &Path,
+        output: &str,
+        evicted: Option<(&Path, PathBuf)>,
+    ) -> ExecutionResult<()> {
+        let name = &self.name;
+        let (mut added, mut changed, mut removed, mut unchanged) = (0, 0, 0, 0);
+
+        let path_str = self.manifest.output.to_string_lossy();
+        match fs::read(output_path).await {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                added += 1;
+                let template_str = self.manifest.template.to_string_lossy();
+                status!("[{name}] + {path_str} (new, from {template_str})");
+            }
+            Ok(existing) if existing == output.as_bytes() => {
+                unchanged += 1;
+            }
+            result => {
+                result?;
+                changed += 1;
+                status!("[{name}] ~ {path_str} (rendered output has changed)");
+            }
+        }
+
+        if let Some((previous, previous_path)) = evicted {
+            if fs::try_exists(previous_path).await? {
+                removed += 1;
+                let previous_str = previous.to_string_lossy();
+                status!("[{name}] - {previous_str} (no longer needed)");
+            }
+        }
+
+        status!(
+            "[{name}] dry run: {added} added, {changed} changed, {removed} removed, {unchanged} unchanged"
+        );
+        Ok(())
+    }
+
+    async fn run_impl(&self) -> ExecutionResult<()> {
+        let name = &self.name;
+
+        let resolve = |path: &Path| {
+            ARGS.resolve(path).ok_or_else(|| {
+                let path_str = path.to_string_lossy();
+                let msg = format!("{path_str} is outside the project root");
+                io::Error::new(io::ErrorKind::PermissionDenied, msg)
+            })
+        };
+        let template_path = resolve(&self.manifest.template)?;
+        let output_path = resolve(&self.manifest.output)?;
+
+        if ARGS.print_resolved_paths {
+            let output_path = path::absolute(&output_path)?;
+            let output_str = output_path.to_string_lossy();
+            println!(
+                "[{name}] {} -> {output_str}",
+                self.manifest.template.display()
+            );
+            return Ok(());
+        }
+
+        let data = self.query.query_value().await?;
+        let data = TemplateFactory::transform_query_data(data);
+        let template = fs::read_to_string(&template_path).await?;
+        let output = self.render(&template, &data)?;
+
+        let index = self.read_index().await?;
+        // the file the template was previously rendered to, if it is no longer needed
+        let previous = index
+            .as_ref()
+            .and_then(|index| index.output.as_ref())
+            .filter(|previous| self.manifest.evict && **previous != self.manifest.output);
+        let evicted = match previous {
+            Some(previous) => Some((previous.as_path(), resolve(previous)?)),
+            None => None,
+        };
+
+        if ARGS.dry_run.is_some() {
+            self.print_diff(&output_path, &output, evicted).await?;
+            return Ok(());
+        }
+
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&output_path, output).await?;
+
+        let output_str = output_path.to_string_lossy();
+        status!("[{name}] Rendered template to {output_str}");
+
+        if let Some((_, previous_path)) = &evicted {
+            if fs::try_exists(previous_path).await? {
+                let previous_str = previous_path.to_string_lossy();
+                status!("[{name}] Evicting {previous_str}");
+                fs::remove_file(previous_path).await?;
+            }
+        }
+        if let Some(mut index) = index {
+            index.output = Some(self.manifest.output.clone());
+            index.write().await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Preprocessor for Template {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supports(&self, mode: Mode) -> bool {
+        matches!(mode, Mode::DryRun | Mode::PrintResolvedPaths)
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        Some(self.query.shell_command())
+    }
+
+    async fn run(&mut self) -> preprocessor::ExecutionResult<()> {
+        let result = self.run_impl().await;
+        self.counts = Counts {
+            processed: result.is_ok().into(),
+            failed: result.is_err().into(),
+            ..Counts::default()
+        };
+        result.map_err(preprocessor::ExecutionError::new)?;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        [&self.manifest.template, &self.manifest.output]
+            .into_iter()
+            .filter_map(|path| ARGS.resolve(path))
+            .collect()
+    }
+
+    fn counts(&self) -> Counts {
+        self.counts
+    }
+}
@@ -0,0 +1,56 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::query;
+
+/// A problem with the preprocessor's configuration
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    /// The provided configuration is not valid for a template job
+    #[error("invalid template configuration")]
+    Manifest(#[from] toml::de::Error),
+    /// An option without a default value was not given
+    #[error("invalid template query configuration")]
+    Query(#[from] query::QueryBuilderError),
+}
+
+/// A problem with using the index of rendered templates
+#[derive(Error, Debug)]
+pub enum IndexError {
+    /// I/O error while accessing the index file
+    #[error("template index file could not be read or written")]
+    Io(#[from] io::Error),
+    /// Unexpected version: must be 1
+    #[error("expected template index file version 1, was {0}")]
+    Version(usize),
+    /// Error parsing the index file's contents
+    #[error("invalid template index file content")]
+    Parse(#[from] toml::de::Error),
+    /// Error writing new index file contents
+    #[error("template index: TOML writing error")]
+    Write(#[from] toml::ser::Error),
+}
+
+/// An error during the template job's execution
+#[derive(Error, Debug)]
+pub enum ExecutionError {
+    /// An error while executing the job's query
+    #[error(transparent)]
+    Query(#[from] query::Error),
+    /// An error accessing the index
+    #[error(transparent)]
+    Index(#[from] IndexError),
+    /// An error reading the template or writing the output file
+    #[error("template or output file could not be accessed")]
+    Io(#[from] io::Error),
+    /// An error while rendering the template
+    #[error("the template could not be rendered")]
+    Render(#[from] handlebars::RenderError),
+}
+
+/// A result with a config error in it
+pub type ManifestResult<T> = Result<T, ManifestError>;
+
+/// A result with an execution error in it
+pub type ExecutionResult<T> = Result<T, ExecutionError>;
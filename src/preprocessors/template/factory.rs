@@ -0,0 +1,44 @@
+use crate::manifest;
+use crate::preprocessor::{BoxedPreprocessor, PreprocessorDefinition};
+use crate::query::Query;
+
+use super::{Manifest, ManifestError, ManifestResult, Template};
+
+/// The `template` preprocessor factory
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateFactory;
+
+impl TemplateFactory {
+    fn parse_config(config: toml::Table) -> ManifestResult<Manifest> {
+        let config = config.try_into()?;
+        Ok(config)
+    }
+
+    fn build_query(name: &str, config: manifest::Query) -> ManifestResult<Query> {
+        let config = Query::builder()
+            .job(name)
+            .default_field(Some("value".to_string()))
+            .default_one(false)
+            .build(config)?;
+        Ok(config)
+    }
+}
+
+impl PreprocessorDefinition for TemplateFactory {
+    const NAME: &'static str = "template";
+    const DESCRIPTION: &'static str =
+        "Renders the query data into a file using a Handlebars template";
+
+    type Error = ManifestError;
+
+    fn configure_impl(
+        name: String,
+        config: toml::Table,
+        query: manifest::Query,
+    ) -> ManifestResult<BoxedPreprocessor> {
+        let config = Self::parse_config(config)?;
+        let query = Self::build_query(&name, query)?;
+        let instance = Template::new(name, config, query);
+        Ok(Box::new(instance))
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::IndexError;
+
+/// Represents an index of a rendered template.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    #[serde(skip)]
+    location: PathBuf,
+    /// a file format version number. Should be 1.
+    pub version: usize,
+    /// The file the template was last rendered to, relative to the project root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+}
+
+impl Index {
+    pub fn new(location: PathBuf) -> Self {
+        Self {
+            location,
+            version: 1,
+            output: None,
+        }
+    }
+
+    /// Reads an index from a file.
+    pub async fn read(location: PathBuf) -> Result<Self, IndexError> {
+        let index = fs::read_to_string(&location).await?;
+        let mut index: Self = toml::from_str(&index)?;
+        if index.version != 1 {
+            return Err(IndexError::Version(index.version));
+        }
+        index.location = location;
+        Ok(index)
+    }
+
+    /// Writes the index to a file.
+    pub async fn write(&self) -> Result<(), IndexError> {
+        let index = toml::to_string(self)?;
+        fs::write(&self.location, index).await?;
+        Ok(())
+    }
+}
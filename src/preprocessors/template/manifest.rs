@@ -0,0 +1,61 @@
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::utils;
+
+/// Auxilliary configuration for the preprocessor
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The template file to render, relative to the project root.
+    pub template: PathBuf,
+
+    /// The file to write the rendered template to, relative to the project root.
+    pub output: PathBuf,
+
+    /// The template engine to use. Currently, only `handlebars` is supported, which is also the
+    /// default.
+    #[serde(default)]
+    pub engine: Engine,
+
+    /// Change this to true or a file path given as a string to enable the index, which records the
+    /// file the template was rendered to. If true, the default path is "template-index.toml".
+    /// Note that if multiple template jobs are using the same index file, this will lead to
+    /// problems!
+    #[serde(default, deserialize_with = "deserialize_index")]
+    pub index: Option<PathBuf>,
+
+    /// Change this to true to delete the file the template was previously rendered to when the
+    /// `output` changes. This requires the index to be enabled.
+    #[serde(default)]
+    pub evict: bool,
+}
+
+/// The template engines that can be used for rendering
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    /// [Handlebars](https://handlebarsjs.com/) templates. Since the output is usually not HTML, no
+    /// escaping is applied to rendered values.
+    #[default]
+    Handlebars,
+}
+
+impl Manifest {
+    pub async fn resolve_index_path(&self) -> Option<io::Result<PathBuf>> {
+        if let Some(index) = &self.index {
+            Some(utils::resolve_manifest_path(index).await)
+        } else {
+            None
+        }
+    }
+}
+
+/// Deserializes the `index` config: if given, must be either a boolean or string.
+fn deserialize_index<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    utils::deserialize_index(deserializer, "template-index.toml")
+}
@@ -0,0 +1,146 @@
+//! The `typst-export` preprocessor
+
+use std::io;
+use std::path::{self, Path, PathBuf};
+
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::args::ARGS;
+use crate::preprocessor::{self, Mode, Preprocessor, PreprocessorDefinition};
+use crate::query::Query;
+use crate::report::Counts;
+use crate::utils::status;
+
+mod error;
+mod factory;
+mod manifest;
+mod value;
+
+use manifest::*;
+
+pub use error::*;
+pub use factory::TypstExportFactory;
+
+/// The `typst-export` preprocessor
+#[derive(Debug)]
+pub struct TypstExport {
+    name: String,
+    manifest: Manifest,
+    query: Query,
+    counts: Counts,
+}
+
+impl TypstExport {
+    pub(crate) fn new(name: String, manifest: Manifest, query: Query) -> Self {
+        Self {
+            name,
+            manifest,
+            query,
+            counts: Counts::default(),
+        }
+    }
+
+    /// Prints the changes that exporting the query data would make to the file currently on disk:
+    /// whether the output file would be added or changed.
+    async fn print_diff(&self, output_path: &Path, output: &str) -> ExecutionResult<()> {
+        let name = &self.name;
+        let (mut added, mut changed, mut unchanged) = (0, 0, 0);
+
+        let path_str = self.manifest.output.to_string_lossy();
+        match fs::read(output_path).await {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                added += 1;
+                let variable = &self.manifest.variable;
+                status!("[{name}] + {path_str} (new, exporting `{variable}`)");
+            }
+            Ok(existing) if existing == output.as_bytes() => {
+                unchanged += 1;
+            }
+            result => {
+                result?;
+                changed += 1;
+                status!("[{name}] ~ {path_str} (exported data has changed)");
+            }
+        }
+
+        status!(
+            "[{name}] dry run: {added} added, {changed} changed, 0 removed, {unchanged} unchanged"
+        );
+        Ok(())
+    }
+
+    async fn run_impl(&self) -> ExecutionResult<()> {
+        let name = &self.name;
+
+        let output_path = ARGS.resolve(&self.manifest.output).ok_or_else(|| {
+            let path_str = self.manifest.output.to_string_lossy();
+            let msg = format!("{path_str} is outside the project root");
+            io::Error::new(io::ErrorKind::PermissionDenied, msg)
+        })?;
+
+        if ARGS.print_resolved_paths {
+            let output_path = path::absolute(&output_path)?;
+            let output_str = output_path.to_string_lossy();
+            println!("[{name}] {} -> {output_str}", self.manifest.variable);
+            return Ok(());
+        }
+
+        let data = self.query.query_value().await?;
+        let data = TypstExportFactory::transform_query_data(data);
+        let output = format!(
+            "#let {} = {}\n",
+            self.manifest.variable,
+            value::to_typst(&data)
+        );
+
+        if ARGS.dry_run.is_some() {
+            self.print_diff(&output_path, &output).await?;
+            return Ok(());
+        }
+
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&output_path, output).await?;
+
+        let output_str = output_path.to_string_lossy();
+        status!("[{name}] Exported query data to {output_str}");
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Preprocessor for TypstExport {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supports(&self, mode: Mode) -> bool {
+        matches!(mode, Mode::DryRun | Mode::PrintResolvedPaths)
+    }
+
+    fn shell_command(&self) -> Option<String> {
+        Some(self.query.shell_command())
+    }
+
+    async fn run(&mut self) -> preprocessor::ExecutionResult<()> {
+        let result = self.run_impl().await;
+        self.counts = Counts {
+            processed: result.is_ok().into(),
+            failed: result.is_err().into(),
+            ..Counts::default()
+        };
+        result.map_err(preprocessor::ExecutionError::new)?;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        ARGS.resolve(&self.manifest.output).into_iter().collect()
+    }
+
+    fn counts(&self) -> Counts {
+        self.counts
+    }
+}
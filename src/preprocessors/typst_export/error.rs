@@ -0,0 +1,36 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::query;
+
+/// A problem with the preprocessor's configuration
+#[derive(Error, Debug)]
+pub enum ManifestError {
+    /// The provided configuration is not valid for a typst-export job
+    #[error("invalid typst-export configuration")]
+    Manifest(#[from] toml::de::Error),
+    /// An option without a default value was not given
+    #[error("invalid typst-export query configuration")]
+    Query(#[from] query::QueryBuilderError),
+    /// The variable name is not a valid Typst identifier
+    #[error("`{0}` is not a valid Typst identifier")]
+    Variable(String),
+}
+
+/// An error during the typst-export job's execution
+#[derive(Error, Debug)]
+pub enum ExecutionError {
+    /// An error while executing the job's query
+    #[error(transparent)]
+    Query(#[from] query::Error),
+    /// An error writing the output file
+    #[error("output file could not be written")]
+    Io(#[from] io::Error),
+}
+
+/// A result with a config error in it
+pub type ManifestResult<T> = Result<T, ManifestError>;
+
+/// A result with an execution error in it
+pub type ExecutionResult<T> = Result<T, ExecutionError>;
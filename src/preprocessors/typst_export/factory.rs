@@ -0,0 +1,46 @@
+use crate::manifest;
+use crate::preprocessor::{BoxedPreprocessor, PreprocessorDefinition};
+use crate::query::Query;
+
+use super::{value, Manifest, ManifestError, ManifestResult, TypstExport};
+
+/// The `typst-export` preprocessor factory
+#[derive(Debug, Clone, Copy)]
+pub struct TypstExportFactory;
+
+impl TypstExportFactory {
+    fn parse_config(config: toml::Table) -> ManifestResult<Manifest> {
+        let config: Manifest = config.try_into()?;
+        if !value::is_identifier(&config.variable) {
+            return Err(ManifestError::Variable(config.variable));
+        }
+        Ok(config)
+    }
+
+    fn build_query(name: &str, config: manifest::Query) -> ManifestResult<Query> {
+        let config = Query::builder()
+            .job(name)
+            .default_field(Some("value".to_string()))
+            .default_one(false)
+            .build(config)?;
+        Ok(config)
+    }
+}
+
+impl PreprocessorDefinition for TypstExportFactory {
+    const NAME: &'static str = "typst-export";
+    const DESCRIPTION: &'static str = "Writes the query data into a file as Typst code";
+
+    type Error = ManifestError;
+
+    fn configure_impl(
+        name: String,
+        config: toml::Table,
+        query: manifest::Query,
+    ) -> ManifestResult<BoxedPreprocessor> {
+        let config = Self::parse_config(config)?;
+        let query = Self::build_query(&name, query)?;
+        let instance = TypstExport::new(name, config, query);
+        Ok(Box::new(instance))
+    }
+}
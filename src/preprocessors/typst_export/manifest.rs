@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// Auxilliary configuration for the preprocessor
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The `.typ` file to write the query data to, relative to the project root.
+    pub output: PathBuf,
+
+    /// The name of the variable the query data is assigned to. Defaults to `data`.
+    #[serde(default = "default_variable")]
+    pub variable: String,
+}
+
+fn default_variable() -> String {
+    "data".to_string()
+}
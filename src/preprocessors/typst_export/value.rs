@@ -0,0 +1,90 @@
+//! Conversion of JSON values into Typst value syntax
+
+use std::fmt::Write;
+
+use serde_json::{Number, Value};
+
+/// Checks whether the string is a valid Typst identifier. This is a conservative approximation
+/// that only accepts ASCII identifiers.
+pub fn is_identifier(ident: &str) -> bool {
+    let mut chars = ident.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Converts the value into Typst code that evaluates to an equivalent value: `null` becomes `none`,
+/// arrays become arrays and objects become dictionaries.
+pub fn to_typst(value: &Value) -> String {
+    let mut output = String::new();
+    write_value(&mut output, value);
+    output
+}
+
+fn write_value(output: &mut String, value: &Value) {
+    match value {
+        Value::Null => output.push_str("none"),
+        Value::Bool(value) => write!(output, "{value}").unwrap(),
+        Value::Number(number) => write_number(output, number),
+        Value::String(string) => write_string(output, string),
+        Value::Array(array) => {
+            output.push('(');
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                write_value(output, item);
+            }
+            // a single parenthesized value is not an array without a trailing comma
+            if array.len() == 1 {
+                output.push(',');
+            }
+            output.push(')');
+        }
+        Value::Object(object) if object.is_empty() => output.push_str("(:)"),
+        Value::Object(object) => {
+            output.push('(');
+            for (i, (key, value)) in object.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                write_string(output, key);
+                output.push_str(": ");
+                write_value(output, value);
+            }
+            output.push(')');
+        }
+    }
+}
+
+fn write_number(output: &mut String, number: &Number) {
+    if let Some(number) = number.as_i64() {
+        write!(output, "{number}").unwrap();
+        return;
+    }
+
+    // Typst integers are 64 bit signed; everything else becomes a float
+    let number = number.to_string();
+    output.push_str(&number);
+    if !number.contains(['.', 'e', 'E']) {
+        output.push_str(".0");
+    }
+}
+
+fn write_string(output: &mut String, string: &str) {
+    output.push('"');
+    for c in string.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '"' => output.push_str("\\\""),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => write!(output, "\\u{{{:x}}}", c as u32).unwrap(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
@@ -1,31 +1,146 @@
 //! The `web-resource` preprocessor
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
 use std::io;
-use std::path::Path;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::{self, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::task_tracker::{TaskTracker, TaskTrackerToken};
+use tracing::Instrument;
 
-use crate::args::ARGS;
-use crate::preprocessor::{self, Preprocessor};
+use crate::args::{DryRun, DumpCommands, HostConcurrency, ARGS};
+use crate::preprocessor::{self, Mode, Preprocessor, PreprocessorDefinition};
 use crate::query::{self, Query};
-use crate::utils;
+use crate::report::{error_chain, AtomicCounts, Counts};
+use crate::utils::{self, status};
 
+mod auth;
+mod cache_control;
+mod concurrency;
 mod error;
 mod factory;
+mod fonts;
+mod freshness;
+mod hashes;
+#[cfg(feature = "http-cache")]
+mod http_cache;
 mod index;
+mod link;
+mod listing;
 mod manifest;
+mod named;
+mod progress;
 mod query_data;
+mod queue;
+mod redirect;
+mod request;
+mod retry;
+mod sidecar;
+mod tls;
 
+use concurrency::*;
+use fonts::FontsManifest;
+use freshness::*;
+use hashes::*;
 use index::*;
 use manifest::*;
-use query_data::*;
+use progress::Progress;
+use queue::*;
 
 pub use error::*;
 pub use factory::WebResourceFactory;
+pub use fonts::FontsFactory;
+pub use index::Resource;
+pub use query_data::{Listing, QueryData, ResourceOptions};
+
+/// The number of bytes downloaded by all web-resource jobs during the current run, see
+/// [reset_run_state]
+static DOWNLOADED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The file that could not be written because the disk was full, if that happened during this run
+static DISK_FULL: std::sync::Mutex<Option<PathBuf>> = std::sync::Mutex::new(None);
+
+/// Resets the state shared by the web-resource jobs of a run, so that e.g. `--max-total-bytes`
+/// applies to each run of `--watch` separately, and a full disk only fails the run it happened in.
+pub(crate) fn reset_run_state() {
+    DOWNLOADED_BYTES.store(0, Ordering::Relaxed);
+    *DISK_FULL
+        .lock()
+        .expect("the disk full state should not be poisoned") = None;
+}
+
+/// Returns the file that could not be written because the disk was full during this run.
+fn disk_full() -> Option<PathBuf> {
+    DISK_FULL
+        .lock()
+        .expect("the disk full state should not be poisoned")
+        .clone()
+}
+
+/// The number of concurrent downloads of a job if not configured otherwise
+const DEFAULT_CONCURRENCY: usize = 16;
+
+/// The HTTP client shared by all web-resource jobs that don't override DNS resolution, and by
+/// other jobs making HTTP requests
+pub(crate) static CLIENT: Lazy<reqwest::Client> =
+    Lazy::new(|| build_client(redirect::policy(), &BTreeMap::new()));
+
+/// Builds an HTTP client with the given redirect policy, resolving the given hosts to the given
+/// addresses instead of using DNS.
+fn build_client(
+    policy: reqwest::redirect::Policy,
+    resolve: &BTreeMap<String, IpAddr>,
+) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder()
+        // needed for TLS verification
+        .tls_info(true)
+        .redirect(policy);
+    for (host, address) in resolve {
+        // port 0 is replaced by the port of the requested URL
+        builder = builder.resolve(host, SocketAddr::new(*address, 0));
+    }
+    builder
+        .build()
+        .expect("the HTTP client should be configured correctly")
+}
+
+/// The HTTP clients used by a job
+#[derive(Debug, Clone)]
+struct Clients {
+    /// The client following redirects
+    client: reqwest::Client,
+    /// The client used with authorization, which doesn't follow redirects
+    no_redirect: reqwest::Client,
+}
+
+impl Clients {
+    /// Returns the shared clients, or new ones if the job overrides DNS resolution.
+    fn new(manifest: &Manifest) -> Self {
+        if manifest.resolve.is_empty() {
+            return Self {
+                client: CLIENT.clone(),
+                no_redirect: auth::NO_REDIRECT_CLIENT.clone(),
+            };
+        }
+        Self {
+            client: build_client(redirect::policy(), &manifest.resolve),
+            no_redirect: build_client(reqwest::redirect::Policy::none(), &manifest.resolve),
+        }
+    }
+}
 
 /// The `web-resource` preprocessor
 #[derive(Debug)]
@@ -33,7 +148,32 @@ pub struct WebResource {
     name: String,
     manifest: Manifest,
     index: Option<Mutex<Index>>,
+    queue: Option<Mutex<WorkQueue>>,
+    limits: ConcurrencyLimits,
     query: Query,
+    clients: Clients,
+    dependencies: Vec<PathBuf>,
+    options: BTreeMap<PathBuf, ResourceOptions>,
+    fonts: Option<FontsManifest>,
+    /// The responses of the named resources, by name, or null if they were not requested
+    responses: serde_json::Value,
+    cancel: CancellationToken,
+    /// The files that downloads have started to replace, which are removed if the job is aborted
+    in_flight: std::sync::Mutex<BTreeSet<PathBuf>>,
+    /// Tracks the references that downloads hold to the preprocessor
+    downloads: TaskTracker,
+    #[cfg(feature = "http-cache")]
+    http_cache: Option<reqwest_middleware::ClientWithMiddleware>,
+    progress: Option<Progress>,
+    counts: AtomicCounts,
+}
+
+/// A reference to the preprocessor that is held by its downloads. Its token is only released after
+/// the reference, so that the preprocessor is not shared anymore once all tokens are gone.
+#[derive(Clone)]
+struct DownloadRef {
+    this: Arc<WebResource>,
+    _token: TaskTrackerToken,
 }
 
 /// The state of the file: if and how the existing file corresponds to the desired web resource.
@@ -43,16 +183,30 @@ enum ResourceState {
     Missing,
     /// A re-download is forced despite the file existing.
     Forced,
+    /// The resource is volatile and is downloaded again in every run.
+    Volatile,
     /// The file seems to be up-to-date: the URL hasn't changed, or no index is kept.
     Existing,
     /// The file seems is not up-to-date: the URL has changed according to the index.
     ChangedResource,
+    /// The file may not be up-to-date: its cache lifetime according to the index has passed.
+    Expired,
+    /// The file is not up-to-date: its content differs from what was downloaded.
+    Modified,
+    /// The file is not up-to-date: the resource has changed on the server.
+    ChangedOnServer,
 }
 
 impl ResourceState {
     pub fn download(self) -> bool {
         match self {
-            Self::Missing | Self::Forced | Self::ChangedResource => true,
+            Self::Missing
+            | Self::Forced
+            | Self::Volatile
+            | Self::ChangedResource
+            | Self::Expired
+            | Self::Modified
+            | Self::ChangedOnServer => true,
             Self::Existing => false,
         }
     }
@@ -61,26 +215,24 @@ impl ResourceState {
         match self {
             Self::Missing => None,
             Self::Forced => Some("overwrite of existing files was forced"),
+            Self::Volatile => Some("resource is volatile"),
             Self::ChangedResource => Some("URL has changed"),
+            Self::Expired => Some("cache lifetime has expired"),
+            Self::Modified => Some("file was modified"),
+            Self::ChangedOnServer => Some("resource has changed"),
             Self::Existing => Some("file exists"),
         }
     }
 
-    fn print_reason(self) {
-        if let Some(msg) = self.reason() {
-            print!(" ({msg})");
-        }
-    }
-
     pub fn print(self, name: &str, url: &str, path: &str) {
+        let reason = self
+            .reason()
+            .map(|msg| format!(" ({msg})"))
+            .unwrap_or_default();
         if self.download() {
-            print!("[{name}] Downloading {url} to {path}");
-            self.print_reason();
-            println!("...");
+            status!("[{name}] Downloading {url} to {path}{reason}...");
         } else {
-            print!("[{name}] Downloading of {url} to {path} skipped");
-            self.print_reason();
-            println!();
+            status!("[{name}] Downloading of {url} to {path} skipped{reason}");
         }
     }
 }
@@ -92,11 +244,28 @@ impl WebResource {
         index: Option<Mutex<Index>>,
         query: Query,
     ) -> Self {
+        let adaptive = ARGS.concurrency_per_host == HostConcurrency::Auto;
+        let limits = ConcurrencyLimits::new(&manifest.per_host_concurrency, adaptive);
+        let clients = Clients::new(&manifest);
         Self {
             name,
             index,
+            queue: None,
+            limits,
             manifest,
             query,
+            clients,
+            dependencies: Vec::new(),
+            options: BTreeMap::new(),
+            fonts: None,
+            responses: serde_json::Value::Null,
+            cancel: CancellationToken::new(),
+            in_flight: Default::default(),
+            downloads: TaskTracker::new(),
+            #[cfg(feature = "http-cache")]
+            http_cache: None,
+            progress: None,
+            counts: AtomicCounts::default(),
         }
     }
 
@@ -104,14 +273,25 @@ impl WebResource {
         if let Some(location) = self.manifest.resolve_index_path().await {
             // an index is in use
             let location = location?;
-            let index = if fs::try_exists(&location).await.unwrap_or(false) {
+            let mut index = if fs::try_exists(&location).await.unwrap_or(false) {
                 // read the existing index
-                Index::read(location).await?
+                let (index, restored) = Index::read(location).await?;
+                if let Some(restored) = restored {
+                    if ARGS.warnings_as_errors {
+                        return Err(restored.error);
+                    }
+                    eprintln!("[{}] warning: {restored}", self.name);
+                }
+                index
             } else {
                 // generate an empty index
                 Index::new(location)
             };
 
+            for (from, to) in &self.manifest.rename {
+                index.rename_prefix(from, to);
+            }
+
             self.index = Some(Mutex::new(index));
         } else {
             // no index is in use
@@ -121,110 +301,1116 @@ impl WebResource {
         Ok(())
     }
 
-    async fn query(&self) -> query::Result<QueryData> {
-        let data = self.query.query().await?;
+    async fn query(&self) -> ExecutionResult<QueryData> {
+        let data = self.query.query_value().await?;
+        let data = WebResourceFactory::transform_query_data(data);
+        let mut data = Vec::<serde_json::Value>::deserialize(data).map_err(query::Error::from)?;
+        if let Some(discriminator) = &self.manifest.discriminator {
+            data.retain(|element| discriminator.matches(element));
+        }
+        let data = self
+            .manifest
+            .on_duplicate
+            .apply(&self.manifest.duplicate_key, data)?;
+        let data =
+            QueryData::deserialize(serde_json::Value::Array(data)).map_err(query::Error::from)?;
         Ok(data)
     }
 
-    async fn download(self: Arc<Self>, resource: Resource) -> Result<(), DownloadError> {
-        let name = self.name();
-        let Resource { url, path } = &resource;
+    /// Determines whether and why the given resource, which would be saved at the given resolved
+    /// path, needs to be downloaded.
+    async fn resource_state(&self, resource: &Resource, resolved_path: &Path) -> ResourceState {
+        let Resource { url, path } = resource;
 
-        let resolved_path = ARGS.resolve(path).ok_or_else(|| {
-            let path_str = path.to_string_lossy();
-            let msg = format!("{path_str} is outside the project root");
-            io::Error::new(io::ErrorKind::PermissionDenied, msg)
-        })?;
-        let path_str = resolved_path.to_string_lossy();
+        let options = self.options.get(path).copied().unwrap_or_default();
+        let exists = fs::try_exists(resolved_path).await.unwrap_or(false);
+        if !exists {
+            return ResourceState::Missing;
+        } else if ARGS.only_missing || options.immutable {
+            // skip all further checks, which may need to access the network
+            return ResourceState::Existing;
+        } else if options.volatile {
+            return ResourceState::Volatile;
+        } else if self.manifest.overwrite || self.manifest.freshness == FreshnessMode::Always {
+            return ResourceState::Forced;
+        }
 
-        let exists = fs::try_exists(&resolved_path).await.unwrap_or(false);
-        let state = if !exists {
-            ResourceState::Missing
-        } else if self.manifest.overwrite {
-            ResourceState::Forced
-        } else if let Some(index) = &self.index {
+        let Some(index) = &self.index else {
+            return ResourceState::Existing;
+        };
+        let Ok(request) = self.manifest.request.fingerprint(resource) else {
+            // let the download report the problem
+            return ResourceState::ChangedResource;
+        };
+        let entry = {
             let index = index.lock().await;
-            if index.is_up_to_date(path, url) {
-                ResourceState::Existing
-            } else {
-                ResourceState::ChangedResource
+            if !index.is_up_to_date(path, url, request.as_deref()) {
+                return ResourceState::ChangedResource;
+            } else if self.manifest.respect_cache_control
+                && index.is_expired(path, utils::unix_time())
+            {
+                return ResourceState::Expired;
             }
-        } else {
-            ResourceState::Existing
+            index.get(path).cloned()
         };
 
-        state.print(name, url, &path_str);
+        match (self.manifest.freshness, entry) {
+            (FreshnessMode::Smart, Some(entry)) => self.smart_state(&entry, resolved_path).await,
+            _ => ResourceState::Existing,
+        }
+    }
+
+    /// Determines whether an existing file is still fresh, by comparing its hash and the
+    /// resource's validators to the ones recorded in its index entry.
+    async fn smart_state(&self, entry: &Entry, resolved_path: &Path) -> ResourceState {
+        let Ok(local_hash) = freshness::file_sha256(resolved_path).await else {
+            return ResourceState::Modified;
+        };
+
+        let remote = match self.head(entry).await {
+            Ok(response) if response.status().is_success() => {
+                Validators::from_headers(response.headers())
+            }
+            // let the download report the problem
+            _ => return ResourceState::ChangedOnServer,
+        };
+
+        let freshness = freshness::check(
+            entry.hash.as_deref(),
+            &local_hash,
+            &entry.validators(),
+            &remote,
+        );
+        match freshness {
+            Freshness::Fresh => ResourceState::Existing,
+            Freshness::Modified => ResourceState::Modified,
+            Freshness::Changed => ResourceState::ChangedOnServer,
+        }
+    }
+
+    /// Checks that the given resource path is allowed, and resolves it in the project root.
+    fn check_path(&self, path: &Path) -> Result<PathBuf, DownloadError> {
+        if !self.manifest.is_extension_allowed(path) {
+            return Err(DownloadError::Extension(path.to_path_buf()));
+        }
+        let resolved_path = resolve_path(path)?;
+        Ok(resolved_path)
+    }
+
+    /// Warns about a resource with a plain `http://` URL, or rejects it with `require_https` or
+    /// `--warnings-as-errors`. Invalid URLs are rejected when downloading.
+    fn check_https(&self, url: &str) -> Result<(), DownloadError> {
+        let Ok(parsed) = url::Url::parse(url) else {
+            return Ok(());
+        };
+        if parsed.scheme() != "http" {
+            return Ok(());
+        }
+        if self.manifest.require_https || ARGS.warnings_as_errors {
+            return Err(DownloadError::Insecure(url.to_string()));
+        }
+        eprintln!(
+            "[{}] warning: {url} is downloaded over insecure HTTP",
+            self.name
+        );
+        Ok(())
+    }
+
+    fn in_flight_files(&self) -> std::sync::MutexGuard<'_, BTreeSet<PathBuf>> {
+        self.in_flight
+            .lock()
+            .expect("the in-flight files should not be poisoned")
+    }
+
+    async fn download(&self, resource: Resource) -> Result<(), ResourceError> {
+        let path = resource.path.clone();
+        self.download_resource(resource)
+            .await
+            .map_err(|error| ResourceError::new(path, error))
+    }
+
+    #[tracing::instrument(
+        name = "download",
+        skip_all,
+        fields(url = %resource.url, path = %resource.path.display())
+    )]
+    async fn download_resource(&self, resource: Resource) -> Result<(), DownloadError> {
+        let name = &self.name;
+        let Resource { url, path } = &resource;
+
+        if disk_full().is_some() {
+            // the run is reported as failed because of the full disk
+            self.counts.failed();
+            return Err(DownloadError::Cancelled);
+        }
+        if self.cancel.is_cancelled() {
+            self.counts.failed();
+            return Err(DownloadError::Aborted);
+        }
+
+        let resolved_path = match self.check_path(path) {
+            Ok(resolved_path) => resolved_path,
+            Err(error) => {
+                self.counts.failed();
+                return Err(error);
+            }
+        };
+        if let Err(error) = self.check_https(url) {
+            self.counts.failed();
+            return Err(error);
+        }
+        let path_str = resolved_path.to_string_lossy();
+
+        let state = self.resource_state(&resource, &resolved_path).await;
+
+        if self.progress.is_none() {
+            state.print(name, url, &path_str);
+        }
 
         if state.download() {
-            let result = self.do_download(&resolved_path, url).await;
-            match &result {
-                Ok(()) => {
+            let result = tokio::select! {
+                result = self.download_with_retries(&resource, &resolved_path) => result,
+                () = self.cancel.cancelled() => {
+                    // don't leave a partial file behind, but keep existing files that the
+                    // download has not started to replace yet
+                    if self.in_flight_files().remove(&resolved_path) {
+                        let _ = fs::remove_file(&resolved_path).await;
+                    }
+                    Err(DownloadError::Aborted)
+                }
+            };
+            self.in_flight_files().remove(&resolved_path);
+            match result {
+                Ok(entry) => {
                     if let Some(index) = &self.index {
                         let mut index = index.lock().await;
-                        index.update(resource.clone());
+                        index.update(entry);
+                    }
+                    self.counts.processed();
+                    if self.progress.is_none() {
+                        status!("[{name}] Downloading {url} to {path_str} finished");
                     }
-                    println!("[{name}] Downloading {url} to {path_str} finished");
                 }
                 Err(error) => {
-                    println!("[{name}] Downloading {url} to {path_str} failed: {error:?}");
+                    self.counts.failed();
+                    if let Some(progress) = &self.progress {
+                        progress.clear();
+                    }
+                    status!("[{name}] Downloading {url} to {path_str} failed: {error:?}");
+                    if self.manifest.fail_fast && !self.cancel.is_cancelled() {
+                        status!("[{name}] Cancelling the remaining downloads");
+                        self.cancel.cancel();
+                    }
+                    return Err(error);
                 }
             }
-            result?;
+        } else {
+            self.counts.skipped();
+        }
+        if let Some(progress) = &self.progress {
+            progress.update(self.counts.get());
+        }
+
+        if let Some(queue) = &self.queue {
+            queue.lock().await.complete(path).await?;
         }
 
         Ok(())
     }
 
-    async fn do_download(&self, resolved_path: &Path, url: &String) -> Result<(), DownloadError> {
+    /// Downloads the resource to the given path, retrying after network errors as often as
+    /// configured for the resource or the job. Returns the index entry for the downloaded file.
+    async fn download_with_retries(
+        &self,
+        resource: &Resource,
+        resolved_path: &Path,
+    ) -> Result<Entry, DownloadError> {
+        let name = &self.name;
+        let Resource { url, path } = resource;
+        let retries = self
+            .options
+            .get(path)
+            .and_then(|options| options.retries)
+            .unwrap_or(self.manifest.retries);
+
+        let mut retry = 0;
+        loop {
+            let permits = self.limits.acquire(url).await;
+            let start = Instant::now();
+            let result = self.do_download(resource, resolved_path).await;
+            permits.record(&result, start.elapsed());
+            drop(permits);
+            match result {
+                Err(error) if retry < retries && error.is_retryable() => {
+                    retry += 1;
+                    let delay = retry::backoff(retry);
+                    let attempt = format!("attempt {} of {}", retry + 1, retries + 1);
+                    status!("[{name}] Downloading {url} failed: {error:?}");
+                    status!("[{name}] Retrying {url} in {delay:?} ({attempt})...");
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Downloads the resource to the given path. Returns the index entry for the downloaded file.
+    async fn do_download(
+        &self,
+        resource: &Resource,
+        resolved_path: &Path,
+    ) -> Result<Entry, DownloadError> {
+        if Self::total_limit_exceeded().is_some() {
+            // the run is reported as failed because of the download limit
+            return Err(DownloadError::Cancelled);
+        }
+
         if let Some(parent) = resolved_path.parent() {
             fs::create_dir_all(parent).await?;
         }
-        let mut response = reqwest::get(url).await?.error_for_status()?;
-        let mut file = fs::File::create(&resolved_path).await?;
-        while let Some(chunk) = response.chunk().await? {
-            file.write_all(&chunk).await?;
+        let response = if self.manifest.auth.is_empty() {
+            let client = &self.clients.client;
+            let request = self
+                .manifest
+                .request
+                .build(client, resource, &self.responses)?;
+            self.send(request)
+                .instrument(tracing::info_span!("request"))
+                .await?
+        } else {
+            self.send_authenticated(resource)
+                .instrument(tracing::info_span!("request"))
+                .await?
+        };
+        let mut response = response.error_for_status()?;
+        let headers = self
+            .manifest
+            .save_headers
+            .then(|| sidecar::ResponseHeaders::new(&response));
+        let mut entry = Entry::new(resource.clone());
+        entry.request = self.manifest.request.fingerprint(resource)?;
+        if self.manifest.respect_cache_control {
+            entry.expires = cache_control::expiry(response.headers(), utils::recorded_time());
+        }
+        let Validators {
+            etag,
+            last_modified,
+        } = Validators::from_headers(response.headers());
+        entry.etag = etag;
+        entry.last_modified = last_modified;
+        // the file may be a link to a shared cache entry, which must not be overwritten
+        self.in_flight_files().insert(resolved_path.to_path_buf());
+        match fs::remove_file(&resolved_path).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error)?,
+            _ => {}
+        }
+        let mut file = fs::File::create(&resolved_path)
+            .await
+            .map_err(|error| Self::file_error(error, resolved_path))?;
+        let options = self
+            .options
+            .get(&resource.path)
+            .copied()
+            .unwrap_or_default();
+        let min_size = options.min_size.or(self.manifest.min_size);
+        let max_size = options.max_size.or(self.manifest.max_size);
+        let mut algorithms = self.manifest.hashes.clone();
+        if self.manifest.integrity && algorithms.is_empty() {
+            algorithms.push(HashAlgorithm::Sha256);
+        }
+        // the SHA-256 hash is always recorded in the index entry
+        let mut hashers = Hashers::new(&[&algorithms[..], &[HashAlgorithm::Sha256]].concat());
+        let result = async {
+            let mut size = 0;
+            while let Some(chunk) = response.chunk().await? {
+                Self::count_downloaded(chunk.len())?;
+                size += chunk.len() as u64;
+                if let Some(max_size) = max_size.filter(|&max_size| size > max_size) {
+                    return Err(DownloadError::TooLarge(max_size));
+                }
+                if let Some(progress) = &self.progress {
+                    progress.add_bytes(chunk.len());
+                }
+                hashers.update(&chunk);
+                file.write_all(&chunk)
+                    .await
+                    .map_err(|error| Self::file_error(error, resolved_path))?;
+            }
+            file.flush()
+                .await
+                .map_err(|error| Self::file_error(error, resolved_path))?;
+            if let Some(min_size) = min_size.filter(|&min_size| size < min_size) {
+                return Err(DownloadError::TooSmall { size, min_size });
+            }
+            Ok::<_, DownloadError>(())
+        }
+        .instrument(tracing::info_span!("body"))
+        .await;
+        drop(file);
+        if let Err(error) = result {
+            // don't leave a partial file behind
+            let _ = fs::remove_file(resolved_path).await;
+            return Err(error);
+        }
+
+        if let Some(fonts) = &self.fonts {
+            fonts.verify(resolved_path).await?;
+        }
+
+        if let Some(headers) = headers {
+            let path = sidecar::path(&resource.path);
+            headers.write(&resolve_path(&path)?).await?;
+            entry.headers = Some(path);
+        }
+
+        let mut hashes = hashers.finalize();
+        let hash = hashes
+            .hex(HashAlgorithm::Sha256)
+            .expect("the SHA-256 hash should have been computed");
+        if self.manifest.link != LinkMode::Copy {
+            let cache = self.manifest.resolve_cache_path().await?;
+            let mode = self.manifest.link;
+            entry.link = Some(link::link_to_cache(mode, &cache, resolved_path, &hash).await?);
+        }
+        entry.hash = Some(hash);
+        hashes.retain(&algorithms);
+        if !self.manifest.hashes.is_empty() {
+            entry.hashes = hashes.to_hex();
+        }
+        if self.manifest.integrity {
+            entry.integrity = Some(hashes.integrity());
+        }
+        Ok(entry)
+    }
+
+    /// Sends a download request, through the HTTP cache if one is configured.
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, DownloadError> {
+        #[cfg(feature = "http-cache")]
+        if let Some(client) = &self.http_cache {
+            let response = client
+                .execute(request.build()?)
+                .await
+                .map_err(|error| match error {
+                    reqwest_middleware::Error::Reqwest(error) => redirect::network_error(error),
+                    error => error.into(),
+                })?;
+            return Ok(response);
+        }
+        let response = request.send().await.map_err(redirect::network_error)?;
+        Ok(response)
+    }
+
+    /// Sends a download request for a job with authorization. Redirects are followed one at a
+    /// time, so that each request only carries the authorization of the host it goes to.
+    async fn send_authenticated(
+        &self,
+        resource: &Resource,
+    ) -> Result<reqwest::Response, DownloadError> {
+        let url = RequestManifest::url(resource, &self.responses)?;
+        let mut url = reqwest::Url::parse(&url)?;
+        let mut method = self.manifest.request.method();
+        let mut body = self.manifest.request.body(resource, &self.responses)?;
+        let mut chain = vec![url.clone()];
+        loop {
+            let request = self
+                .clients
+                .no_redirect
+                .request(method.clone(), url.clone());
+            let mut request = self.manifest.request.with_body(request, body.clone());
+            if let Some(auth) = self.manifest.auth_for(&url) {
+                request = auth.apply(request, resource, &self.responses)?;
+            }
+            let response = self.send(request).await?;
+            let Some(target) = auth::redirect_target(&url, &response) else {
+                return Ok(response);
+            };
+            chain.push(target.clone());
+            if chain.len() > redirect::MAX_REDIRECTS + 1 {
+                return Err(RedirectError::new(chain).into());
+            }
+            // only these redirects repeat the request as is; others continue with a `GET` request
+            let status = response.status();
+            if status != StatusCode::TEMPORARY_REDIRECT && status != StatusCode::PERMANENT_REDIRECT
+            {
+                if method != Method::HEAD {
+                    method = Method::GET;
+                }
+                body = None;
+            }
+            url = target;
+        }
+    }
+
+    /// Sends a `HEAD` request for the given index entry's resource, with the authorization of its
+    /// host. With authorization, redirects are not followed.
+    async fn head(&self, entry: &Entry) -> reqwest::Result<reqwest::Response> {
+        let client = &self.clients.client;
+        let resource = Resource {
+            path: entry.path.clone(),
+            url: entry.url.clone(),
+        };
+        // the download will report problems with rendering the URL
+        let url =
+            RequestManifest::url(&resource, &self.responses).unwrap_or_else(|_| entry.url.clone());
+        let Ok(parsed_url) = reqwest::Url::parse(&url) else {
+            return client.head(&url).send().await;
+        };
+        let Some(auth) = self.manifest.auth_for(&parsed_url) else {
+            return client.head(parsed_url).send().await;
+        };
+        let request = self.clients.no_redirect.head(parsed_url);
+        match auth.apply(request, &resource, &self.responses) {
+            Ok(request) => request.send().await,
+            // the download will report the problem
+            Err(_) => client.head(&url).send().await,
+        }
+    }
+
+    /// Converts an error writing the given file. If the disk is full, remaining downloads are
+    /// cancelled, since they would fail as well.
+    fn file_error(error: io::Error, path: &Path) -> DownloadError {
+        if error.kind() != io::ErrorKind::StorageFull {
+            return error.into();
+        }
+        let mut disk_full = DISK_FULL
+            .lock()
+            .expect("the disk full state should not be poisoned");
+        let path = disk_full.get_or_insert_with(|| path.to_path_buf());
+        DownloadError::DiskFull(path.clone())
+    }
+
+    /// Adds to the total number of downloaded bytes, failing if that exceeds the
+    /// `--max-total-bytes` limit.
+    fn count_downloaded(bytes: usize) -> Result<(), DownloadError> {
+        let bytes = bytes as u64;
+        let downloaded = DOWNLOADED_BYTES.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        match ARGS.max_total_bytes {
+            Some(limit) if downloaded > limit => {
+                Err(DownloadError::TotalLimit { limit, downloaded })
+            }
+            _ => Ok(()),
         }
-        file.flush().await?;
+    }
+
+    /// Returns the `--max-total-bytes` limit and the number of bytes downloaded during this run,
+    /// if the limit was exceeded.
+    fn total_limit_exceeded() -> Option<(u64, u64)> {
+        let limit = ARGS.max_total_bytes?;
+        let downloaded = DOWNLOADED_BYTES.load(Ordering::Relaxed);
+        (downloaded > limit).then_some((limit, downloaded))
+    }
+
+    /// Deletes the files of all index entries that are not among the given resources, and removes
+    /// these entries from the index. Does nothing if eviction is not enabled.
+    async fn evict(&self, resources: &BTreeMap<PathBuf, String>) -> ExecutionResult<()> {
+        let name = &self.name;
+        let (true, Some(index)) = (self.manifest.evict, &self.index) else {
+            return Ok(());
+        };
+
+        let mut index = index.lock().await;
+        for path in index.evictable(resources) {
+            let path_str = path.to_string_lossy();
+            status!("[{name}] Evicting {path_str}");
+            let resolved_path = resolve_path(&path).map_err(ExecutionError::Evict)?;
+            let headers = index.get(&path).and_then(|entry| entry.headers.clone());
+            let headers = headers.map(|headers| resolve_path(&headers)).transpose();
+            let headers = headers.map_err(ExecutionError::Evict)?;
+            // if the file is a link, only the link is deleted; the cache entry may be shared
+            for file in std::iter::once(resolved_path).chain(headers) {
+                match fs::remove_file(&file).await {
+                    // the file was already deleted
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                    result => result.map_err(ExecutionError::Evict)?,
+                }
+            }
+            index.remove(&path);
+        }
+
         Ok(())
     }
 
-    async fn run_impl(self: &mut Arc<WebResource>) -> ExecutionResult<()> {
-        Arc::get_mut(self)
-            .expect("web-resource ref count should be one before starting the processing")
-            .populate_index()
-            .await?;
-
-        let downloads = self
-            .query()
-            .await?
-            .resources
+    /// Prints the changes that downloading the given resources would make to the files currently
+    /// on disk and in the index: which files would be added, overwritten, or evicted.
+    async fn print_diff(&self, resources: &BTreeMap<PathBuf, String>) {
+        let name = &self.name;
+        let (mut added, mut changed, mut removed, mut unchanged) = (0, 0, 0, 0);
+
+        for (path, url) in resources {
+            let path_str = path.to_string_lossy();
+            let resolved_path = match self.check_path(path) {
+                Ok(resolved_path) => resolved_path,
+                Err(error) => {
+                    status!("[{name}] ! {path_str} ({error})");
+                    continue;
+                }
+            };
+
+            let resource = Resource {
+                path: path.clone(),
+                url: url.clone(),
+            };
+            let state = self.resource_state(&resource, &resolved_path).await;
+            match state {
+                ResourceState::Missing => {
+                    added += 1;
+                    status!("[{name}] + {path_str} (new, from {url})");
+                }
+                ResourceState::Existing => {
+                    unchanged += 1;
+                }
+                _ => {
+                    changed += 1;
+                    let reason = state.reason().unwrap_or_default();
+                    status!("[{name}] ~ {path_str} ({reason})");
+                }
+            }
+        }
+
+        if let (true, Some(index)) = (self.manifest.evict, &self.index) {
+            let index = index.lock().await;
+            for path in index.evictable(resources) {
+                removed += 1;
+                let path_str = path.to_string_lossy();
+                status!("[{name}] - {path_str} (no longer needed)");
+            }
+        }
+
+        status!(
+            "[{name}] dry run: {added} added, {changed} changed, {removed} removed, {unchanged} unchanged"
+        );
+    }
+
+    /// Verifies the TLS certificates of all hosts the given resources are downloaded from,
+    /// without downloading anything. Resources that are not downloaded via HTTPS are ignored.
+    async fn verify_tls(&self, resources: &BTreeMap<PathBuf, String>) -> ExecutionResult<()> {
+        let name = &self.name;
+
+        let hosts: BTreeSet<_> = resources
+            .values()
+            .filter_map(|url| url::Url::parse(url).ok())
+            .filter(|url| url.scheme() == "https")
+            .filter_map(|url| {
+                let host = url.host_str()?;
+                Some(match url.port() {
+                    Some(port) => format!("{host}:{port}"),
+                    None => host.to_string(),
+                })
+            })
+            .collect();
+
+        let mut errors = Vec::new();
+        for host in hosts {
+            match tls::verify(&self.clients.client, &host).await {
+                Ok(tls::Certificate {
+                    not_after,
+                    days_left,
+                }) => {
+                    let expiry = format!("valid until {not_after} ({days_left} days left)");
+                    status!("[{name}] {host}: certificate {expiry}");
+                }
+                Err(error) => {
+                    eprintln!("[{name}] {error}");
+                    errors.push(error);
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(error::MultipleTlsError::new(errors).into());
+        }
+
+        Ok(())
+    }
+
+    /// Sends a `HEAD` request for each resource, with the authorization of its host, and reports
+    /// the resources whose URLs are not reachable. Nothing is downloaded.
+    async fn check_links(&self, resources: &BTreeMap<PathBuf, String>) -> ExecutionResult<()> {
+        let name = &self.name;
+
+        let mut errors = Vec::new();
+        for (path, url) in resources {
+            let entry = Entry::new(Resource {
+                path: path.clone(),
+                url: url.clone(),
+            });
+            let url = url.clone();
+            let error = match self.head(&entry).await {
+                // with authorization, redirects are not followed; they still show the link works
+                Ok(response)
+                    if response.status().is_success() || response.status().is_redirection() =>
+                {
+                    status!("[{name}] {url}: {}", response.status());
+                    continue;
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    error::LinkError::Status { url, status }
+                }
+                Err(error) => {
+                    let reason = error_chain(&error);
+                    error::LinkError::Request { url, reason }
+                }
+            };
+            eprintln!("[{name}] {error}");
+            errors.push(error);
+        }
+
+        if !errors.is_empty() {
+            return Err(error::MultipleLinkError::new(errors).into());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the file of each resource exists and, if an index is kept, was downloaded from
+    /// the resource's current URL. Nothing is downloaded.
+    async fn check_complete(&self, resources: &BTreeMap<PathBuf, String>) -> ExecutionResult<()> {
+        let name = &self.name;
+
+        let mut errors = Vec::new();
+        for (path, url) in resources {
+            let error = match self.check_path(path) {
+                Err(error) => error::MissingError::Path {
+                    path: path.clone(),
+                    reason: error.to_string(),
+                },
+                Ok(resolved_path) if !fs::try_exists(&resolved_path).await.unwrap_or(false) => {
+                    error::MissingError::Missing(path.clone())
+                }
+                Ok(_) if !self.is_indexed(path, url).await => error::MissingError::Outdated {
+                    path: path.clone(),
+                    url: url.clone(),
+                },
+                Ok(_) => continue,
+            };
+            eprintln!("[{name}] {error}");
+            errors.push(error);
+        }
+
+        if !errors.is_empty() {
+            return Err(error::MultipleMissingError::new(errors).into());
+        }
+
+        status!("[{name}] All {} resources are present", resources.len());
+        Ok(())
+    }
+
+    /// Checks whether the index records the resource as downloaded from the given URL. Without
+    /// an index, this is assumed.
+    async fn is_indexed(&self, path: &Path, url: &str) -> bool {
+        let Some(index) = &self.index else {
+            return true;
+        };
+        let resource = Resource {
+            path: path.to_path_buf(),
+            url: url.to_string(),
+        };
+        let Ok(request) = self.manifest.request.fingerprint(&resource) else {
+            return false;
+        };
+        let index = index.lock().await;
+        index.is_up_to_date(path, url, request.as_deref())
+    }
+
+    /// Prints the commands this job would execute as part of a shell script. To print download
+    /// commands, the query is executed.
+    async fn dump_commands(&self, commands: DumpCommands) -> ExecutionResult<()> {
+        let mut script = format!("\n# {}\n{}\n", self.name, self.query.shell_command());
+        if commands == DumpCommands::Downloads {
+            for (path, url) in self.resources().await? {
+                let Ok(resolved_path) = self.check_path(&path) else {
+                    // this resource would not be downloaded
+                    continue;
+                };
+                let path = utils::shell_quote(&resolved_path.to_string_lossy());
+                let url = utils::shell_quote(&url);
+                writeln!(script, "curl -fL --create-dirs -o {path} {url}")
+                    .expect("writing to a string failed");
+            }
+        }
+        print!("{script}");
+        Ok(())
+    }
+
+    /// Prints the absolute path each resource would be saved to, alongside its URL.
+    async fn print_resolved_paths(&self) -> ExecutionResult<()> {
+        let name = &self.name;
+        let mut output = String::new();
+        for (path, url) in self.resources().await? {
+            let resolved_path = match self.check_path(&path) {
+                Ok(resolved_path) => path::absolute(&resolved_path)
+                    .unwrap_or(resolved_path)
+                    .to_string_lossy()
+                    .into_owned(),
+                Err(error) => format!("(not downloaded: {error})"),
+            };
+            writeln!(output, "[{name}] {url} -> {resolved_path}")
+                .expect("writing to a string failed");
+        }
+        print!("{output}");
+        Ok(())
+    }
+
+    /// Queries the document for resources, and fetches the listings among them.
+    async fn resources(&self) -> ExecutionResult<BTreeMap<PathBuf, String>> {
+        Ok(self.query_resources().await?.resources)
+    }
+
+    /// Resolves a relative URL against the configured base URL. Absolute URLs, and all URLs if no
+    /// base URL is configured, are returned unchanged; invalid URLs are rejected when they are
+    /// used.
+    fn resolve_url(&self, url: String) -> String {
+        let Some(base_url) = &self.manifest.base_url else {
+            return url;
+        };
+        if !matches!(
+            url::Url::parse(&url),
+            Err(url::ParseError::RelativeUrlWithoutBase)
+        ) {
+            return url;
+        }
+        let base_url = url::Url::parse(base_url).expect("the base URL should be valid");
+        match base_url.join(&url) {
+            Ok(joined) => joined.into(),
+            Err(_) => url,
+        }
+    }
+
+    /// Queries the document for resources, and fetches the listings among them. The listed
+    /// resources are added to the returned resources, and the listings are removed.
+    async fn query_resources(&self) -> ExecutionResult<QueryData> {
+        let QueryData {
+            resources,
+            listings,
+            mut options,
+        } = self.query().await?;
+        let mut resources: BTreeMap<_, _> = resources
+            .into_iter()
+            .map(|(path, url)| (path, self.resolve_url(url)))
+            .collect();
+        let listings: BTreeMap<_, _> = listings
             .into_iter()
-            .map(|(path, url)| Arc::clone(self).download(Resource { path, url }));
-        let errors = utils::spawn_set(downloads).await;
+            .map(|(path, listing)| (path, self.resolve_url(listing)))
+            .collect();
+
+        for (path, listing) in listings {
+            let path_str = path.to_string_lossy();
+            status!("[{}] Fetching listing {listing} for {path_str}", self.name);
+            let listing = Listing { path, listing };
+            for Resource { path, url } in listing
+                .fetch(&self.clients.client, &self.manifest.listing)
+                .await?
+            {
+                // resources given directly in the document take precedence
+                resources.entry(path).or_insert(url);
+            }
+        }
+
+        if let Some(fonts) = &self.fonts {
+            resources = fonts.relocate(resources);
+            options = fonts.relocate(options);
+        }
+
+        Ok(QueryData {
+            resources,
+            listings: BTreeMap::new(),
+            options,
+        })
+    }
+
+    async fn run_impl(self: &mut Arc<WebResource>) -> ExecutionResult<()> {
+        self.counts.reset();
+
+        if let Some(commands) = ARGS.dump_commands {
+            return self.dump_commands(commands).await;
+        }
+
+        if ARGS.print_resolved_paths {
+            return self.print_resolved_paths().await;
+        }
+
+        // without downloading, another iteration would not change the query result
+        let max_iterations = match self.manifest.iterate {
+            Some(max_iterations)
+                if ARGS.dry_run.is_none() && !ARGS.verify_tls_only && !ARGS.check_complete =>
+            {
+                max_iterations
+            }
+            _ => {
+                self.run_pass().await?;
+                self.print_font_path();
+                return Ok(());
+            }
+        };
+
+        let mut previous = None;
+        for iteration in 1..=max_iterations {
+            let resources = self.run_pass().await?;
+            if previous.as_ref() == Some(&resources) {
+                status!(
+                    "[{}] Query result is stable after {iteration} iterations",
+                    self.name
+                );
+                self.print_font_path();
+                return Ok(());
+            }
+            previous = Some(resources);
+        }
+
+        Err(ExecutionError::Iterate(max_iterations))
+    }
+
+    /// Prints how to make the downloaded fonts available to Typst, if this is a `fonts` job.
+    fn print_font_path(&self) {
+        let Some(directory) = self
+            .fonts
+            .as_ref()
+            .and_then(|fonts| ARGS.resolve(&fonts.directory))
+        else {
+            return;
+        };
+        if ARGS.dry_run.is_some() || ARGS.verify_tls_only || ARGS.check_complete {
+            return;
+        }
+        let directory = utils::shell_quote(&directory.to_string_lossy());
+        status!(
+            "[{}] Fonts are ready; compile with `--font-path {directory}`",
+            self.name
+        );
+    }
+
+    /// Returns exclusive access to the preprocessor. The downloads of an interrupted run, e.g. one
+    /// that exceeded the job's timeout, are aborted but may not have released their references to
+    /// the preprocessor yet; this waits until they have.
+    async fn exclusive(self: &mut Arc<WebResource>) -> &mut WebResource {
+        let downloads = self.downloads.clone();
+        downloads.close();
+        downloads.wait().await;
+        downloads.reopen();
+        Arc::get_mut(self).expect("no other references to the web-resource should remain")
+    }
+
+    /// Requests the named resources and keeps their responses, so that the requests for the
+    /// resources can reference them.
+    async fn fetch_named_resources(self: &mut Arc<WebResource>) -> ExecutionResult<()> {
+        let named = &self.manifest.resources;
+        if named.is_empty() {
+            return Ok(());
+        }
+        let order = named::order(named).expect("the named resources should have been validated");
+        status!("[{}] Requesting {} named resources", self.name, order.len());
+        let responses = named::fetch_all(&self.clients.client, named, &order).await?;
+        self.exclusive().await.responses = responses;
+        Ok(())
+    }
+
+    /// Queries the resources and downloads them once. Returns the resources that were processed.
+    async fn run_pass(self: &mut Arc<WebResource>) -> ExecutionResult<BTreeMap<PathBuf, String>> {
+        let this = self.exclusive().await;
+        this.populate_index().await?;
+        this.queue = None;
+        this.responses = serde_json::Value::Null;
+        this.cancel = CancellationToken::new();
+        #[cfg(feature = "http-cache")]
+        if let Some(location) = this.manifest.resolve_http_cache_path().await {
+            let mode = this.manifest.http_cache.as_ref().map(|cache| cache.mode);
+            let mode = mode.expect("the HTTP cache should be configured");
+            let location = location.map_err(ExecutionError::HttpCache)?;
+            // with authorization, redirects are followed by the job instead of the client
+            let inner = if this.manifest.auth.is_empty() {
+                &this.clients.client
+            } else {
+                &this.clients.no_redirect
+            };
+            this.http_cache = Some(http_cache::client(mode, location, inner.clone()));
+        }
+
+        // a work queue is kept next to the index
+        let queue_location = this
+            .index
+            .as_mut()
+            .map(|index| WorkQueue::location_for(index.get_mut().location()));
+        let resumed = match &queue_location {
+            Some(location)
+                if !ARGS.no_resume
+                    && ARGS.dry_run.is_none()
+                    && !ARGS.verify_tls_only
+                    && !ARGS.check_complete =>
+            {
+                WorkQueue::read_existing(location.clone()).await?
+            }
+            _ => None,
+        };
+
+        let (resources, options) = match &resumed {
+            Some(queue) => {
+                let count = queue.resources.len();
+                status!(
+                    "[{}] Resuming interrupted run with {count} remaining downloads",
+                    self.name
+                );
+                (queue.resources.clone(), queue.options.clone())
+            }
+            None => {
+                let QueryData {
+                    resources, options, ..
+                } = self.query_resources().await?;
+                (resources, options)
+            }
+        };
+
+        // remember the files this job depends on, for watching
+        let mut dependencies: Vec<_> = resources
+            .keys()
+            .filter_map(|path| ARGS.resolve(path))
+            .collect();
+        let this = self.exclusive().await;
+        if let Some(index) = &mut this.index {
+            dependencies.push(index.get_mut().location().to_path_buf());
+        }
+        this.dependencies = dependencies;
+        this.options = options.clone();
+
+        if let Some(mode) = ARGS.dry_run {
+            self.print_diff(&resources).await;
+            if mode == DryRun::Check {
+                self.fetch_named_resources().await?;
+                self.check_links(&resources).await?;
+            }
+            return Ok(resources);
+        }
+
+        if ARGS.verify_tls_only {
+            self.verify_tls(&resources).await?;
+            return Ok(resources);
+        }
+
+        if ARGS.check_complete {
+            self.check_complete(&resources).await?;
+            return Ok(resources);
+        }
+
+        // when resuming, not all resources needed by the document are known
+        if resumed.is_none() {
+            self.evict(&resources).await?;
+        }
+
+        self.fetch_named_resources().await?;
+
+        if let Some(location) = queue_location {
+            let mut queue = WorkQueue::new(location, resources.clone(), options);
+            queue.write().await?;
+            self.exclusive().await.queue = Some(Mutex::new(queue));
+        }
+        let progress = ARGS
+            .progress
+            .then(|| Progress::new(&self.name, resources.len()));
+        self.exclusive().await.progress = progress;
+
+        let this = DownloadRef {
+            this: Arc::clone(self),
+            _token: self.downloads.token(),
+        };
+        let download = move |(path, url): (PathBuf, String)| {
+            let this = this.clone();
+            async move { this.this.download(Resource { path, url }).await }
+        };
+        let workers = self
+            .manifest
+            .concurrency
+            .map_or(DEFAULT_CONCURRENCY, NonZeroUsize::get);
+        let mut errors = utils::worker_pool(resources.clone(), workers, download).await;
+        if let Some(progress) = &self.progress {
+            progress.finish(self.counts.get());
+        }
 
         if let Some(index) = &self.index {
-            let index = index.lock().await;
-            index.write().await?;
+            index.lock().await.write().await?;
+        }
+
+        if let Some(queue) = &self.queue {
+            // after a successful run, there's nothing to resume
+            if errors.is_empty() {
+                queue.lock().await.delete().await?;
+            }
+        }
+
+        if let (false, Some(path)) = (errors.is_empty(), disk_full()) {
+            // report one clear error instead of all the failed and cancelled downloads
+            return Err(ExecutionError::DiskFull(path));
+        }
+        if let (false, Some((limit, downloaded))) =
+            (errors.is_empty(), Self::total_limit_exceeded())
+        {
+            // report one clear error instead of all the failed and cancelled downloads
+            return Err(ExecutionError::TotalLimit { limit, downloaded });
+        }
+
+        if self.cancel.is_cancelled() {
+            // only report the downloads that caused the cancellation
+            errors.retain(|error| !matches!(error.error, DownloadError::Aborted));
         }
 
         if !errors.is_empty() {
             return Err(error::MultipleDownloadError::new(errors).into());
         }
 
-        Ok::<_, ExecutionError>(())
+        Ok(resources)
     }
 }
 
+/// Resolves a resource's path in the project root, failing if it is outside the root.
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    ARGS.resolve(path).ok_or_else(|| {
+        let path_str = path.to_string_lossy();
+        let msg = format!("{path_str} is outside the project root");
+        io::Error::new(io::ErrorKind::PermissionDenied, msg)
+    })
+}
+
 #[async_trait]
 impl Preprocessor for Arc<WebResource> {
     fn name(&self) -> &str {
         &self.name
     }
 
+    fn supports(&self, _mode: Mode) -> bool {
+        true
+    }
+
     async fn run(&mut self) -> preprocessor::ExecutionResult<()> {
         self.run_impl()
             .await
             .map_err(preprocessor::ExecutionError::new)?;
         Ok(())
     }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.dependencies.clone()
+    }
+
+    fn counts(&self) -> Counts {
+        self.counts.get()
+    }
+
+    fn host_concurrency(&self) -> BTreeMap<String, usize> {
+        self.limits.adaptive_limits()
+    }
+
+    async fn abort(&mut self) {
+        // the interrupted downloads can't be trusted to be complete; wait until they have stopped,
+        // so that none of them writes to its file after it was removed
+        self.exclusive().await;
+        let files = std::mem::take(&mut *self.in_flight_files());
+        for file in files {
+            let _ = fs::remove_file(&file).await;
+        }
+        if let Some(progress) = &self.progress {
+            progress.clear();
+        }
+    }
 }
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{RequestBuilder, Response, Url};
+use serde_json::Value;
+
+use super::{build_client, AuthManifest, DownloadError, Manifest, RequestManifest, Resource};
+
+/// The HTTP client used by jobs with authorization. It doesn't follow redirects, so that each
+/// request can be given the authorization of the host it actually goes to.
+pub static NO_REDIRECT_CLIENT: Lazy<reqwest::Client> =
+    Lazy::new(|| build_client(Policy::none(), &BTreeMap::new()));
+
+impl Manifest {
+    /// Returns the authorization for requests to the given URL's host, if one is configured.
+    pub fn auth_for(&self, url: &Url) -> Option<&AuthManifest> {
+        self.auth.get(url.host_str()?)
+    }
+}
+
+impl AuthManifest {
+    /// Adds the authorization header for downloading the given resource to the request. The
+    /// header's value is a Handlebars template, rendered like the request body.
+    pub fn apply(
+        &self,
+        request: RequestBuilder,
+        resource: &Resource,
+        responses: &Value,
+    ) -> Result<RequestBuilder, DownloadError> {
+        let value = RequestManifest::render(&self.value, resource, responses)?;
+        Ok(request.header(&self.header, value))
+    }
+}
+
+/// Returns where the given response redirects to, resolved against the requested URL, if it is a
+/// redirect.
+pub fn redirect_target(url: &Url, response: &Response) -> Option<Url> {
+    if !response.status().is_redirection() {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    url.join(location).ok()
+}
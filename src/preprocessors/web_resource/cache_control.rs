@@ -0,0 +1,28 @@
+use reqwest::header::{HeaderMap, CACHE_CONTROL};
+
+/// Determines when a response expires, according to its `Cache-Control` header. The result is
+/// given in seconds since the Unix epoch. Returns `None` if the header doesn't specify a lifetime.
+///
+/// `no-cache` and `no-store` directives result in the response expiring immediately; otherwise the
+/// `max-age` directive is used.
+pub fn expiry(headers: &HeaderMap, now: u64) -> Option<u64> {
+    let cache_control = headers.get(CACHE_CONTROL)?.to_str().ok()?;
+
+    let mut max_age = None;
+    for directive in cache_control.split(',') {
+        let (key, value) = match directive.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+
+        if key.eq_ignore_ascii_case("no-cache") || key.eq_ignore_ascii_case("no-store") {
+            // the response must always be revalidated
+            return Some(now);
+        }
+        if key.eq_ignore_ascii_case("max-age") {
+            max_age = value.and_then(|value| value.parse::<u64>().ok());
+        }
+    }
+
+    max_age.map(|max_age| now.saturating_add(max_age))
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use url::Url;
+
+use super::DownloadError;
+
+/// The concurrency an adaptive host limit starts with
+const INITIAL_ADAPTIVE_LIMIT: f64 = 2.0;
+
+/// The highest concurrency an adaptive host limit can reach
+const MAX_ADAPTIVE_LIMIT: f64 = 64.0;
+
+/// How much slower than the fastest download from a host a download may be while the host's
+/// adaptive limit is still increased
+const LATENCY_TOLERANCE: u32 = 4;
+
+/// Limits on the number of concurrent downloads per host. The overall number of concurrent
+/// downloads is limited by the number of download workers.
+#[derive(Debug, Default)]
+pub struct ConcurrencyLimits {
+    hosts: HashMap<String, Arc<Semaphore>>,
+    /// The limits of hosts without a fixed limit, if these are tuned adaptively
+    adaptive: Option<Mutex<HashMap<String, Arc<AdaptiveLimit>>>>,
+}
+
+/// A permit for one download; the download may proceed as long as this is held.
+#[derive(Debug)]
+pub struct Permit {
+    host: Option<HostPermit>,
+}
+
+#[derive(Debug)]
+enum HostPermit {
+    Fixed { _permit: OwnedSemaphorePermit },
+    Adaptive(AdaptivePermit),
+}
+
+/// The concurrency limit of one host, adjusted AIMD-style: it grows by one after as many
+/// successful downloads as the current limit, and is halved when the host appears overloaded.
+#[derive(Debug)]
+struct AdaptiveLimit {
+    state: Mutex<AdaptiveState>,
+    notify: Notify,
+}
+
+#[derive(Debug)]
+struct AdaptiveState {
+    limit: f64,
+    active: usize,
+    min_latency: Option<Duration>,
+}
+
+/// A permit of an [AdaptiveLimit]; dropping it allows another download to start.
+#[derive(Debug)]
+struct AdaptivePermit {
+    limit: Arc<AdaptiveLimit>,
+}
+
+impl ConcurrencyLimits {
+    /// Creates limits from the per-host limits. Hosts without a specific limit are only subject to
+    /// the overall limit, unless `adaptive` is set; then, their limits are tuned according to
+    /// how the hosts respond.
+    pub fn new(hosts: &BTreeMap<String, NonZeroUsize>, adaptive: bool) -> Self {
+        let hosts = hosts
+            .iter()
+            .map(|(host, limit)| {
+                let semaphore = Arc::new(Semaphore::new(limit.get()));
+                (host.to_ascii_lowercase(), semaphore)
+            })
+            .collect();
+        let adaptive = adaptive.then(Default::default);
+        Self { hosts, adaptive }
+    }
+
+    /// Waits until a download from the given URL may start.
+    pub async fn acquire(&self, url: &str) -> Permit {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+        let Some(host) = host else {
+            return Permit { host: None };
+        };
+        if let Some(semaphore) = self.hosts.get(&host) {
+            let permit = acquire(semaphore).await;
+            let host = Some(HostPermit::Fixed { _permit: permit });
+            return Permit { host };
+        }
+        let Some(adaptive) = &self.adaptive else {
+            return Permit { host: None };
+        };
+        let limit = {
+            let mut adaptive = adaptive.lock().expect("lock should not be poisoned");
+            Arc::clone(adaptive.entry(host).or_insert_with(Default::default))
+        };
+        let permit = AdaptiveLimit::acquire(limit).await;
+        let host = Some(HostPermit::Adaptive(permit));
+        Permit { host }
+    }
+
+    /// The current concurrency of each host whose limit is tuned adaptively
+    pub fn adaptive_limits(&self) -> BTreeMap<String, usize> {
+        let Some(adaptive) = &self.adaptive else {
+            return BTreeMap::new();
+        };
+        let adaptive = adaptive.lock().expect("lock should not be poisoned");
+        adaptive
+            .iter()
+            .map(|(host, limit)| (host.clone(), limit.current()))
+            .collect()
+    }
+}
+
+impl Permit {
+    /// Records the result of the download this permit was held for, and how long it took, to tune
+    /// the host's limit if it is adaptive.
+    pub fn record<T>(&self, result: &Result<T, DownloadError>, latency: Duration) {
+        let Some(HostPermit::Adaptive(permit)) = &self.host else {
+            return;
+        };
+        match result {
+            Ok(_) => permit.limit.succeeded(latency),
+            Err(error) if error.is_overload() => permit.limit.overloaded(),
+            // other errors say nothing about the host's load
+            Err(_) => {}
+        }
+    }
+}
+
+impl Default for AdaptiveLimit {
+    fn default() -> Self {
+        let state = AdaptiveState {
+            limit: INITIAL_ADAPTIVE_LIMIT,
+            active: 0,
+            min_latency: None,
+        };
+        Self {
+            state: Mutex::new(state),
+            notify: Notify::new(),
+        }
+    }
+}
+
+impl AdaptiveLimit {
+    fn lock(&self) -> std::sync::MutexGuard<'_, AdaptiveState> {
+        self.state.lock().expect("lock should not be poisoned")
+    }
+
+    /// Waits until fewer downloads than the current limit are active.
+    async fn acquire(limit: Arc<Self>) -> AdaptivePermit {
+        loop {
+            // created before checking, so that a release in between is not missed
+            let notified = limit.notify.notified();
+            {
+                let mut state = limit.lock();
+                if (state.active as f64) < state.limit.floor() {
+                    state.active += 1;
+                    break;
+                }
+            }
+            notified.await;
+        }
+        AdaptivePermit { limit }
+    }
+
+    /// The current limit
+    fn current(&self) -> usize {
+        self.lock().limit.floor() as usize
+    }
+
+    /// Increases the limit after a successful download, unless it was much slower than earlier
+    /// ones, which indicates that the host is getting busy.
+    fn succeeded(&self, latency: Duration) {
+        let mut state = self.lock();
+        let min_latency = state.min_latency.map_or(latency, |min| min.min(latency));
+        state.min_latency = Some(min_latency);
+        if latency <= min_latency.saturating_mul(LATENCY_TOLERANCE) {
+            state.limit = (state.limit + 1.0 / state.limit).min(MAX_ADAPTIVE_LIMIT);
+        }
+        drop(state);
+        self.notify.notify_waiters();
+    }
+
+    /// Halves the limit after the host responded in a way indicating it is overloaded.
+    fn overloaded(&self) {
+        let mut state = self.lock();
+        state.limit = (state.limit / 2.0).max(1.0);
+    }
+}
+
+impl Drop for AdaptivePermit {
+    fn drop(&mut self) {
+        self.limit.lock().active -= 1;
+        self.limit.notify.notify_waiters();
+    }
+}
+
+impl DownloadError {
+    /// Checks whether the error indicates that the server is overloaded: it responded with
+    /// `429 Too Many Requests` or a server error, or didn't respond in time.
+    pub fn is_overload(&self) -> bool {
+        let error = match self {
+            Self::Network(error) => error,
+            #[cfg(feature = "http-cache")]
+            Self::Middleware(reqwest_middleware::Error::Reqwest(error)) => error,
+            _ => return false,
+        };
+        let status = error.status();
+        error.is_timeout()
+            || status == Some(StatusCode::TOO_MANY_REQUESTS)
+            || status.is_some_and(|status| status.is_server_error())
+    }
+}
+
+async fn acquire(semaphore: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+    Arc::clone(semaphore)
+        .acquire_owned()
+        .await
+        .expect("download semaphores are never closed")
+}
@@ -1,8 +1,11 @@
 use std::fmt;
 use std::io;
+use std::path::PathBuf;
 
+use reqwest::StatusCode;
 use thiserror::Error;
 use tokio::task::JoinError;
+use url::Url;
 
 use crate::query;
 
@@ -12,9 +15,6 @@ pub enum QueryConfigError {
     /// An option without a default value was not given
     #[error("invalid web-resource query configuration")]
     Builder(#[from] query::QueryBuilderError),
-    /// The `--one` option was given, but is not supported
-    #[error("web-resource does not support --one")]
-    One,
 }
 
 /// A problem with the preprocessor's configuration
@@ -26,6 +26,29 @@ pub enum ManifestError {
     /// An error in the configuration of the job's query
     #[error(transparent)]
     Query(#[from] QueryConfigError),
+    /// The configured HTTP method is not valid
+    #[error("invalid HTTP method `{0}`")]
+    Method(String),
+    /// The configured authorization header is not a valid header name
+    #[error("invalid HTTP header `{0}`")]
+    Header(String),
+    /// A host whose resolution is overridden is not a valid domain name
+    #[error("invalid host `{0}` in `resolve`")]
+    Resolve(String),
+    /// The configured base URL is not an absolute HTTP(S) URL
+    #[error("invalid base URL `{0}`")]
+    BaseUrl(String),
+    /// A named resource references a named resource that is not configured
+    #[error("the resource `{name}` references the unknown resource `{reference}`")]
+    UnknownResource {
+        /// The referencing named resource
+        name: String,
+        /// The unknown reference
+        reference: String,
+    },
+    /// Named resources reference each other in a cycle
+    #[error("the references between resources form a cycle: {0}")]
+    ResourceCycle(String),
 }
 
 /// A problem with using the index of downloaded resources
@@ -48,28 +71,377 @@ pub enum IndexError {
 /// An error doring downloading a resource from the web
 #[derive(Error, Debug)]
 pub enum DownloadError {
+    /// The resource's file extension is not among the allowed extensions
+    #[error("the file extension of {} is not allowed", .0.display())]
+    Extension(PathBuf),
     /// A network error during the download
     #[error("network I/O error during download")]
     Network(#[from] reqwest::Error),
+    /// The resource's URL is not valid
+    #[error("invalid URL")]
+    Url(#[from] url::ParseError),
+    /// The server redirected too many times
+    #[error(transparent)]
+    Redirect(#[from] RedirectError),
+    /// A network or cache error during a download through the HTTP cache
+    #[cfg(feature = "http-cache")]
+    #[error("network I/O error during download")]
+    Middleware(#[from] reqwest_middleware::Error),
     /// An error accessing the local file for the resource
     #[error("file I/O error during download")]
     File(#[from] io::Error),
     /// An error while waiting for the download to finish
     #[error("waiting for a download task failed")]
     Join(#[from] JoinError),
+    /// An error rendering the request body
+    #[error("the request body could not be rendered")]
+    Body(#[from] handlebars::RenderError),
+    /// An error updating the work queue after the download
+    #[error("the work queue could not be updated")]
+    Queue(#[from] IndexError),
+    /// The downloaded file is smaller than the minimum size
+    #[error("the downloaded file has {size} bytes, less than the minimum of {min_size} bytes")]
+    TooSmall {
+        /// The size of the downloaded file
+        size: u64,
+        /// The configured minimum size
+        min_size: u64,
+    },
+    /// The download exceeded the maximum size
+    #[error("the download exceeded the maximum size of {0} bytes")]
+    TooLarge(u64),
+    /// The downloaded file is not a font
+    #[error("{} is not a font file", .0.display())]
+    Font(PathBuf),
+    /// The disk was full while writing the given file
+    #[error("disk full while writing {}", .0.display())]
+    DiskFull(PathBuf),
+    /// The download was not started because an earlier download filled up the disk or exceeded
+    /// the `--max-total-bytes` limit
+    #[error(
+        "the download was cancelled because the disk is full or the download limit was exceeded"
+    )]
+    Cancelled,
+    /// The download was cancelled because another download of the job failed and `fail_fast` is
+    /// enabled
+    #[error("the download was cancelled because another download failed")]
+    Aborted,
+    /// The resource would be downloaded over plain HTTP, but HTTPS is required
+    #[error("{0} is not downloaded over HTTPS")]
+    Insecure(String),
+    /// The limit on the total number of downloaded bytes was exceeded
+    #[error("the download limit of {limit} bytes was exceeded ({downloaded} bytes downloaded)")]
+    TotalLimit {
+        /// The configured limit
+        limit: u64,
+        /// The number of bytes downloaded when the limit was exceeded
+        downloaded: u64,
+    },
+}
+
+impl DownloadError {
+    /// A short identifier of the kind of error, e.g. for machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Extension(_) => "extension",
+            Self::Network(_) => "network",
+            Self::Url(_) => "url",
+            Self::Redirect(_) => "redirect",
+            #[cfg(feature = "http-cache")]
+            Self::Middleware(_) => "network",
+            Self::File(_) => "file",
+            Self::Join(_) => "join",
+            Self::Body(_) => "request-body",
+            Self::Queue(_) => "queue",
+            Self::TooSmall { .. } | Self::TooLarge(_) => "size",
+            Self::Font(_) => "font",
+            Self::DiskFull(_) => "disk-full",
+            Self::Cancelled | Self::Aborted => "cancelled",
+            Self::TotalLimit { .. } => "total-limit",
+            Self::Insecure(_) => "insecure",
+        }
+    }
+}
+
+/// The redirect limit was hit while downloading a resource
+#[derive(Error, Debug, Clone)]
+pub struct RedirectError {
+    chain: Vec<Url>,
+}
+
+impl RedirectError {
+    /// Creates a new error
+    pub fn new(chain: Vec<Url>) -> Self {
+        Self { chain }
+    }
+
+    /// The visited URLs, starting with the requested one
+    pub fn chain(&self) -> &[Url] {
+        &self.chain
+    }
+
+    /// Checks whether a URL was visited more than once, i.e. the redirects form a loop
+    pub fn is_loop(&self) -> bool {
+        self.chain
+            .iter()
+            .enumerate()
+            .any(|(i, url)| self.chain[..i].contains(url))
+    }
+}
+
+impl fmt::Display for RedirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_loop() {
+            write!(f, "redirect loop: ")?;
+        } else {
+            write!(f, "too many redirects: ")?;
+        }
+        for (i, url) in self.chain.iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{url}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A failed download of a resource
+#[derive(Error, Debug)]
+pub struct ResourceError {
+    /// The path of the resource, if known
+    pub path: Option<PathBuf>,
+    /// The reason the download failed
+    pub error: DownloadError,
+}
+
+impl ResourceError {
+    /// Creates a new error
+    pub fn new(path: PathBuf, error: DownloadError) -> Self {
+        let path = Some(path);
+        Self { path, error }
+    }
+}
+
+impl From<JoinError> for ResourceError {
+    fn from(error: JoinError) -> Self {
+        let error = error.into();
+        Self { path: None, error }
+    }
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path.display(), self.error),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+/// An error while fetching a listing of resources
+#[derive(Error, Debug)]
+pub enum ListingError {
+    /// A network error while fetching the listing
+    #[error("network I/O error while fetching listing")]
+    Network(#[from] reqwest::Error),
+    /// The listing is not valid JSON
+    #[error("listing is not valid JSON")]
+    Json(#[from] serde_json::Error),
+    /// The listing's URL or one of the listed URLs is invalid
+    #[error("invalid URL in listing")]
+    Url(#[from] url::ParseError),
+    /// The pointer to the listed resources does not point to an array
+    #[error("listing does not contain an array at `{0}`")]
+    Pointer(String),
+    /// A listed resource is missing a required field
+    #[error("listed resource does not have a `{0}` string field")]
+    Field(String),
+    /// The link to the next page is not a string
+    #[error("listing does not contain a string or null at `{0}`")]
+    NextPointer(String),
+    /// The listing has more pages than allowed
+    #[error("listing has more than {0} pages")]
+    MaxPages(usize),
+}
+
+/// An error while requesting a named resource
+#[derive(Error, Debug)]
+pub enum NamedResourceError {
+    /// The URL, body or a header of the request could not be rendered
+    #[error("the request for the resource `{name}` could not be rendered")]
+    Render {
+        /// The named resource
+        name: String,
+        /// The rendering error
+        #[source]
+        source: handlebars::RenderError,
+    },
+    /// The request failed or the server responded with an error status
+    #[error("the request for the resource `{name}` failed")]
+    Request {
+        /// The named resource
+        name: String,
+        /// The network or status error
+        #[source]
+        source: reqwest::Error,
+    },
+}
+
+/// A host failed TLS verification
+#[derive(Error, Debug)]
+pub enum TlsError {
+    /// The TLS handshake, or the request following it, failed
+    #[error("{host}: TLS handshake failed: {reason}")]
+    Handshake {
+        /// The verified host
+        host: String,
+        /// The reason for the failure, including its causes
+        reason: String,
+    },
+    /// The host's certificate is missing, malformed or expired
+    #[error("{host}: invalid certificate: {reason}")]
+    Certificate {
+        /// The verified host
+        host: String,
+        /// The reason why the certificate is invalid
+        reason: String,
+    },
+}
+
+/// One or more hosts failed TLS verification
+#[derive(Error, Debug)]
+pub struct MultipleTlsError {
+    errors: Vec<TlsError>,
+}
+
+impl MultipleTlsError {
+    /// Creates a new error
+    pub fn new(errors: Vec<TlsError>) -> Self {
+        Self { errors }
+    }
+}
+
+impl fmt::Display for MultipleTlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at least one host failed TLS verification:")?;
+        for error in &self.errors {
+            writeln!(f)?;
+            write!(f, "  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A resource's URL is not reachable, as found by `--dry-run=check`
+#[derive(Error, Debug)]
+pub enum LinkError {
+    /// The server responded with an error status
+    #[error("{url}: {status}")]
+    Status {
+        /// The checked URL
+        url: String,
+        /// The response's status
+        status: StatusCode,
+    },
+    /// The request failed, e.g. because the host could not be reached
+    #[error("{url}: {reason}")]
+    Request {
+        /// The checked URL
+        url: String,
+        /// The reason for the failure, including its causes
+        reason: String,
+    },
+}
+
+/// One or more resources' URLs are not reachable
+#[derive(Error, Debug)]
+pub struct MultipleLinkError {
+    errors: Vec<LinkError>,
+}
+
+impl MultipleLinkError {
+    /// Creates a new error
+    pub fn new(errors: Vec<LinkError>) -> Self {
+        Self { errors }
+    }
+}
+
+impl fmt::Display for MultipleLinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at least one link is broken:")?;
+        for error in &self.errors {
+            writeln!(f)?;
+            write!(f, "  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A resource's file is not present, as found by `--check-complete`
+#[derive(Error, Debug)]
+pub enum MissingError {
+    /// The file does not exist
+    #[error("{} is missing", .0.display())]
+    Missing(PathBuf),
+    /// The file exists, but was downloaded from a different URL according to the index
+    #[error("{} was not downloaded from {url}", .path.display())]
+    Outdated {
+        /// The resource's path
+        path: PathBuf,
+        /// The resource's current URL
+        url: String,
+    },
+    /// The file can't be checked, e.g. because it is outside the project root
+    #[error("{}: {reason}", .path.display())]
+    Path {
+        /// The resource's path
+        path: PathBuf,
+        /// The reason the path can't be checked
+        reason: String,
+    },
+}
+
+/// One or more resources' files are not present
+#[derive(Error, Debug)]
+pub struct MultipleMissingError {
+    errors: Vec<MissingError>,
+}
+
+impl MultipleMissingError {
+    /// Creates a new error
+    pub fn new(errors: Vec<MissingError>) -> Self {
+        Self { errors }
+    }
+}
+
+impl fmt::Display for MultipleMissingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at least one resource is not present:")?;
+        for error in &self.errors {
+            writeln!(f)?;
+            write!(f, "  {error}")?;
+        }
+        Ok(())
+    }
 }
 
 /// One or more preprocessors were not configured correctly
 #[derive(Error, Debug)]
 pub struct MultipleDownloadError {
-    errors: Vec<DownloadError>,
+    errors: Vec<ResourceError>,
 }
 
 impl MultipleDownloadError {
     /// Creates a new error
-    pub fn new(errors: Vec<DownloadError>) -> Self {
+    pub fn new(errors: Vec<ResourceError>) -> Self {
         Self { errors }
     }
+
+    /// The individual failed downloads
+    pub fn errors(&self) -> &[ResourceError] {
+        &self.errors
+    }
 }
 
 impl fmt::Display for MultipleDownloadError {
@@ -92,9 +464,76 @@ pub enum ExecutionError {
     /// An error while executing the job's query
     #[error(transparent)]
     Query(#[from] query::Error),
+    /// An error while fetching a listing of resources
+    #[error(transparent)]
+    Listing(#[from] ListingError),
+    /// An error while requesting a named resource
+    #[error(transparent)]
+    NamedResource(#[from] NamedResourceError),
     /// An error doring downloading a resource from the web
     #[error(transparent)]
     Download(#[from] MultipleDownloadError),
+    /// An error verifying the TLS certificates of the resources' hosts
+    #[error(transparent)]
+    Tls(#[from] MultipleTlsError),
+    /// Broken links found by `--dry-run=check`
+    #[error(transparent)]
+    Links(#[from] MultipleLinkError),
+    /// Missing files found by `--check-complete`
+    #[error(transparent)]
+    Missing(#[from] MultipleMissingError),
+    /// Multiple query results have the same key, and `on_duplicate` is `error`
+    #[error("multiple query results have the {key} {value}")]
+    Duplicate {
+        /// The field identifying duplicates
+        key: String,
+        /// The duplicated value, as JSON
+        value: String,
+    },
+    /// The query result did not stabilize within the maximum number of iterations
+    #[error("the query result did not stabilize within {0} iterations")]
+    Iterate(usize),
+    /// The disk was full while downloading, and remaining downloads were cancelled
+    #[error("disk full while writing {}", .0.display())]
+    DiskFull(PathBuf),
+    /// An error deleting a file that is no longer needed
+    #[error("a file that is no longer needed could not be deleted")]
+    Evict(#[source] io::Error),
+    /// The `--max-total-bytes` limit was exceeded, and remaining downloads were cancelled
+    #[error("the download limit of {limit} bytes was exceeded ({downloaded} bytes downloaded)")]
+    TotalLimit {
+        /// The configured limit
+        limit: u64,
+        /// The number of bytes downloaded during the run
+        downloaded: u64,
+    },
+    /// The directory of the HTTP cache could not be determined
+    #[cfg(feature = "http-cache")]
+    #[error("the HTTP cache directory could not be determined")]
+    HttpCache(#[source] io::Error),
+}
+
+impl ExecutionError {
+    /// A short identifier of the kind of error, e.g. for machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Index(_) => "index",
+            Self::Query(error) => error.kind(),
+            Self::Listing(_) => "listing",
+            Self::NamedResource(_) => "named-resource",
+            Self::Download(_) => "download",
+            Self::Tls(_) => "tls",
+            Self::Links(_) => "link",
+            Self::Missing(_) => "missing",
+            Self::Duplicate { .. } => "duplicate",
+            Self::Iterate(_) => "iterate",
+            Self::DiskFull(_) => "disk-full",
+            Self::Evict(_) => "evict",
+            Self::TotalLimit { .. } => "total-limit",
+            #[cfg(feature = "http-cache")]
+            Self::HttpCache(_) => "http-cache",
+        }
+    }
 }
 
 /// A result with a config error in it
@@ -1,38 +1,111 @@
 use std::sync::Arc;
 
+use reqwest::header::HeaderName;
+use reqwest::Method;
+use url::{Host, Url};
+
 use crate::manifest;
 use crate::preprocessor::{BoxedPreprocessor, PreprocessorDefinition};
 use crate::query::Query;
 
-use super::{Manifest, ManifestError, ManifestResult, QueryConfigError, WebResource};
+use super::{
+    named, request, Manifest, ManifestError, ManifestResult, QueryConfigError, WebResource,
+};
 
 /// The `web-resource` preprocessor factory
 #[derive(Debug, Clone, Copy)]
 pub struct WebResourceFactory;
 
 impl WebResourceFactory {
-    fn parse_config(config: toml::Table) -> ManifestResult<Manifest> {
-        let config = config.try_into()?;
+    pub(super) fn parse_config(config: toml::Table) -> ManifestResult<Manifest> {
+        let mut config: Manifest = config.try_into()?;
+        let method = &config.request.method;
+        if Method::from_bytes(method.as_bytes()).is_err() {
+            return Err(ManifestError::Method(method.clone()));
+        }
+        for auth in config.auth.values() {
+            if HeaderName::from_bytes(auth.header.as_bytes()).is_err() {
+                return Err(ManifestError::Header(auth.header.clone()));
+            }
+        }
+        for resource in config.resources.values() {
+            let method = &resource.request.method;
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                return Err(ManifestError::Method(method.clone()));
+            }
+            for header in resource.headers.keys() {
+                if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                    return Err(ManifestError::Header(header.clone()));
+                }
+            }
+        }
+        named::order(&config.resources)?;
+        for host in config.resolve.keys() {
+            if !matches!(Host::parse(host), Ok(Host::Domain(_))) {
+                return Err(ManifestError::Resolve(host.clone()));
+            }
+        }
+        if let Some(base_url) = config.base_url.take() {
+            let rendered = request::render_env(&base_url)
+                .map_err(|_| ManifestError::BaseUrl(base_url.clone()))?;
+            match Url::parse(&rendered) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                    config.base_url = Some(rendered);
+                }
+                _ => return Err(ManifestError::BaseUrl(base_url)),
+            }
+        }
         Ok(config)
     }
 
-    fn build_query(config: manifest::Query) -> ManifestResult<Query> {
+    /// Gives the web-resource jobs that enable the index with `index = true` distinct index
+    /// paths. If only one job does, it uses the default `web-resource-index.toml`; if several do,
+    /// each uses `web-resource-index.<job>.toml` instead, where characters of the job name that
+    /// are not alphanumeric, `-` or `_` are replaced by `-`.
+    pub fn derive_index_paths(jobs: &mut [manifest::Job]) {
+        let is_default = |job: &manifest::Job| {
+            job.kind == Self::NAME && job.manifest.get("index") == Some(&toml::Value::Boolean(true))
+        };
+        if jobs.iter().filter(|job| is_default(job)).count() < 2 {
+            return;
+        }
+        for job in jobs.iter_mut() {
+            if !is_default(job) {
+                continue;
+            }
+            let name: String = job
+                .name
+                .chars()
+                .map(|c| match c {
+                    c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+                    _ => '-',
+                })
+                .collect();
+            let index = format!("web-resource-index.{name}.toml");
+            job.manifest
+                .insert("index".to_string(), toml::Value::String(index));
+        }
+    }
+
+    pub(super) fn build_query(
+        name: &str,
+        config: manifest::Query,
+        default_selector: &str,
+    ) -> ManifestResult<Query> {
         let config = Query::builder()
+            .job(name)
             .default_field(Some("value".to_string()))
-            .default_one(false)
-            .default_selector("<web-resource>".to_string())
+            .fixed_one(false)
+            .default_selector(default_selector.to_string())
             .build(config)
             .map_err(QueryConfigError::Builder)?;
-        if config.one {
-            return Err(QueryConfigError::One.into());
-        }
-
         Ok(config)
     }
 }
 
 impl PreprocessorDefinition for WebResourceFactory {
     const NAME: &'static str = "web-resource";
+    const DESCRIPTION: &'static str = "Downloads the resources referenced by the document";
 
     type Error = ManifestError;
 
@@ -44,7 +117,7 @@ impl PreprocessorDefinition for WebResourceFactory {
         let config = Self::parse_config(config)?;
         // index begins as None and is asynchronously populated later
         let index = None;
-        let query = Self::build_query(query)?;
+        let query = Self::build_query(&name, query, "<web-resource>")?;
         let instance = WebResource::new(name, config, index, query);
         Ok(Box::new(Arc::new(instance)))
     }
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+use crate::manifest;
+use crate::preprocessor::{BoxedPreprocessor, PreprocessorDefinition};
+
+use super::{DownloadError, ManifestError, ManifestResult, WebResource, WebResourceFactory};
+
+/// The file extensions of font formats supported by Typst
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+/// The magic numbers at the start of TrueType, OpenType and font collection files
+const FONT_MAGIC: [&[u8; 4]; 4] = [b"\x00\x01\x00\x00", b"OTTO", b"true", b"ttcf"];
+
+/// Configuration specific to the `fonts` preprocessor, in addition to the `web-resource`
+/// configuration
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FontsManifest {
+    /// The directory fonts are downloaded to, relative to the project root. The paths of the
+    /// queried fonts are relative to this directory. Defaults to "fonts".
+    #[serde(default = "default_directory")]
+    pub directory: PathBuf,
+    /// Whether downloaded files are checked to be fonts. Defaults to true.
+    #[serde(default = "default_verify")]
+    pub verify: bool,
+}
+
+fn default_directory() -> PathBuf {
+    "fonts".into()
+}
+
+fn default_verify() -> bool {
+    true
+}
+
+impl FontsManifest {
+    /// Moves the given resources into the font directory.
+    pub fn relocate<T>(&self, resources: BTreeMap<PathBuf, T>) -> BTreeMap<PathBuf, T> {
+        resources
+            .into_iter()
+            .map(|(path, value)| (self.directory.join(path), value))
+            .collect()
+    }
+
+    /// Checks that the downloaded file is a font, if verification is enabled. A file that is not
+    /// a font is removed.
+    pub async fn verify(&self, path: &Path) -> Result<(), DownloadError> {
+        if !self.verify {
+            return Ok(());
+        }
+
+        let mut magic = [0; 4];
+        let mut file = fs::File::open(path).await?;
+        let is_font = match file.read_exact(&mut magic).await {
+            Ok(_) => FONT_MAGIC.contains(&&magic),
+            Err(_) => false,
+        };
+        drop(file);
+        if !is_font {
+            let _ = fs::remove_file(path).await;
+            return Err(DownloadError::Font(path.to_path_buf()));
+        }
+        Ok(())
+    }
+}
+
+/// The `fonts` preprocessor factory. Font jobs are web-resource jobs with defaults suitable for
+/// fonts: resources are queried from `<font>` metadata and downloaded into a font directory, only
+/// font files are allowed, and an index is kept so that fonts no longer used are evicted.
+#[derive(Debug, Clone, Copy)]
+pub struct FontsFactory;
+
+impl PreprocessorDefinition for FontsFactory {
+    const NAME: &'static str = "fonts";
+    const DESCRIPTION: &'static str =
+        "Downloads the fonts used by the document into a font directory";
+
+    type Error = ManifestError;
+
+    fn configure_impl(
+        name: String,
+        mut config: toml::Table,
+        query: manifest::Query,
+    ) -> ManifestResult<BoxedPreprocessor> {
+        let fonts: FontsManifest = config.clone().try_into()?;
+        let mut defaults = |key: &str, value: toml::Value| {
+            config.entry(key).or_insert(value);
+        };
+        defaults("index", "font-index.toml".into());
+        defaults("evict", true.into());
+        defaults(
+            "allowed_extensions",
+            FONT_EXTENSIONS.map(toml::Value::from).to_vec().into(),
+        );
+        let config = WebResourceFactory::parse_config(config)?;
+        // index begins as None and is asynchronously populated later
+        let index = None;
+        let query = WebResourceFactory::build_query(&name, query, "<font>")?;
+        let mut instance = WebResource::new(name, config, index, query);
+        instance.fonts = Some(fonts);
+        Ok(Box::new(Arc::new(instance)))
+    }
+}
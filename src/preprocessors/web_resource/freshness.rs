@@ -0,0 +1,128 @@
+use std::io;
+use std::path::Path;
+
+use reqwest::header::{HeaderMap, ETAG, LAST_MODIFIED};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// How to decide whether an existing file needs to be downloaded again
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FreshnessMode {
+    /// Existing files are always downloaded again
+    Always,
+    /// Existing files are never downloaded again (unless their URL changed or their cache lifetime
+    /// expired)
+    #[default]
+    Never,
+    /// Existing files are downloaded again if they were modified locally, or if the resource
+    /// changed on the server according to a `HEAD` request. This requires the index.
+    Smart,
+}
+
+/// The freshness of an existing file, as determined in [FreshnessMode::Smart]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// The file is up-to-date
+    Fresh,
+    /// The file's content doesn't match the content that was downloaded
+    Modified,
+    /// The resource has changed on the server
+    Changed,
+}
+
+/// Validators that identify a version of a resource on the server
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    /// The `ETag` header, including the `W/` prefix if it is weak
+    pub etag: Option<String>,
+    /// The `Last-Modified` header
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Extracts the validators from a response's headers.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+}
+
+/// Decides whether an existing file is fresh. The file is modified if its hash doesn't match the
+/// one recorded when downloading it (or no hash was recorded). Otherwise, the resource has changed
+/// if any validator known both from the download and from the server differs; `ETag`s are compared
+/// with [etag_matches]. If no validator can be compared, the resource is considered changed only
+/// if the server provides validators, so that they are recorded for future checks.
+pub fn check(
+    recorded_hash: Option<&str>,
+    local_hash: &str,
+    recorded: &Validators,
+    remote: &Validators,
+) -> Freshness {
+    if recorded_hash != Some(local_hash) {
+        return Freshness::Modified;
+    }
+
+    let etag = recorded
+        .etag
+        .as_deref()
+        .zip(remote.etag.as_deref())
+        .map(|(recorded, remote)| etag_matches(recorded, remote));
+    let last_modified = recorded
+        .last_modified
+        .as_deref()
+        .zip(remote.last_modified.as_deref())
+        .map(|(recorded, remote)| recorded == remote);
+    if etag == Some(false) || last_modified == Some(false) {
+        return Freshness::Changed;
+    }
+    let compared = etag.is_some() || last_modified.is_some();
+
+    let remote_has_validators = remote.etag.is_some() || remote.last_modified.is_some();
+    if !compared && remote_has_validators {
+        Freshness::Changed
+    } else {
+        Freshness::Fresh
+    }
+}
+
+/// Computes the SHA-256 hash of a file in hexadecimal, reading it in chunks so that large files
+/// don't need to be held in memory.
+pub async fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buffer).await?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buffer[..len]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compares two `ETag`s using the weak comparison of RFC 9110: they match if their opaque tags are
+/// equal, regardless of whether either is weak (`W/"..."`). This is correct for deciding whether a
+/// resource has changed, and necessary because servers may weaken an `ETag` e.g. when compressing
+/// the response, so that `HEAD` and `GET` responses disagree about it. Resuming a partial download
+/// with a `Range` request would instead need the strong comparison, where weak `ETag`s never
+/// match.
+pub fn etag_matches(a: &str, b: &str) -> bool {
+    fn opaque_tag(etag: &str) -> &str {
+        let etag = etag.trim();
+        etag.strip_prefix("W/").unwrap_or(etag)
+    }
+
+    opaque_tag(a) == opaque_tag(b)
+}
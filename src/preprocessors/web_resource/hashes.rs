@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// A hash algorithm whose hashes of downloaded files can be recorded with `hashes`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashAlgorithm {
+    /// SHA-256
+    Sha256,
+    /// SHA-384
+    Sha384,
+    /// SHA-512
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// The algorithm's name, as used in the index and in Subresource Integrity strings
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+/// Computes the hashes of a file with several algorithms in one pass over its content
+#[derive(Debug, Clone)]
+pub struct Hashers {
+    sha256: Option<Sha256>,
+    sha384: Option<Sha384>,
+    sha512: Option<Sha512>,
+}
+
+/// The hashes of a file by algorithm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hashes(BTreeMap<HashAlgorithm, Vec<u8>>);
+
+impl Hashers {
+    /// Creates hashers for the given algorithms.
+    pub fn new(algorithms: &[HashAlgorithm]) -> Self {
+        let enabled = |algorithm| algorithms.contains(&algorithm);
+        Self {
+            sha256: enabled(HashAlgorithm::Sha256).then(Sha256::new),
+            sha384: enabled(HashAlgorithm::Sha384).then(Sha384::new),
+            sha512: enabled(HashAlgorithm::Sha512).then(Sha512::new),
+        }
+    }
+
+    /// Adds the next chunk of the file's content.
+    pub fn update(&mut self, chunk: &[u8]) {
+        if let Some(hasher) = &mut self.sha256 {
+            hasher.update(chunk);
+        }
+        if let Some(hasher) = &mut self.sha384 {
+            hasher.update(chunk);
+        }
+        if let Some(hasher) = &mut self.sha512 {
+            hasher.update(chunk);
+        }
+    }
+
+    /// Returns the hashes of the whole content.
+    pub fn finalize(self) -> Hashes {
+        let mut hashes = BTreeMap::new();
+        if let Some(hasher) = self.sha256 {
+            hashes.insert(HashAlgorithm::Sha256, hasher.finalize().to_vec());
+        }
+        if let Some(hasher) = self.sha384 {
+            hashes.insert(HashAlgorithm::Sha384, hasher.finalize().to_vec());
+        }
+        if let Some(hasher) = self.sha512 {
+            hashes.insert(HashAlgorithm::Sha512, hasher.finalize().to_vec());
+        }
+        Hashes(hashes)
+    }
+}
+
+impl Hashes {
+    /// Keeps only the hashes of the given algorithms.
+    pub fn retain(&mut self, algorithms: &[HashAlgorithm]) {
+        self.0.retain(|algorithm, _| algorithms.contains(algorithm));
+    }
+
+    /// The hash of the given algorithm in hexadecimal, if it was computed
+    pub fn hex(&self, algorithm: HashAlgorithm) -> Option<String> {
+        self.0.get(&algorithm).map(|hash| hex(hash))
+    }
+
+    /// The hashes in hexadecimal, by the names of their algorithms
+    pub fn to_hex(&self) -> BTreeMap<String, String> {
+        self.0
+            .iter()
+            .map(|(algorithm, hash)| (algorithm.name().to_string(), hex(hash)))
+            .collect()
+    }
+
+    /// The Subresource Integrity string containing all hashes, e.g. `sha384-...`
+    pub fn integrity(&self) -> String {
+        self.0
+            .iter()
+            .map(|(algorithm, hash)| {
+                let hash = BASE64_STANDARD.encode(hash);
+                format!("{}-{hash}", algorithm.name())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Formats a hash in hexadecimal.
+fn hex(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
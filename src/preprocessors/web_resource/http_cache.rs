@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+
+use super::HttpCacheMode;
+
+/// Creates a client that sends requests through the given client, and stores responses in the
+/// HTTP cache in the given directory.
+pub fn client(
+    mode: HttpCacheMode,
+    directory: PathBuf,
+    inner: reqwest::Client,
+) -> ClientWithMiddleware {
+    let mode = match mode {
+        HttpCacheMode::Default => CacheMode::Default,
+        HttpCacheMode::NoStore => CacheMode::NoStore,
+        HttpCacheMode::ForceCache => CacheMode::ForceCache,
+    };
+    let cache = HttpCache {
+        mode,
+        manager: CACacheManager { path: directory },
+        options: HttpCacheOptions::default(),
+    };
+    ClientBuilder::new(inner).with(Cache(cache)).build()
+}
@@ -1,20 +1,24 @@
 use std::borrow::Borrow;
 use std::collections::BTreeMap;
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
 
-use super::IndexError;
+use crate::utils::{self, Restored};
+
+use super::{IndexError, Validators};
 
 /// Represents an index of resources.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Index {
     #[serde(skip)]
     location: PathBuf,
+    /// Whether the index was changed since it was read or last written
+    #[serde(skip)]
+    changed: bool,
     /// a file format version number. Should be 1.
     pub version: usize,
     /// The entries in the index.
@@ -25,7 +29,7 @@ pub struct Index {
         deserialize_with = "deserialize_entries",
         skip_serializing_if = "BTreeMap::is_empty"
     )]
-    pub entries: BTreeMap<PathBuf, Resource>,
+    pub entries: BTreeMap<PathBuf, Entry>,
 }
 
 /// A resource that should be downloaded
@@ -37,35 +41,150 @@ pub struct Resource {
     pub url: String,
 }
 
+/// An entry in the index, recording a resource that was downloaded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The path the resource was downloaded to.
+    pub path: PathBuf,
+    /// The URL the resource was downloaded from.
+    pub url: String,
+    /// The time (in seconds since the Unix epoch) after which the resource should be downloaded
+    /// again, as indicated by the `Cache-Control` header of the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    /// Identifies the request beyond its URL, if it is not a plain `GET` request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<String>,
+    /// The cache entry the resource's path links to, if files are linked instead of copied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<PathBuf>,
+    /// The sidecar file the response headers were written to, if `save_headers` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<PathBuf>,
+    /// The SHA-256 hash of the downloaded file, for detecting local modifications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The hashes of the downloaded file configured with `hashes`, in hexadecimal, by algorithm.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hashes: BTreeMap<String, String>,
+    /// The Subresource Integrity string of the downloaded file, if `integrity` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    /// The `ETag` header of the response, for detecting changes on the server. It is stored as
+    /// received, including the `W/` prefix of weak `ETag`s.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response, for detecting changes on the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl Entry {
+    /// Creates an index entry for a downloaded resource.
+    pub fn new(resource: Resource) -> Self {
+        let Resource { path, url } = resource;
+        Self {
+            path,
+            url,
+            request: None,
+            expires: None,
+            link: None,
+            headers: None,
+            hash: None,
+            hashes: BTreeMap::new(),
+            integrity: None,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// The validators recorded for the downloaded resource.
+    pub fn validators(&self) -> Validators {
+        Validators {
+            etag: self.etag.clone(),
+            last_modified: self.last_modified.clone(),
+        }
+    }
+}
+
 impl Index {
     pub fn new(location: PathBuf) -> Self {
         Self {
             location,
+            // the index file doesn't exist yet
+            changed: true,
             version: 1,
             entries: BTreeMap::new(),
         }
     }
 
-    /// Reads an index from a file.
-    pub async fn read(location: PathBuf) -> Result<Self, IndexError> {
-        let index = fs::read_to_string(&location).await?;
-        let mut index: Self = toml::from_str(&index)?;
+    /// The location of the index file.
+    pub fn location(&self) -> &Path {
+        &self.location
+    }
+
+    /// Reads an index from a file. If the index can't be parsed, e.g. because a previous run
+    /// crashed while writing it, the backup from the last successful write is read instead, and
+    /// replaces the index file when the index is written. This is returned alongside the index so
+    /// that it can be reported.
+    pub async fn read(
+        location: PathBuf,
+    ) -> Result<(Self, Option<Restored<IndexError>>), IndexError> {
+        let (mut index, restored) = match Self::read_file(&location).await {
+            Err(error @ IndexError::Parse(_)) => {
+                let backup = utils::backup_path(&location);
+                let Ok(index) = Self::read_file(&backup).await else {
+                    return Err(error);
+                };
+                let restored = Restored {
+                    location: location.clone(),
+                    backup,
+                    error,
+                };
+                (index, Some(restored))
+            }
+            result => (result?, None),
+        };
+        index.location = location;
+        // the restored index needs to replace the corrupt file
+        index.changed = restored.is_some();
+        Ok((index, restored))
+    }
+
+    async fn read_file(location: &Path) -> Result<Self, IndexError> {
+        let index = fs::read_to_string(location).await?;
+        let index: Self = toml::from_str(&index)?;
         if index.version != 1 {
             return Err(IndexError::Version(index.version));
         }
-        index.location = location;
         Ok(index)
     }
 
-    /// Writes the index to a file.
-    pub async fn write(&self) -> Result<(), IndexError> {
-        let mut file = fs::File::create(&self.location).await?;
-        let index = toml::to_string(self)?;
-        file.write_all(index.as_bytes()).await?;
+    /// Writes the index to a file, unless it is unchanged since it was read or last written. The
+    /// index is written to a temporary file first, which then replaces the actual index file so
+    /// that it is never left partially written. Afterwards, a backup of the new index is saved for
+    /// restoring the index if it gets corrupted anyway.
+    pub async fn write(&mut self) -> Result<(), IndexError> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        // the entries are serialized one by one, so that large indices don't need to be turned
+        // into a TOML document as a whole
+        let mut index = toml::to_string(&Header {
+            version: self.version,
+        })?;
+        for entry in self.entries.values() {
+            index.push('\n');
+            index.push_str(&toml::to_string(&Single { resource: [entry] })?);
+        }
+
+        utils::write_with_backup(&self.location, index.as_bytes()).await?;
+        self.changed = false;
         Ok(())
     }
 
-    pub fn get<P>(&self, path: &P) -> Option<&Resource>
+    pub fn get<P>(&self, path: &P) -> Option<&Entry>
     where
         PathBuf: Borrow<P>,
         P: Ord + ?Sized,
@@ -73,20 +192,97 @@ impl Index {
         self.entries.get(path)
     }
 
-    pub fn is_up_to_date<P>(&self, path: &P, url: &str) -> bool
+    /// Checks whether the given resource was downloaded from the given URL, using the given
+    /// request (see [Entry::request]).
+    pub fn is_up_to_date<P>(&self, path: &P, url: &str, request: Option<&str>) -> bool
+    where
+        PathBuf: Borrow<P>,
+        P: Ord + ?Sized,
+    {
+        self.get(path)
+            .is_some_and(|res| res.url == url && res.request.as_deref() == request)
+    }
+
+    /// Checks whether the cache lifetime of the given resource has passed at the time `now`.
+    /// Resources without a recorded expiry never expire.
+    pub fn is_expired<P>(&self, path: &P, now: u64) -> bool
+    where
+        PathBuf: Borrow<P>,
+        P: Ord + ?Sized,
+    {
+        self.get(path)
+            .and_then(|res| res.expires)
+            .is_some_and(|expires| expires <= now)
+    }
+
+    pub fn update(&mut self, entry: Entry) {
+        self.changed = true;
+        self.entries.insert(entry.path.clone(), entry);
+    }
+
+    /// Removes the entry for the given path from the index.
+    pub fn remove<P>(&mut self, path: &P) -> Option<Entry>
     where
         PathBuf: Borrow<P>,
         P: Ord + ?Sized,
     {
-        self.get(path).is_some_and(|res| res.url == url)
+        let entry = self.entries.remove(path);
+        self.changed |= entry.is_some();
+        entry
+    }
+
+    /// Returns the paths of all entries that are not among the given resources, i.e. that are no
+    /// longer needed.
+    pub fn evictable<V>(&self, resources: &BTreeMap<PathBuf, V>) -> Vec<PathBuf> {
+        self.entries
+            .keys()
+            .filter(|path| !resources.contains_key(*path))
+            .cloned()
+            .collect()
     }
 
-    pub fn update(&mut self, resource: Resource) {
-        self.entries.insert(resource.path.clone(), resource);
+    /// Changes the paths of all entries starting with the prefix `from` to start with `to`
+    /// instead. All other data of the renamed entries is kept.
+    pub fn rename_prefix(&mut self, from: &Path, to: &Path) {
+        let renamed: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+
+        for path in renamed {
+            let mut entry = self
+                .entries
+                .remove(&path)
+                .expect("the entry was just found in the index");
+            let suffix = path
+                .strip_prefix(from)
+                .expect("the path should start with the prefix");
+            // joining an empty suffix would add a trailing separator
+            entry.path = if suffix.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(suffix)
+            };
+            self.update(entry);
+        }
     }
 }
 
-fn serialize_entries<S>(map: &BTreeMap<PathBuf, Resource>, serializer: S) -> Result<S::Ok, S::Error>
+/// The part of the index file before its entries
+#[derive(Serialize)]
+struct Header {
+    version: usize,
+}
+
+/// An index file containing a single entry, for serializing the entries one by one
+#[derive(Serialize)]
+struct Single<'a> {
+    resource: [&'a Entry; 1],
+}
+
+fn serialize_entries<S>(map: &BTreeMap<PathBuf, Entry>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
@@ -94,14 +290,14 @@ where
 }
 
 /// Deserializes the `entries` sequence as a map.
-fn deserialize_entries<'de, D>(deserializer: D) -> Result<BTreeMap<PathBuf, Resource>, D::Error>
+fn deserialize_entries<'de, D>(deserializer: D) -> Result<BTreeMap<PathBuf, Entry>, D::Error>
 where
     D: Deserializer<'de>,
 {
     struct EntriesVisitor;
 
     impl<'de> Visitor<'de> for EntriesVisitor {
-        type Value = BTreeMap<PathBuf, Resource>;
+        type Value = BTreeMap<PathBuf, Entry>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
             formatter.write_str("`false` or a string`")
@@ -112,7 +308,7 @@ where
             A: de::SeqAccess<'de>,
         {
             let mut entries = BTreeMap::new();
-            while let Some(elem) = seq.next_element::<Resource>()? {
+            while let Some(elem) = seq.next_element::<Entry>()? {
                 entries.insert(elem.path.to_owned(), elem);
             }
             Ok(entries)
@@ -0,0 +1,61 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+use super::LinkMode;
+
+/// Moves a downloaded file into the content-addressed cache, and replaces it with a link to the
+/// cache entry. If the cache already contains a file with that content, the downloaded file is
+/// discarded. Where the link can't be created, the cache entry is copied instead. Returns the
+/// cache entry.
+pub async fn link_to_cache(
+    mode: LinkMode,
+    cache: &Path,
+    path: &Path,
+    hash: &str,
+) -> io::Result<PathBuf> {
+    // keep the extension so that the file type stays recognizable
+    let mut entry = cache.join(hash);
+    if let Some(extension) = path.extension() {
+        entry.set_extension(extension);
+    }
+
+    fs::create_dir_all(cache).await?;
+    if fs::try_exists(&entry).await? {
+        fs::remove_file(path).await?;
+    } else if fs::rename(path, &entry).await.is_err() {
+        // e.g. the cache is on a different file system
+        fs::copy(path, &entry).await?;
+        fs::remove_file(path).await?;
+    }
+
+    let result = match mode {
+        LinkMode::Symlink => symlink(&entry, path).await,
+        LinkMode::Hardlink => fs::hard_link(&entry, path).await,
+        LinkMode::Copy => Err(io::ErrorKind::Unsupported.into()),
+    };
+    if let Err(error) = result {
+        let path_str = path.to_string_lossy();
+        let entry_str = entry.to_string_lossy();
+        eprintln!("warning: could not link {path_str} to {entry_str}, copying instead: {error}");
+        fs::copy(&entry, path).await?;
+    }
+
+    Ok(entry)
+}
+
+#[cfg(unix)]
+async fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    fs::symlink(original, link).await
+}
+
+#[cfg(windows)]
+async fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    fs::symlink_file(original, link).await
+}
+
+#[cfg(not(any(unix, windows)))]
+async fn symlink(_original: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
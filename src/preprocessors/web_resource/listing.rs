@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde_json::Value;
+use url::Url;
+
+use super::retry::backoff;
+use super::{Listing, ListingError, ListingManifest, Resource};
+
+/// How often fetching a page is retried when the server responds with `429 Too Many Requests`
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+impl Listing {
+    /// Fetches the listing and returns the resources listed in it. The listed URLs are resolved
+    /// relative to the listing's URL, and the listed paths relative to the listing's path. If the
+    /// listing is paginated, all pages are fetched and their resources combined.
+    pub async fn fetch(
+        &self,
+        client: &reqwest::Client,
+        manifest: &ListingManifest,
+    ) -> Result<Vec<Resource>, ListingError> {
+        let mut resources = Vec::new();
+        let mut next = Some(Url::parse(&self.listing)?);
+        let mut pages = 0;
+        while let Some(base) = next.take() {
+            if pages == manifest.max_pages {
+                return Err(ListingError::MaxPages(manifest.max_pages));
+            }
+            if pages > 0 && manifest.page_delay > 0 {
+                tokio::time::sleep(Duration::from_millis(manifest.page_delay)).await;
+            }
+            pages += 1;
+
+            let page = Self::fetch_page(client, base.clone()).await?;
+            resources.extend(self.page_resources(&base, &page, manifest)?);
+
+            if let Some(pointer) = &manifest.next_pointer {
+                next = match page.pointer(pointer) {
+                    None | Some(Value::Null) => None,
+                    Some(Value::String(url)) => Some(base.join(url)?),
+                    Some(_) => return Err(ListingError::NextPointer(pointer.clone())),
+                };
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Fetches a single page of the listing. If the server limits the request rate, the request
+    /// is repeated after the time requested by the server, or after a backoff delay.
+    async fn fetch_page(client: &reqwest::Client, url: Url) -> Result<Value, ListingError> {
+        let mut retry = 0;
+        loop {
+            let response = client.get(url.clone()).send().await?;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS && retry < RATE_LIMIT_RETRIES {
+                retry += 1;
+                let delay = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse().ok())
+                    .map_or_else(|| backoff(retry), Duration::from_secs);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            let response = response.error_for_status()?;
+            return Ok(serde_json::from_slice(&response.bytes().await?)?);
+        }
+    }
+
+    /// Returns the resources listed in a single page of the listing.
+    fn page_resources(
+        &self,
+        base: &Url,
+        page: &Value,
+        manifest: &ListingManifest,
+    ) -> Result<Vec<Resource>, ListingError> {
+        let items = page
+            .pointer(&manifest.pointer)
+            .and_then(Value::as_array)
+            .ok_or_else(|| ListingError::Pointer(manifest.pointer.clone()))?;
+
+        items
+            .iter()
+            .map(|item| {
+                let field = |name: &str| {
+                    item.get(name)
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| ListingError::Field(name.to_string()))
+                };
+                let url = base.join(field(&manifest.url_field)?)?;
+                let path = self.path.join(field(&manifest.path_field)?);
+                Ok(Resource {
+                    path,
+                    url: url.into(),
+                })
+            })
+            .collect()
+    }
+}
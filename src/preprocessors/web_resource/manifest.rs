@@ -1,11 +1,14 @@
-use std::fmt;
+use std::collections::{hash_map, BTreeMap, HashMap, HashSet};
 use std::io;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 
-use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer};
 
-use crate::args::ARGS;
+use crate::utils;
+
+use super::{ExecutionError, FreshnessMode, HashAlgorithm};
 
 /// Auxilliary configuration for the preprocessor
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -16,8 +19,11 @@ pub struct Manifest {
     pub overwrite: bool,
 
     /// Change this to true or a file path given as a string to enable the index. If true, the
-    /// default path is "web-resource-index.toml"; note that if multiple web-resource jobs are using
-    /// the same index file, this will lead to problems!
+    /// default path is "web-resource-index.toml", or "web-resource-index.<job>.toml" if several
+    /// web-resource jobs enable the index this way. Note that if multiple web-resource jobs are
+    /// using the same explicitly given index file, this will lead to problems!
+    /// The index is also required for resuming interrupted runs, as the remaining downloads are
+    /// persisted in a work queue next to it.
     #[serde(default, deserialize_with = "deserialize_index")]
     pub index: Option<PathBuf>,
 
@@ -25,71 +31,446 @@ pub struct Manifest {
     /// to be enabled.
     #[serde(default)]
     pub evict: bool,
+
+    /// Change this to true to respect the `Cache-Control` headers of downloaded resources. The
+    /// lifetime of each resource is then recorded in the index; until it has passed, the existing
+    /// file is used without any network access, afterwards the resource is downloaded again. This
+    /// requires the index to be enabled.
+    #[serde(default)]
+    pub respect_cache_control: bool,
+
+    /// Renames paths in the index before downloading: each key is a path prefix that is replaced
+    /// by the corresponding value. After reorganizing the project's directories, this lets
+    /// existing files be recognized at their new locations instead of being downloaded again.
+    /// Since the renamed index is saved, the entries can be removed after one run. This requires
+    /// the index to be enabled.
+    #[serde(default)]
+    pub rename: BTreeMap<PathBuf, PathBuf>,
+
+    /// Only processes query results whose discriminator field has the given value, and ignores all
+    /// others. This allows one label to carry metadata for different preprocessors.
+    #[serde(default)]
+    pub discriminator: Option<Discriminator>,
+
+    /// Decides what happens when multiple query results have the same `duplicate_key`, which is
+    /// usually a mistake in the document: `error` fails the job, `first` and `last` keep only the
+    /// first or last of these results, and `dedup` (the default) only drops results that are
+    /// identical to an earlier one. In any case, results for the same path must not have
+    /// different URLs.
+    #[serde(default)]
+    pub on_duplicate: DuplicateMode,
+
+    /// The field of the query results that identifies duplicates. Defaults to `path`.
+    #[serde(default = "default_duplicate_key")]
+    pub duplicate_key: String,
+
+    /// Configures how listings are read. Listings are query results that contain a `listing` URL
+    /// instead of a `url`; the listing is a JSON document listing multiple resources that are
+    /// downloaded into the directory given as `path`.
+    #[serde(default)]
+    pub listing: ListingManifest,
+
+    /// If given, only resources whose paths have one of these file extensions are downloaded;
+    /// others are rejected. By default, all extensions are allowed, but when querying untrusted
+    /// documents, restricting this prevents e.g. executables or dotfiles from being written.
+    #[serde(default)]
+    pub allowed_extensions: Option<Vec<String>>,
+
+    /// If given, downloaded files smaller than this many bytes are rejected: the file is deleted
+    /// and the download fails. This catches e.g. error pages that were served with a success
+    /// status, or truncated responses. Individual resources can override this with their own
+    /// `min_size` field.
+    #[serde(default)]
+    pub min_size: Option<u64>,
+
+    /// If given, downloads larger than this many bytes are aborted, and the partial file is
+    /// deleted. Individual resources can override this with their own `max_size` field.
+    #[serde(default)]
+    pub max_size: Option<u64>,
+
+    /// If given, the query and downloads are repeated until the query result doesn't change
+    /// anymore, at most this many times. This is useful if the document's metadata depends on the
+    /// downloaded files. If the query result is still changing after the last iteration, the job
+    /// fails.
+    #[serde(default)]
+    pub iterate: Option<usize>,
+
+    /// The maximum number of concurrent downloads of this job. Defaults to 16.
+    #[serde(default)]
+    pub concurrency: Option<NonZeroUsize>,
+
+    /// The maximum number of concurrent downloads from specific hosts, e.g.
+    /// `{ "api.example.com" = 2 }`. Downloads from these hosts are additionally subject to the
+    /// `concurrency` limit; hosts that are not listed are only subject to that limit.
+    #[serde(default)]
+    pub per_host_concurrency: BTreeMap<String, NonZeroUsize>,
+
+    /// The number of times a download is retried after a network error, waiting longer before
+    /// each retry. Individual resources can override this with their own `retries` field.
+    /// Defaults to 0.
+    #[serde(default)]
+    pub retries: u32,
+
+    /// Change this to true to stop the job as soon as one download fails: downloads that are in
+    /// progress are cancelled and their partial files removed, and no further downloads are
+    /// started. Other jobs are not affected.
+    #[serde(default)]
+    pub fail_fast: bool,
+
+    /// Change this to true to reject resources with plain `http://` URLs, since files downloaded
+    /// over HTTP can be tampered with. Otherwise, such resources only cause a warning, unless
+    /// `--warnings-as-errors` is given.
+    #[serde(default)]
+    pub require_https: bool,
+
+    /// Decides whether existing files are downloaded again: `never` (the default) keeps them,
+    /// `always` downloads them again, and `smart` downloads them again if they were modified
+    /// locally or if the resource's `ETag` or `Last-Modified` header changed, which is checked
+    /// with a `HEAD` request. `smart` requires the index to be enabled; without it, existing files
+    /// are kept. Files whose URL changed or whose cache lifetime expired are downloaded again in
+    /// any case.
+    #[serde(default)]
+    pub freshness: FreshnessMode,
+
+    /// Configures the HTTP requests used for downloading, e.g.
+    /// `{ method = "POST", body = '{"id": "{{path}}"}', content_type = "application/json" }`.
+    /// By default, resources are downloaded with plain `GET` requests.
+    #[serde(default)]
+    pub request: RequestManifest,
+
+    /// Authorization for downloads from specific hosts, e.g.
+    /// `{ "api.example.com" = { value = "Bearer {{env.EXAMPLE_TOKEN}}" } }`. Each host's header is
+    /// only sent to that host: redirects are followed one at a time, and each request only gets
+    /// the header configured for the host it goes to.
+    #[serde(default)]
+    pub auth: BTreeMap<String, AuthManifest>,
+
+    /// Named resources that are requested before downloading, e.g. to log in to an API, such as
+    /// `{ login = { url = "https://api.example.com/login", method = "POST", body = "..." } }`.
+    /// Their responses are not saved; instead, the auth header values, the request body and the
+    /// URLs of the document's resources can reference them as Handlebars templates, e.g.
+    /// `Bearer {{resources.login.body.token}}`. Each response is available as `status`, `headers`
+    /// and `body`, which is parsed if it is JSON. Named resources can reference each other in the
+    /// same way, and are requested in the order of these references; cycles are rejected.
+    #[serde(default)]
+    pub resources: BTreeMap<String, NamedResourceManifest>,
+
+    /// A base URL against which relative resource and listing URLs in the document are resolved,
+    /// e.g. `https://example.com/assets/`, so that the host only needs to be given in one place.
+    /// Absolute URLs are used unchanged. The base URL can reference environment variables as
+    /// `{{env.NAME}}`, and must be an absolute `http` or `https` URL.
+    pub base_url: Option<String>,
+
+    /// Overrides DNS resolution for specific hosts, like `curl --resolve`, e.g.
+    /// `{ "assets.example.com" = "10.0.0.5" }`. Requests to these hosts, including listings and
+    /// redirects, go to the given IP addresses instead of those returned by DNS; the URLs,
+    /// including their ports, are otherwise used as they are. This can e.g. point downloads at a
+    /// staging server or an internal mirror without changing the document.
+    #[serde(default)]
+    pub resolve: BTreeMap<String, IpAddr>,
+
+    /// Change this to true to write the final URL, status and headers of each download's response
+    /// to a sidecar file next to the downloaded file, e.g. `logo.svg.headers.json` for
+    /// `logo.svg`. This helps diagnosing e.g. content type or caching problems later. Sidecar
+    /// files are evicted together with their resources.
+    #[serde(default)]
+    pub save_headers: bool,
+
+    /// Hash algorithms (`sha256`, `sha384`, `sha512`) whose hashes of each downloaded file are
+    /// recorded in the index as `hashes`, e.g. for feeding them into other tooling. They are
+    /// computed while downloading. This has no effect without an index.
+    #[serde(default)]
+    pub hashes: Vec<HashAlgorithm>,
+
+    /// Change this to true to record a Subresource Integrity string (`integrity`) for each
+    /// downloaded file in the index. It contains the hashes of the algorithms given in `hashes`,
+    /// or the SHA-256 hash if none are given. This has no effect without an index.
+    #[serde(default)]
+    pub integrity: bool,
+
+    /// How downloaded files are stored. With `symlink` or `hardlink`, each file is moved into a
+    /// content-addressed cache and linked from the resource's path, so that identical files
+    /// referenced by several documents only take up disk space once. Where linking is not
+    /// supported, files are copied instead. Defaults to `copy`, which does not use the cache.
+    #[serde(default)]
+    pub link: LinkMode,
+
+    /// The directory of the content-addressed cache used when linking files, relative to the
+    /// directory containing `typst.toml`. Defaults to "web-resource-cache".
+    #[serde(default = "default_cache")]
+    pub cache: PathBuf,
+
+    /// Enables an on-disk HTTP cache for downloads, e.g. `{ mode = "force-cache" }`. The cache
+    /// follows the HTTP caching rules: responses are stored according to their headers, and
+    /// stale responses are revalidated with conditional requests. The index still decides
+    /// whether a resource is downloaded at all; the cache only applies to the downloads that are
+    /// made, so that e.g. a file that was deleted locally can be restored without network access.
+    #[cfg(feature = "http-cache")]
+    #[serde(default)]
+    pub http_cache: Option<HttpCacheManifest>,
 }
 
-impl Manifest {
-    pub async fn resolve_index_path(&self) -> Option<io::Result<PathBuf>> {
-        async fn inner<P: AsRef<Path>>(index: P) -> io::Result<PathBuf> {
-            let mut path = ARGS.resolve_typst_toml().await?;
-            let result = path.pop();
-            assert!(
-                result,
-                "the path should have had a final filename component"
-            );
-            path.push(&index);
-            Ok(path)
-        }
+/// Configuration of the on-disk HTTP cache
+#[cfg(feature = "http-cache")]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HttpCacheManifest {
+    /// The directory of the cache, relative to the directory containing `typst.toml`. Defaults
+    /// to "web-resource-http-cache".
+    #[serde(default = "default_http_cache")]
+    pub directory: PathBuf,
+    /// How the cache is used. Defaults to `default`.
+    #[serde(default)]
+    pub mode: HttpCacheMode,
+}
 
-        if let Some(index) = &self.index {
-            Some(inner(index).await)
-        } else {
-            None
+/// How the HTTP cache is used
+#[cfg(feature = "http-cache")]
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpCacheMode {
+    /// Responses are cached and revalidated according to their headers
+    #[default]
+    Default,
+    /// Responses are neither taken from nor stored in the cache
+    NoStore,
+    /// Cached responses are used regardless of their age; only missing responses are downloaded
+    ForceCache,
+}
+
+#[cfg(feature = "http-cache")]
+fn default_http_cache() -> PathBuf {
+    "web-resource-http-cache".into()
+}
+
+/// Configuration of the HTTP requests used for downloading
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct RequestManifest {
+    /// The HTTP method. Defaults to `GET`.
+    pub method: String,
+    /// The request body, a Handlebars template that can reference the resource's `url` and
+    /// `path`, and environment variables as `env.NAME`
+    pub body: Option<String>,
+    /// The `Content-Type` header of the request
+    pub content_type: Option<String>,
+}
+
+/// A named resource, which is requested before downloading so that its response can be
+/// referenced
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NamedResourceManifest {
+    /// The URL, a Handlebars template that can reference environment variables as `env.NAME` and
+    /// other named resources as `resources.NAME`
+    pub url: String,
+    /// The method, body and content type of the request; the body is rendered like the URL
+    #[serde(flatten)]
+    pub request: RequestManifest,
+    /// Additional headers of the request, whose values are rendered like the URL
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Authorization for downloads from one host
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthManifest {
+    /// The header carrying the authorization. Defaults to `Authorization`.
+    #[serde(default = "default_auth_header")]
+    pub header: String,
+    /// The header's value, a Handlebars template that can reference the resource's `url` and
+    /// `path`, and environment variables as `env.NAME`, e.g. `Bearer {{env.EXAMPLE_TOKEN}}`
+    pub value: String,
+}
+
+fn default_auth_header() -> String {
+    "Authorization".to_string()
+}
+
+impl Default for RequestManifest {
+    fn default() -> Self {
+        Self {
+            method: "GET".to_string(),
+            body: None,
+            content_type: None,
         }
     }
 }
 
-/// Deserializes the `index` config: if given, must be either a boolean or string.
-fn deserialize_index<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    struct IndexVisitor;
+/// How downloaded files are stored
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkMode {
+    /// The file is stored at the resource's path
+    #[default]
+    Copy,
+    /// The file is stored in the cache, and the resource's path is a symbolic link to it
+    Symlink,
+    /// The file is stored in the cache, and the resource's path is a hard link to it
+    Hardlink,
+}
 
-    impl<'de> Visitor<'de> for IndexVisitor {
-        type Value = Option<PathBuf>;
+/// How multiple query results with the same key are handled
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateMode {
+    /// Duplicates are an error
+    Error,
+    /// Only the first of the duplicates is kept
+    First,
+    /// Only the last of the duplicates is kept, at the position of the first
+    Last,
+    /// Only results that are identical to an earlier one are dropped
+    #[default]
+    Dedup,
+}
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a boolean or string`")
+impl DuplicateMode {
+    /// Handles the query results that have the same value in the given key field. Results without
+    /// that field are kept.
+    pub fn apply(
+        self,
+        key: &str,
+        elements: Vec<serde_json::Value>,
+    ) -> Result<Vec<serde_json::Value>, ExecutionError> {
+        let mut result: Vec<serde_json::Value> = Vec::with_capacity(elements.len());
+        let mut positions = HashMap::new();
+        let mut seen = HashSet::new();
+        for element in elements {
+            let Some(value) = element.get(key).map(|value| value.to_string()) else {
+                result.push(element);
+                continue;
+            };
+            match (self, positions.entry(value)) {
+                (_, hash_map::Entry::Vacant(entry)) => {
+                    entry.insert(result.len());
+                    seen.insert(element.to_string());
+                    result.push(element);
+                }
+                (Self::Error, hash_map::Entry::Occupied(entry)) => {
+                    let key = key.to_string();
+                    let value = entry.key().clone();
+                    return Err(ExecutionError::Duplicate { key, value });
+                }
+                (Self::First, _) => {}
+                (Self::Last, hash_map::Entry::Occupied(entry)) => {
+                    result[*entry.get()] = element;
+                }
+                (Self::Dedup, _) => {
+                    if seen.insert(element.to_string()) {
+                        result.push(element);
+                    }
+                }
+            }
         }
+        Ok(result)
+    }
+}
 
-        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            Ok(v.then(|| "web-resource-index.toml".into()))
-        }
+fn default_duplicate_key() -> String {
+    "path".to_string()
+}
+
+fn default_cache() -> PathBuf {
+    "web-resource-cache".into()
+}
+
+/// Configuration for reading listings of resources
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ListingManifest {
+    /// A JSON pointer to the array of listed resources inside the listing. By default, the listing
+    /// itself must be that array.
+    pub pointer: String,
+    /// The field of each listed resource that contains its URL, relative to the listing's URL.
+    /// Defaults to `url`.
+    pub url_field: String,
+    /// The field of each listed resource that contains its path, relative to the listing's path.
+    /// Defaults to `path`.
+    pub path_field: String,
+    /// A JSON pointer to the URL of the listing's next page, relative to the current page's URL.
+    /// If given, pages are fetched until this is missing or `null`, and the resources of all
+    /// pages are combined. By default, listings are not paginated.
+    pub next_pointer: Option<String>,
+    /// The maximum number of pages fetched for a single listing; a listing with more pages is
+    /// rejected. Defaults to 100.
+    pub max_pages: usize,
+    /// The delay in milliseconds between fetching two pages of a listing. Defaults to 0.
+    pub page_delay: u64,
+}
 
-        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            self.visit_string(v.to_owned())
+impl Default for ListingManifest {
+    fn default() -> Self {
+        Self {
+            pointer: String::new(),
+            url_field: "url".to_string(),
+            path_field: "path".to_string(),
+            next_pointer: None,
+            max_pages: 100,
+            page_delay: 0,
         }
+    }
+}
+
+impl Manifest {
+    /// Checks whether the given path's extension is allowed.
+    pub fn is_extension_allowed(&self, path: &Path) -> bool {
+        let Some(allowed) = &self.allowed_extensions else {
+            return true;
+        };
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        allowed.iter().any(|allowed| {
+            allowed
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(&extension)
+        })
+    }
+}
 
-        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            Ok(Some(v.into()))
+/// Identifies the query results that are meant for a job
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Discriminator {
+    /// The field of the query results that is checked
+    pub field: String,
+    /// The value the field must have for a query result to be processed
+    pub value: String,
+}
+
+impl Discriminator {
+    /// Checks whether the given query result should be processed.
+    pub fn matches(&self, element: &serde_json::Value) -> bool {
+        element
+            .get(self.field.as_str())
+            .and_then(|value| value.as_str())
+            .is_some_and(|value| value == self.value)
+    }
+}
+
+impl Manifest {
+    pub async fn resolve_index_path(&self) -> Option<io::Result<PathBuf>> {
+        if let Some(index) = &self.index {
+            Some(utils::resolve_manifest_path(index).await)
+        } else {
+            None
         }
+    }
+
+    pub async fn resolve_cache_path(&self) -> io::Result<PathBuf> {
+        utils::resolve_manifest_path(&self.cache).await
+    }
 
-        fn visit_none<E>(self) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            Ok(None)
+    #[cfg(feature = "http-cache")]
+    pub async fn resolve_http_cache_path(&self) -> Option<io::Result<PathBuf>> {
+        if let Some(http_cache) = &self.http_cache {
+            Some(utils::resolve_manifest_path(&http_cache.directory).await)
+        } else {
+            None
         }
     }
+}
 
-    deserializer.deserialize_any(IndexVisitor)
+/// Deserializes the `index` config: if given, must be either a boolean or string.
+fn deserialize_index<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    utils::deserialize_index(deserializer, "web-resource-index.toml")
 }
@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use handlebars::Handlebars;
+use itertools::Itertools;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{ManifestError, NamedResourceError, NamedResourceManifest};
+
+/// The data available when rendering the request of a named resource
+#[derive(Serialize)]
+struct RequestData<'a> {
+    env: HashMap<String, String>,
+    resources: &'a Map<String, Value>,
+}
+
+impl NamedResourceManifest {
+    /// The names of the named resources this one references as `resources.NAME`.
+    fn references(&self) -> BTreeSet<&str> {
+        let templates = [&self.url]
+            .into_iter()
+            .chain(&self.request.body)
+            .chain(self.headers.values());
+        templates
+            .flat_map(|template| references(template))
+            .collect()
+    }
+
+    /// Sends the request for this named resource, whose templates are rendered with the responses
+    /// of the named resources requested before, and returns the response.
+    async fn fetch(
+        &self,
+        client: &reqwest::Client,
+        name: &str,
+        responses: &Map<String, Value>,
+    ) -> Result<Value, NamedResourceError> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        let data = RequestData {
+            env: std::env::vars().collect(),
+            resources: responses,
+        };
+        let render = |template: &str| {
+            handlebars
+                .render_template(template, &data)
+                .map_err(|source| NamedResourceError::Render {
+                    name: name.to_string(),
+                    source,
+                })
+        };
+        let request_error = |source| NamedResourceError::Request {
+            name: name.to_string(),
+            source,
+        };
+
+        let request = client.request(self.request.method(), render(&self.url)?);
+        let body = self.request.body.as_deref().map(render).transpose()?;
+        let mut request = self.request.with_body(request, body);
+        for (header, value) in &self.headers {
+            request = request.header(header, render(value)?);
+        }
+        let response = request.send().await.map_err(request_error)?;
+        let response = response.error_for_status().map_err(request_error)?;
+
+        let status = response.status().as_u16();
+        let headers: Map<_, _> = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = value.to_str().ok()?;
+                Some((name.to_string(), Value::from(value)))
+            })
+            .collect();
+        let body = response.text().await.map_err(request_error)?;
+        let body = serde_json::from_str(&body).unwrap_or(Value::String(body));
+        Ok(serde_json::json!({
+            "status": status,
+            "headers": headers,
+            "body": body,
+        }))
+    }
+}
+
+/// Returns the names of the named resources referenced in a template as `resources.NAME`.
+pub fn references(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .match_indices("resources.")
+        .filter_map(|(start, prefix)| {
+            let rest = &template[start + prefix.len()..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-')
+                .unwrap_or(rest.len());
+            (end > 0).then(|| &rest[..end])
+        })
+}
+
+/// Returns the names of the named resources in an order in which each resource comes after those
+/// it references. Fails if a resource references an unknown resource, or if there is a cycle.
+pub fn order(
+    resources: &BTreeMap<String, NamedResourceManifest>,
+) -> Result<Vec<String>, ManifestError> {
+    let mut order = Vec::new();
+    let mut path = Vec::new();
+    for name in resources.keys() {
+        visit(resources, name, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Adds the given resource to the order after the resources it references, depth-first. `path`
+/// contains the resources whose references are being visited.
+fn visit<'a>(
+    resources: &'a BTreeMap<String, NamedResourceManifest>,
+    name: &'a str,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<String>,
+) -> Result<(), ManifestError> {
+    if let Some(start) = path.iter().position(|&resource| resource == name) {
+        let cycle = path[start..].iter().chain([&name]);
+        let cycle = cycle.map(|name| format!("`{name}`")).join(" -> ");
+        return Err(ManifestError::ResourceCycle(cycle));
+    }
+    if order.iter().any(|resource| resource == name) {
+        return Ok(());
+    }
+    let resource = &resources[name];
+    path.push(name);
+    for reference in resource.references() {
+        if !resources.contains_key(reference) {
+            return Err(ManifestError::UnknownResource {
+                name: name.to_string(),
+                reference: reference.to_string(),
+            });
+        }
+        visit(resources, reference, path, order)?;
+    }
+    path.pop();
+    order.push(name.to_string());
+    Ok(())
+}
+
+/// Requests the named resources in the given order, and returns their responses by name.
+pub async fn fetch_all(
+    client: &reqwest::Client,
+    resources: &BTreeMap<String, NamedResourceManifest>,
+    order: &[String],
+) -> Result<Value, NamedResourceError> {
+    let mut responses = Map::new();
+    for name in order {
+        let response = resources[name].fetch(client, name, &responses).await?;
+        responses.insert(name.clone(), response);
+    }
+    Ok(Value::Object(responses))
+}
@@ -0,0 +1,89 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::report::Counts;
+use crate::utils::status;
+
+/// How often a summary line is printed at most when progress can't be displayed live
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The aggregate progress of a job's downloads, displayed instead of a line per file with
+/// `--progress`. On a terminal, a single line is updated in place; otherwise, summary lines are
+/// printed periodically.
+#[derive(Debug)]
+pub struct Progress {
+    name: String,
+    total: usize,
+    bytes: AtomicU64,
+    live: bool,
+    last_summary: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    /// Creates the progress display for the given number of resources of the named job
+    pub fn new(name: &str, total: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            total,
+            bytes: AtomicU64::new(0),
+            live: io::stderr().is_terminal(),
+            last_summary: Mutex::new(None),
+        }
+    }
+
+    /// Adds downloaded bytes to the progress
+    pub fn add_bytes(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Displays the progress after a resource was completed, given the job's current counts
+    pub fn update(&self, counts: Counts) {
+        if self.live {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K{}", self.summary(counts));
+            let _ = stderr.flush();
+            return;
+        }
+        let mut last_summary = self
+            .last_summary
+            .lock()
+            .expect("lock should not be poisoned");
+        if last_summary.is_some_and(|last| last.elapsed() < SUMMARY_INTERVAL) {
+            return;
+        }
+        *last_summary = Some(Instant::now());
+        status!("{}", self.summary(counts));
+    }
+
+    /// Removes the live progress line, so that another message can be printed
+    pub fn clear(&self) {
+        if self.live {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+
+    /// Displays the final progress after all resources were completed
+    pub fn finish(&self, counts: Counts) {
+        self.clear();
+        status!("{}", self.summary(counts));
+    }
+
+    fn summary(&self, counts: Counts) -> String {
+        let Counts {
+            processed,
+            skipped,
+            failed,
+        } = counts;
+        let done = processed + skipped + failed;
+        let megabytes = self.bytes.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        format!(
+            "[{}] {done}/{} files done ({processed} downloaded, {skipped} skipped, \
+            {failed} failed), {megabytes:.1} MB",
+            self.name, self.total,
+        )
+    }
+}
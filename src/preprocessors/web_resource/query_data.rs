@@ -4,13 +4,86 @@ use std::fmt;
 use std::path::PathBuf;
 
 use serde::de::{self, Deserializer, Error, Unexpected, Visitor};
-use serde::Deserialize;
+use serde::ser::{SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
 
 use super::Resource;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// The resources and listings of a web-resource query result. This (de)serializes as the list
+/// of query result elements, e.g. `[{"path": "a.svg", "url": "https://..."}]`; resources given
+/// multiple times are combined.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct QueryData {
+    /// The URLs of resources, by the path they are downloaded to
     pub resources: BTreeMap<PathBuf, String>,
+    /// The listing URLs, by the directory the listed resources are downloaded to
+    pub listings: BTreeMap<PathBuf, String>,
+    /// The options of resources that configure any, by path
+    pub options: BTreeMap<PathBuf, ResourceOptions>,
+}
+
+/// Options of a resource given in the query result, in addition to its path and URL
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceOptions {
+    /// Overrides the job's number of retries for this resource.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// The resource never changes, e.g. because its URL contains a hash of its content: once its
+    /// file exists, it is kept without checking the index or the server.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub immutable: bool,
+    /// The resource changes frequently: it is downloaded again in every run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub volatile: bool,
+    /// Overrides the job's minimum file size for this resource.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// Overrides the job's maximum file size for this resource.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+}
+
+impl ResourceOptions {
+    /// Combines the options of a resource that is given multiple times.
+    fn merge(self, other: Self) -> Self {
+        Self {
+            retries: self.retries.max(other.retries),
+            immutable: self.immutable || other.immutable,
+            volatile: self.volatile || other.volatile,
+            // the stricter size limits apply
+            min_size: self.min_size.max(other.min_size),
+            max_size: match (self.max_size, other.max_size) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+/// A listing of multiple resources that should be downloaded into a directory
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    /// The directory to download the listed resources to. Must be in the document's root.
+    pub path: PathBuf,
+    /// The URL of the listing.
+    pub listing: String,
+}
+
+/// A resource given in the query result
+#[derive(Serialize, Deserialize)]
+struct ResourceElement {
+    #[serde(flatten)]
+    resource: Resource,
+    #[serde(flatten)]
+    options: ResourceOptions,
+}
+
+/// A single element of the query result: either a resource or a listing
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Element {
+    Resource(ResourceElement),
+    Listing(Listing),
 }
 
 impl<'de> Deserialize<'de> for QueryData {
@@ -21,7 +94,7 @@ impl<'de> Deserialize<'de> for QueryData {
         struct FieldVisitor;
 
         impl<'de> Visitor<'de> for FieldVisitor {
-            type Value = BTreeMap<PathBuf, String>;
+            type Value = QueryData;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
                 formatter
@@ -32,9 +105,33 @@ impl<'de> Deserialize<'de> for QueryData {
             where
                 A: de::SeqAccess<'de>,
             {
-                let mut resources = Self::Value::new();
-                while let Some(Resource { path, url }) = seq.next_element()? {
-                    let entry = resources.entry(path);
+                let mut resources: BTreeMap<PathBuf, String> = BTreeMap::new();
+                let mut listings = BTreeMap::new();
+                let mut options = BTreeMap::new();
+                while let Some(element) = seq.next_element()? {
+                    let (map, path, url) = match element {
+                        Element::Resource(ResourceElement {
+                            resource: Resource { path, url },
+                            options: resource_options,
+                        }) => {
+                            if resource_options != ResourceOptions::default() {
+                                let entry: &mut ResourceOptions =
+                                    options.entry(path.clone()).or_default();
+                                *entry = entry.merge(resource_options);
+                                if entry.immutable && entry.volatile {
+                                    return Err(Error::custom(format!(
+                                        "{} can't be both immutable and volatile",
+                                        path.display()
+                                    )));
+                                }
+                            }
+                            (&mut resources, path, url)
+                        }
+                        Element::Listing(Listing { path, listing }) => {
+                            (&mut listings, path, listing)
+                        }
+                    };
+                    let entry = map.entry(path);
                     match entry {
                         Entry::Occupied(entry) => {
                             // the entry is either ok, or we error here
@@ -50,12 +147,40 @@ impl<'de> Deserialize<'de> for QueryData {
                         }
                     }
                 }
-                Ok(resources)
+                Ok(QueryData {
+                    resources,
+                    listings,
+                    options,
+                })
             }
         }
 
-        deserializer
-            .deserialize_seq(FieldVisitor)
-            .map(|resources| Self { resources })
+        deserializer.deserialize_seq(FieldVisitor)
+    }
+}
+
+impl Serialize for QueryData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let len = self.resources.len() + self.listings.len();
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for (path, url) in &self.resources {
+            let resource = Resource {
+                path: path.clone(),
+                url: url.clone(),
+            };
+            let options = self.options.get(path).copied().unwrap_or_default();
+            seq.serialize_element(&ResourceElement { resource, options })?;
+        }
+        for (path, listing) in &self.listings {
+            let listing = Listing {
+                path: path.clone(),
+                listing: listing.clone(),
+            };
+            seq.serialize_element(&listing)?;
+        }
+        seq.end()
     }
 }
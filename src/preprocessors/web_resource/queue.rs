@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use super::query_data::ResourceOptions;
+use super::IndexError;
+
+/// The resources of a run that have not been downloaded yet. The queue is persisted while
+/// downloading, so that an interrupted run can be resumed without querying the document again.
+/// It is kept next to the index, so jobs without an index can't be resumed.
+///
+/// The queue file itself is only written once at the start of a run; completed resources are
+/// appended to a separate log (see [WorkQueue::log_location]), so that each completion only
+/// costs a short write instead of rewriting the whole queue.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkQueue {
+    #[serde(skip)]
+    location: PathBuf,
+    /// The log of completed resources, once it was opened for writing
+    #[serde(skip)]
+    log: Option<File>,
+    /// The remaining resources, mapping paths to URLs
+    #[serde(default)]
+    pub resources: BTreeMap<PathBuf, String>,
+    /// The options of remaining resources that configure any, by path
+    #[serde(default)]
+    pub options: BTreeMap<PathBuf, ResourceOptions>,
+}
+
+impl WorkQueue {
+    pub fn new(
+        location: PathBuf,
+        resources: BTreeMap<PathBuf, String>,
+        options: BTreeMap<PathBuf, ResourceOptions>,
+    ) -> Self {
+        Self {
+            location,
+            log: None,
+            resources,
+            options,
+        }
+    }
+
+    /// Returns the location of the work queue belonging to the given index file, e.g.
+    /// `web-resource-index.queue.toml` for `web-resource-index.toml`.
+    pub fn location_for(index: &Path) -> PathBuf {
+        index.with_extension("queue.toml")
+    }
+
+    /// Returns the location of the log of completed resources, e.g.
+    /// `web-resource-index.queue.done` for `web-resource-index.queue.toml`. Each line of the log
+    /// contains the path of a completed resource as a JSON string.
+    pub fn log_location(&self) -> PathBuf {
+        self.location.with_extension("done")
+    }
+
+    /// Reads a work queue from a file, if that file exists. Resources recorded in the log of
+    /// completed resources are removed from the queue.
+    pub async fn read_existing(location: PathBuf) -> Result<Option<Self>, IndexError> {
+        let queue = match fs::read_to_string(&location).await {
+            Ok(queue) => queue,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let mut queue: Self = toml::from_str(&queue)?;
+        queue.location = location;
+
+        let log = match fs::read_to_string(queue.log_location()).await {
+            Ok(log) => log,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        // the last line may be incomplete if the run was interrupted while writing it
+        let completed = log
+            .lines()
+            .map_while(|line| serde_json::from_str::<PathBuf>(line).ok());
+        for path in completed {
+            queue.resources.remove(&path);
+            queue.options.remove(&path);
+        }
+
+        Ok(Some(queue))
+    }
+
+    /// Writes the work queue to its file, and starts a new log of completed resources.
+    pub async fn write(&mut self) -> Result<(), IndexError> {
+        let queue = toml::to_string(self)?;
+        fs::write(&self.location, queue).await?;
+        let log = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.log_location())
+            .await?;
+        self.log = Some(log);
+        Ok(())
+    }
+
+    /// Deletes the work queue's files, e.g. because all work is done.
+    pub async fn delete(&mut self) -> Result<(), IndexError> {
+        self.log = None;
+        for location in [self.log_location(), self.location.clone()] {
+            match fs::remove_file(&location).await {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a resource that does not need to be downloaded anymore from the queue, and records
+    /// this in the log of completed resources if the queue was written.
+    pub async fn complete(&mut self, path: &Path) -> Result<(), IndexError> {
+        self.resources.remove(path);
+        self.options.remove(path);
+        if let Some(log) = &mut self.log {
+            let mut line = serde_json::to_string(&path.to_string_lossy())
+                .expect("a string should be serializable");
+            line.push('\n');
+            log.write_all(line.as_bytes()).await?;
+            log.flush().await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use reqwest::redirect::Policy;
+
+use super::{DownloadError, RedirectError};
+
+/// The number of redirects followed before giving up, the same as reqwest's default
+pub const MAX_REDIRECTS: usize = 10;
+
+/// Creates a redirect policy that follows up to [MAX_REDIRECTS] redirects, and then fails with a
+/// [RedirectError] containing the visited URLs.
+pub fn policy() -> Policy {
+    Policy::custom(|attempt| {
+        if attempt.previous().len() <= MAX_REDIRECTS {
+            return attempt.follow();
+        }
+        let chain = attempt.previous().iter().chain([attempt.url()]);
+        let error = RedirectError::new(chain.cloned().collect());
+        attempt.error(error)
+    })
+}
+
+/// Converts a network error, recovering the redirect chain if the redirect limit was hit.
+pub fn network_error(error: reqwest::Error) -> DownloadError {
+    let redirect = std::error::Error::source(&error)
+        .and_then(|source| source.downcast_ref::<RedirectError>())
+        .filter(|_| error.is_redirect());
+    match redirect {
+        Some(redirect) => redirect.clone().into(),
+        None => error.into(),
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use handlebars::Handlebars;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, RequestBuilder};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::{DownloadError, RequestManifest, Resource};
+
+/// The data available when rendering a request body
+#[derive(Serialize)]
+struct BodyData<'a> {
+    url: &'a str,
+    path: &'a str,
+    env: HashMap<String, String>,
+    resources: &'a Value,
+}
+
+/// The data available when rendering a template that doesn't belong to a resource
+#[derive(Serialize)]
+struct EnvData {
+    env: HashMap<String, String>,
+}
+
+/// Renders a Handlebars template that can reference environment variables as `env.NAME`.
+pub fn render_env(template: &str) -> Result<String, handlebars::RenderError> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let data = EnvData {
+        env: std::env::vars().collect(),
+    };
+    handlebars.render_template(template, &data)
+}
+
+impl RequestManifest {
+    /// The configured HTTP method. The method is validated when the job is configured.
+    pub fn method(&self) -> Method {
+        Method::from_bytes(self.method.as_bytes()).expect("the HTTP method should be valid")
+    }
+
+    /// Renders the request body for the given resource, if a body is configured. The body is a
+    /// Handlebars template that can reference `url`, `path`, environment variables as `env.NAME`
+    /// and the responses of named resources as `resources.NAME`.
+    pub fn body(
+        &self,
+        resource: &Resource,
+        responses: &Value,
+    ) -> Result<Option<String>, DownloadError> {
+        let Some(body) = &self.body else {
+            return Ok(None);
+        };
+        let body = Self::render(body, resource, responses)?;
+        Ok(Some(body))
+    }
+
+    /// Renders a Handlebars template for the given resource, which can reference `url`, `path`,
+    /// environment variables as `env.NAME` and the responses of named resources as
+    /// `resources.NAME`.
+    pub fn render(
+        template: &str,
+        resource: &Resource,
+        responses: &Value,
+    ) -> Result<String, DownloadError> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        let data = BodyData {
+            url: &resource.url,
+            path: &resource.path.to_string_lossy(),
+            env: std::env::vars().collect(),
+            resources: responses,
+        };
+        let rendered = handlebars.render_template(template, &data)?;
+        Ok(rendered)
+    }
+
+    /// Returns the URL the given resource is requested from. If named resources were requested,
+    /// the resource's URL is a template that can reference their responses; it is rendered like
+    /// the request body. Otherwise, the URL is used as is.
+    pub fn url(resource: &Resource, responses: &Value) -> Result<String, DownloadError> {
+        if responses.is_null() {
+            return Ok(resource.url.clone());
+        }
+        Self::render(&resource.url, resource, responses)
+    }
+
+    /// Builds the request for downloading the given resource with the given client.
+    pub fn build(
+        &self,
+        client: &reqwest::Client,
+        resource: &Resource,
+        responses: &Value,
+    ) -> Result<RequestBuilder, DownloadError> {
+        let url = Self::url(resource, responses)?;
+        let request = client.request(self.method(), url);
+        let request = self.with_body(request, self.body(resource, responses)?);
+        Ok(request)
+    }
+
+    /// Adds the given body, and the configured `Content-Type` header, to the request.
+    pub fn with_body(&self, mut request: RequestBuilder, body: Option<String>) -> RequestBuilder {
+        if let Some(content_type) = &self.content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        request
+    }
+
+    /// Identifies the request for the given resource beyond its URL, so that the index can tell
+    /// when the method or body changed. Plain `GET` requests without a body are identified by their
+    /// URL alone, and return `None`. The body is rendered without the responses of named
+    /// resources, so that e.g. a new token doesn't make the request look changed.
+    pub fn fingerprint(&self, resource: &Resource) -> Result<Option<String>, DownloadError> {
+        let body = self.body(resource, &Value::Null)?;
+        if self.method() == Method::GET && body.is_none() {
+            return Ok(None);
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.method().as_str());
+        hasher.update("\n");
+        hasher.update(body.unwrap_or_default());
+        Ok(Some(format!("{:x}", hasher.finalize())))
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use super::DownloadError;
+
+/// The delay before the first retry of a failed download; each further retry waits twice as long
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+
+/// The longest delay between two attempts of a download
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Returns how long to wait before the given retry, counting from one.
+pub fn backoff(retry: u32) -> Duration {
+    let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+    INITIAL_DELAY.saturating_mul(factor).min(MAX_DELAY)
+}
+
+impl DownloadError {
+    /// Checks whether another attempt of the download could succeed. Only network errors are
+    /// considered transient; local problems and exceeded limits would occur again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network(_) => true,
+            #[cfg(feature = "http-cache")]
+            Self::Middleware(_) => true,
+            _ => false,
+        }
+    }
+}
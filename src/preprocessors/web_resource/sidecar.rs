@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tokio::fs;
+
+/// The response to a download, as written to a `.headers.json` sidecar file with `save_headers`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHeaders {
+    /// The URL the response came from, after following redirects
+    pub url: String,
+    /// The response's status code
+    pub status: u16,
+    /// The response headers; repeated headers are combined into one, separated by commas
+    pub headers: BTreeMap<String, String>,
+}
+
+impl ResponseHeaders {
+    /// Records the given response's headers.
+    pub fn new(response: &reqwest::Response) -> Self {
+        let mut headers = BTreeMap::<String, String>::new();
+        for (name, value) in response.headers() {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            headers
+                .entry(name.to_string())
+                .and_modify(|existing| {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
+        Self {
+            url: response.url().to_string(),
+            status: response.status().as_u16(),
+            headers,
+        }
+    }
+
+    /// Writes the headers to the given (resolved) sidecar file.
+    pub async fn write(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_vec_pretty(self)?;
+        fs::write(path, content).await
+    }
+}
+
+/// Returns the path of the sidecar file for the resource at the given path, e.g.
+/// `assets/logo.svg.headers.json` for `assets/logo.svg`.
+pub fn path(path: &Path) -> PathBuf {
+    let mut sidecar = OsString::from(path.as_os_str());
+    sidecar.push(".headers.json");
+    sidecar.into()
+}
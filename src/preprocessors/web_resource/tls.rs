@@ -0,0 +1,49 @@
+use reqwest::tls::TlsInfo;
+use x509_parser::prelude::*;
+
+use super::TlsError;
+use crate::report::error_chain;
+
+/// The result of successfully verifying a host's TLS certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    /// The end of the certificate's validity period
+    pub not_after: String,
+    /// The number of whole days until the certificate expires
+    pub days_left: i64,
+}
+
+/// Performs a TLS handshake with the given host (which may include a port) and checks the
+/// certificate presented by it. No resource is downloaded; only the response headers to a `HEAD`
+/// request are received.
+pub async fn verify(client: &reqwest::Client, host: &str) -> Result<Certificate, TlsError> {
+    let handshake_error = |error: reqwest::Error| TlsError::Handshake {
+        host: host.to_string(),
+        reason: error_chain(&error),
+    };
+    let certificate_error = |reason: String| TlsError::Certificate {
+        host: host.to_string(),
+        reason,
+    };
+
+    let url = format!("https://{host}/");
+    // any HTTP status is fine, as long as the handshake succeeded
+    let response = client.head(url).send().await.map_err(handshake_error)?;
+    let der = response
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(TlsInfo::peer_certificate)
+        .ok_or_else(|| certificate_error("no certificate was presented".to_string()))?;
+    let (_, certificate) =
+        X509Certificate::from_der(der).map_err(|error| certificate_error(error.to_string()))?;
+
+    let validity = certificate.validity();
+    let not_after = validity.not_after.to_string();
+    let Some(time_left) = validity.time_to_expiration() else {
+        return Err(certificate_error(format!("expired at {not_after}")));
+    };
+    Ok(Certificate {
+        not_after,
+        days_left: time_left.whole_days(),
+    })
+}
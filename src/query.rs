@@ -1,31 +1,233 @@
 //! Executing `typst query` commands
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
+use std::io::{self, BufRead};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use itertools::Either;
+use once_cell::sync::Lazy;
 use serde::Deserialize;
+use serde_json::{Number, Value};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
 use tokio::process::Command;
+use tokio::sync::{OnceCell, Semaphore};
+use tokio_util::io::SyncIoBridge;
+use typst_syntax::package::PackageVersion;
 
 use crate::args::ARGS;
-use crate::manifest;
+use crate::manifest::{self, FallbackInput};
+use crate::utils::{self, status};
 
 pub use error::*;
 
+mod cache;
+
+/// The delay before the first retry of a failed query, if not configured otherwise
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The results of the `typst query` processes of the current run, by their command line and
+/// format. Jobs that run the same query share a single process instead of each spawning their
+/// own. Failed queries are not cached, so each job runs them again.
+static RESULTS: Lazy<Mutex<HashMap<String, Arc<OnceCell<Value>>>>> = Lazy::new(Default::default);
+
+/// Limits the number of `typst query` processes running at the same time (`--query-concurrency`)
+static PROCESSES: Lazy<Semaphore> = Lazy::new(|| {
+    let limit = ARGS
+        .query_concurrency
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    Semaphore::new(limit)
+});
+
+/// Forgets the results of all queries run so far. This is necessary before running jobs again,
+/// as the document may have changed since. Results cached on disk are kept, but checked against
+/// the document again.
+pub(crate) async fn clear_cache() {
+    RESULTS
+        .lock()
+        .expect("the query results should not be poisoned")
+        .clear();
+    cache::reset().await;
+}
+
+/// Runs `typst --version` with the Typst binary given by `--typst`, and returns its version.
+pub async fn probe_typst_version() -> Result<PackageVersion> {
+    let mut command = Command::new(&ARGS.typst);
+    command.arg("--version").stderr(Stdio::piped());
+    let output = command.output().await?;
+    if !output.status.success() {
+        let status = output.status;
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(Error::Failure {
+            command: Box::new(command),
+            status,
+            stderr,
+        });
+    }
+    // the output looks like `typst 0.11.1 (5cac0dd4)`
+    let output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let version = output.split_whitespace().nth(1).unwrap_or_default();
+    // pre-releases such as `0.12.0-rc1` are treated like the release
+    let version = version.split('-').next().unwrap_or_default();
+    version.parse().map_err(|_| Error::Version(output))
+}
+
 /// A query that can be run against a Typst document. This is usually configured from a
 /// [config::Query] using a [QueryBuilder].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Query {
-    /// The selector to be queried, e.g. `<label>`
-    pub selector: String,
+    /// The name of the job running the query, used in status messages
+    pub job: String,
+    /// The document to query; by default the input file given on the command line
+    pub input: PathBuf,
+    /// The selectors to be queried, e.g. `<label>`; the results of several selectors are
+    /// concatenated in order
+    pub selector: Vec<String>,
     /// The field (`--field`) to be queried from the selector (with metadata elements, this is
     /// usually `value`)
     pub field: Option<String>,
+    /// Nested fields that are selected from each result after querying `field`, e.g. `["url"]`
+    /// for a `field` of `value.url`
+    pub field_path: Vec<String>,
     /// Whether only one (`--one`) query result is expected and should be returned
     pub one: bool,
     /// Any additional inputs (`--input`) to be given to the queried document. Regardless of these
-    /// settings, `prequery-fallback` is always set to `true` during queries.
+    /// settings, the fallback input is set during queries unless disabled.
     pub inputs: HashMap<String, String>,
+    /// The input that is set during queries, by default `prequery-fallback=true`. If `None`, no
+    /// fallback input is set.
+    pub fallback: Option<FallbackInput>,
+    /// The directory in which local packages are looked up (`--package-path`)
+    pub package_path: Option<PathBuf>,
+    /// The directory in which downloaded packages are cached (`--package-cache-path`)
+    pub package_cache_path: Option<PathBuf>,
+    /// Additional directories that are recursively searched for fonts (`--font-path`)
+    pub font_paths: Vec<PathBuf>,
+    /// The document's creation time (`--creation-timestamp`), in seconds since the Unix epoch
+    pub creation_timestamp: Option<u64>,
+    /// How numbers in the query result are handled
+    pub number_mode: NumberMode,
+    /// The format of the query result
+    pub format: QueryFormat,
+    /// Environment variables set for the `typst query` process
+    pub env: BTreeMap<String, String>,
+    /// Whether the `typst query` process doesn't inherit prequery's environment
+    pub clear_env: bool,
+    /// Queries whose results are zipped into records instead of querying the selector, by the
+    /// name of the records' field
+    pub zip: BTreeMap<String, String>,
+    /// A JSON Schema file the query result must be valid against
+    pub schema: Option<PathBuf>,
+    /// How long the `typst query` process may run before it is killed
+    pub timeout: Option<Duration>,
+    /// How often a failed `typst query` process is run again
+    pub retries: u32,
+    /// The delay before the first retry; each further retry waits twice as long
+    pub retry_delay: Duration,
+    /// The minimum number of results the query must have
+    pub min_results: Option<usize>,
+}
+
+/// The format of a query result
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueryFormat {
+    /// A single JSON document, as output by `typst query`
+    #[default]
+    Json,
+    /// Newline-delimited JSON: each non-empty line is a separate element of the result, e.g. when
+    /// using a `--typst` wrapper that post-processes the result
+    Ndjson,
+    /// A YAML document, as output by `typst query --format yaml`
+    Yaml,
+}
+
+impl QueryFormat {
+    /// Parses a query result in this format.
+    pub fn parse(self, output: &[u8]) -> Result<Value> {
+        self.parse_reader(output)
+    }
+
+    /// Parses a query result in this format while reading it, so that large results don't need to
+    /// be buffered as a whole before parsing.
+    pub fn parse_reader<R: BufRead>(self, mut reader: R) -> Result<Value> {
+        match self {
+            Self::Json => Ok(serde_json::from_reader(reader)?),
+            Self::Ndjson => {
+                let mut values = Vec::new();
+                let mut line = Vec::new();
+                for index in 1.. {
+                    line.clear();
+                    if reader.read_until(b'\n', &mut line)? == 0 {
+                        break;
+                    }
+                    if line.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    let value = serde_json::from_slice(&line).map_err(|source| Error::Line {
+                        line: index,
+                        source,
+                    })?;
+                    values.push(value);
+                }
+                Ok(Value::Array(values))
+            }
+            Self::Yaml => Ok(serde_yaml::from_reader(reader)?),
+        }
+    }
+}
+
+/// How numbers in a query result are handled. Large integers or high-precision decimals may not be
+/// representable as 64 bit integers or floats.
+///
+/// Query results are parsed with serde_json's `arbitrary_precision` feature so that the exact
+/// modes can keep such numbers intact. Since features are unified across a build, this also
+/// affects other users of serde_json in this crate: [Number] keeps numbers in their textual form,
+/// so code handling numbers should go through its accessors (`as_i64`, `as_f64`, ...) instead of
+/// relying on how they are printed.
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberMode {
+    /// Numbers are parsed as 64 bit integers if possible, and as floats otherwise
+    #[default]
+    Default,
+    /// Numbers are kept exactly as they appear in the query result
+    ArbitraryPrecision,
+    /// Numbers are converted to strings, exactly as they appear in the query result
+    String,
+}
+
+impl NumberMode {
+    /// Converts all numbers in the given value according to this mode.
+    pub fn apply(self, value: Value) -> Value {
+        match value {
+            Value::Number(number) => match self {
+                // with `arbitrary_precision`, integers that don't fit 64 bits are neither i64 nor
+                // u64, but are still not converted to floats by serde_json
+                Self::Default if !number.is_i64() && !number.is_u64() => number
+                    .as_f64()
+                    .and_then(Number::from_f64)
+                    .map_or(Value::Null, Value::Number),
+                Self::Default | Self::ArbitraryPrecision => Value::Number(number),
+                Self::String => Value::String(number.to_string()),
+            },
+            Value::Array(array) => array.into_iter().map(|value| self.apply(value)).collect(),
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, self.apply(value)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
 }
 
 impl Query {
@@ -36,65 +238,500 @@ impl Query {
 
     /// Builds the `typst query` command line for executing this command.
     pub fn command(&self) -> Command {
+        self.command_for(&self.input)
+    }
+
+    /// Builds the `typst query` command line for executing this command against the given input
+    /// file instead of the one given on the command line. With several selectors, this is the
+    /// command line for the first one.
+    pub fn command_for(&self, input: &Path) -> Command {
+        let selector = self.selector.first().map_or("", String::as_str);
+        self.command_with(input, selector)
+    }
+
+    /// Builds the `typst query` command line for querying the given selector in the given input
+    /// file.
+    fn command_with(&self, input: &Path, selector: &str) -> Command {
         let mut cmd = Command::new(&ARGS.typst);
+        if self.clear_env {
+            cmd.env_clear();
+        }
+        cmd.envs(&self.env);
         cmd.arg("query");
-        if let Some(root) = &ARGS.root {
+        if let Some(root) = ARGS.typst_root(input) {
             cmd.arg("--root").arg(root);
         }
+        if let Some(package_path) = &self.package_path {
+            cmd.arg("--package-path").arg(package_path);
+        }
+        if let Some(package_cache_path) = &self.package_cache_path {
+            cmd.arg("--package-cache-path").arg(package_cache_path);
+        }
+        for font_path in &self.font_paths {
+            cmd.arg("--font-path").arg(font_path);
+        }
+        if let Some(timestamp) = self.creation_timestamp.or(ARGS.reproducible_time()) {
+            cmd.arg("--creation-timestamp").arg(timestamp.to_string());
+        }
         if let Some(field) = &self.field {
             cmd.arg("--field").arg(field);
         }
         if self.one {
             cmd.arg("--one");
         }
-        let mut input = String::new();
-        for (key, value) in &self.inputs {
-            input.clear();
-            write!(&mut input, "{key}={value}").expect("writing to a string failed");
-            cmd.arg("--input").arg(&input);
+        if self.format == QueryFormat::Yaml {
+            cmd.arg("--format").arg("yaml");
+        }
+        let mut input_arg = String::new();
+        // sorted, so that the command line is the same in every run
+        let inputs: BTreeMap<_, _> = self.inputs.iter().collect();
+        for (key, value) in inputs {
+            input_arg.clear();
+            write!(&mut input_arg, "{key}={value}").expect("writing to a string failed");
+            cmd.arg("--input").arg(&input_arg);
+        }
+        if let Some(FallbackInput { key, value }) = &self.fallback {
+            if !ARGS.no_default_fallback {
+                cmd.arg("--input").arg(format!("{key}={value}"));
+            }
         }
-        cmd.arg("--input").arg("prequery-fallback=true");
-        cmd.arg(&ARGS.input).arg(&self.selector);
+        cmd.arg(input).arg(selector);
 
         cmd
     }
 
+    /// Renders the `typst query` command line for executing this command, quoted for a POSIX
+    /// shell, e.g. for comparing it to a manual invocation. With several selectors, this is the
+    /// command line for the first one.
+    pub fn command_display(&self) -> String {
+        self.quote_command(&self.command())
+    }
+
+    /// Returns the `typst query` command line for executing this command, quoted for a POSIX
+    /// shell. With several selectors or when zipping, the command lines of all queries are
+    /// returned on separate lines.
+    pub fn shell_command(&self) -> String {
+        let selectors = if self.zip.is_empty() {
+            Either::Left(self.selector.iter())
+        } else {
+            Either::Right(self.zip.values())
+        };
+        selectors
+            .map(|selector| self.quote_command(&self.command_with(&self.input, selector)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Quotes the given command line for a POSIX shell.
+    fn quote_command(&self, command: &Command) -> String {
+        let clear_env = self.clear_env.then(|| "env -i".to_string());
+        let command = command.as_std();
+        let program = command.get_program();
+        let env = command.get_envs().filter_map(|(key, value)| {
+            let key = key.to_string_lossy();
+            let value = utils::shell_quote(&value?.to_string_lossy());
+            Some(format!("{key}={value}"))
+        });
+        let command = std::iter::once(program)
+            .chain(command.get_args())
+            .map(|arg| utils::shell_quote(&arg.to_string_lossy()));
+        clear_env
+            .into_iter()
+            .chain(env)
+            .chain(command)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// Executes the query. This builds the necessary command line, runs the command, and returns
-    /// the result parsed into the desired type from JSON.
+    /// the result parsed into the desired type from JSON. If the input is [STDIN], the document
+    /// read from stdin is queried instead, see [stdin_document].
     pub async fn query<T>(&self) -> Result<T>
     where
         T: for<'a> Deserialize<'a>,
     {
-        let mut command = self.command();
-        command.stderr(Stdio::inherit());
-        let output = command.output().await?;
-        if !output.status.success() {
-            let status = output.status;
-            Err(Error::Failure { command, status })?;
+        if self.input == Path::new(STDIN) {
+            let input = stdin_document().await?;
+            return self.execute(input).await;
+        }
+        self.execute(&self.input).await
+    }
+
+    /// Executes the query and returns the result without converting it to a specific type, e.g.
+    /// to inspect it before deciding how to parse it. The result is still validated against the
+    /// `schema`, and numbers are handled according to the `number_mode`.
+    pub async fn query_value(&self) -> Result<Value> {
+        self.query().await
+    }
+
+    /// Executes the query against a document given as source code instead of a file. The source
+    /// is written to a temporary file in the project root, so that it can import other files of
+    /// the project; the file is deleted afterwards. This is mainly useful for testing, and for
+    /// tools that generate documents on the fly.
+    pub async fn query_source<T>(&self, source: &str) -> Result<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let file_name = format!(".prequery-source-{}-{id}.typ", std::process::id());
+        let input = ARGS.resolve_root().join(file_name);
+        fs::write(&input, source).await?;
+        let result = self.execute(&input).await;
+        // the query result is more relevant than a failure to clean up
+        let _ = fs::remove_file(&input).await;
+        result
+    }
+
+    /// Executes the query against the given input file and converts the result.
+    async fn execute<T>(&self, input: &Path) -> Result<T>
+    where
+        T: for<'a> Deserialize<'a>,
+    {
+        let value = if self.zip.is_empty() {
+            self.run_selectors(input).await?
+        } else {
+            self.run_zipped(input).await?
+        };
+        // with `one`, the result is a single element, even if that is an array
+        if let (Value::Array(results), false) = (&value, self.one) {
+            let found = results.len();
+            status!("[{}] Query found {found} results", self.job);
+            if let Some(min_results) = self.min_results.filter(|&min| found < min) {
+                return Err(Error::MinResults { found, min_results });
+            }
         }
+        if let Some(schema) = &self.schema {
+            validate(schema, &value).await?;
+        }
+        let value = T::deserialize(self.number_mode.apply(value))?;
+        Ok(value)
+    }
+
+    /// Executes `typst query` for the given selector and parses its output. If another job
+    /// already ran or is running the same query, its result is used instead; with
+    /// `--query-cache`, so are results of earlier runs.
+    async fn run(&self, input: &Path, selector: &str) -> Result<Value> {
+        let command = self.quote_command(&self.command_with(input, selector));
+        let key = format!("{:?} {command}", self.format);
+        let cell = RESULTS
+            .lock()
+            .expect("the query results should not be poisoned")
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let value = cell
+            .get_or_try_init(|| async {
+                // only the queried document is cached on disk, not e.g. temporary sources
+                let cached = input == self.input;
+                if cached {
+                    if let Some(value) = cache::get(&key, input).await {
+                        status!("[{}] Using cached result of query of {selector}", self.job);
+                        return Ok(value);
+                    }
+                }
+                if ARGS.dry_run.is_some() {
+                    status!("[{}] Querying: {command}", self.job);
+                }
+                let value = self.run_with_retries(input, selector).await?;
+                if cached {
+                    cache::insert(&key, input, &value).await;
+                }
+                Ok::<_, Error>(value)
+            })
+            .await?;
+        self.select_field_path(value.clone())
+    }
 
-        let value = serde_json::from_slice(&output.stdout)?;
+    /// Executes `typst query` for the given selector and parses its output. If the process fails,
+    /// it is run again as often as configured, waiting longer before each retry.
+    #[tracing::instrument(name = "query", skip(self, input))]
+    async fn run_with_retries(&self, input: &Path, selector: &str) -> Result<Value> {
+        let mut retry = 0;
+        loop {
+            match self.run_once(input, selector).await {
+                Err(error) if retry < self.retries && error.is_retryable() => {
+                    retry += 1;
+                    let delay = self
+                        .retry_delay
+                        .saturating_mul(2u32.saturating_pow(retry - 1));
+                    let attempt = format!("attempt {} of {}", retry + 1, self.retries + 1);
+                    let job = &self.job;
+                    status!("[{job}] Query of {selector} failed: {error}");
+                    status!("[{job}] Retrying query of {selector} in {delay:?} ({attempt})...");
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Executes `typst query` for the given selector once and parses its output.
+    async fn run_once(&self, input: &Path, selector: &str) -> Result<Value> {
+        let mut command = self.command_with(input, selector);
+        // the diagnostics are reported with the error, instead of being interleaved with other
+        // jobs' output
+        command.stderr(Stdio::piped());
+        // when the timeout expires, dropping the child kills it; tokio then reaps it
+        command.kill_on_drop(true);
+        let _permit = PROCESSES
+            .acquire()
+            .await
+            .expect("the query semaphore should not be closed");
+        let start = Instant::now();
+        let mut child = command.stdout(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let mut stderr = child.stderr.take().expect("stderr should be piped");
+        // the result is parsed while it is read, instead of buffering the whole output first
+        let format = self.format;
+        let parse = tokio::task::spawn_blocking(move || {
+            let mut reader = io::BufReader::new(SyncIoBridge::new(stdout));
+            let value = format.parse_reader(&mut reader);
+            // if parsing stopped early, the rest is discarded so that the process can exit
+            let _ = io::copy(&mut reader, &mut io::sink());
+            value
+        });
+        let output = async {
+            let mut output = Vec::new();
+            let (status, _) = tokio::try_join!(child.wait(), stderr.read_to_end(&mut output))?;
+            let value = parse
+                .await
+                .expect("parsing the query result should not panic");
+            Ok::<_, io::Error>((status, output, value))
+        };
+        let (status, stderr, value) = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, output).await {
+                Ok(output) => output?,
+                Err(_) => {
+                    let elapsed = start.elapsed();
+                    let command = Box::new(command);
+                    return Err(Error::Timeout { command, elapsed });
+                }
+            },
+            None => output.await?,
+        };
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr).into_owned();
+            Err(Error::Failure {
+                command: Box::new(command),
+                status,
+                stderr,
+            })?;
+        }
+        // warnings of successful queries are still shown, attributed to the job
+        for line in String::from_utf8_lossy(&stderr).lines() {
+            eprintln!("[{}] {line}", self.job);
+        }
+
+        let mut value = value?;
+        if self.field.is_none() {
+            value = self.unwrap_metadata(value);
+        }
         Ok(value)
     }
+
+    /// Executes the query for each selector against the given input file. A single selector's
+    /// result is returned as is; the results of several selectors are concatenated in order.
+    async fn run_selectors(&self, input: &Path) -> Result<Value> {
+        if let [selector] = self.selector.as_slice() {
+            return self.run(input, selector).await;
+        }
+        let mut results = Vec::new();
+        for selector in &self.selector {
+            let Value::Array(values) = self.run(input, selector).await? else {
+                return Err(Error::SelectorShape(selector.clone()));
+            };
+            results.extend(values);
+        }
+        Ok(Value::Array(results))
+    }
+
+    /// Executes the zipped queries against the given input file, and combines their results
+    /// positionally into records: the n-th record contains the n-th result of each query, under
+    /// that query's name.
+    async fn run_zipped(&self, input: &Path) -> Result<Value> {
+        let mut records: Option<Vec<serde_json::Map<String, Value>>> = None;
+        let mut first = "";
+        for (name, selector) in &self.zip {
+            let Value::Array(values) = self.run(input, selector).await? else {
+                return Err(Error::ZipShape(name.clone()));
+            };
+            let records = records.get_or_insert_with(|| {
+                first = name;
+                vec![serde_json::Map::new(); values.len()]
+            });
+            if records.len() != values.len() {
+                return Err(Error::ZipLength {
+                    name: name.clone(),
+                    length: values.len(),
+                    expected_name: first.to_string(),
+                    expected_length: records.len(),
+                });
+            }
+            for (record, value) in records.iter_mut().zip(values) {
+                record.insert(name.clone(), value);
+            }
+        }
+        let records = records.unwrap_or_default();
+        Ok(records.into_iter().map(Value::Object).collect())
+    }
+
+    /// Selects the nested fields of [Query::field_path] from each result, or from the single
+    /// result with `one`.
+    fn select_field_path(&self, value: Value) -> Result<Value> {
+        if self.field_path.is_empty() {
+            return Ok(value);
+        }
+        let select = |mut element: Value| {
+            for segment in &self.field_path {
+                let error = |missing| {
+                    let field = self.field.as_deref().unwrap_or_default();
+                    let path = [field]
+                        .into_iter()
+                        .chain(self.field_path.iter().map(String::as_str));
+                    Error::FieldPath {
+                        path: path.collect::<Vec<_>>().join("."),
+                        segment: segment.clone(),
+                        missing,
+                    }
+                };
+                let Value::Object(mut object) = element else {
+                    return Err(error(false));
+                };
+                element = object.remove(segment).ok_or_else(|| error(true))?;
+            }
+            Ok(element)
+        };
+        match value {
+            value if self.one => select(value),
+            Value::Array(elements) => elements.into_iter().map(select).collect(),
+            value => Ok(value),
+        }
+    }
+
+    /// Without a `field`, the query returns whole elements; for `metadata` elements, these are
+    /// envelopes like `{ "func": "metadata", "value": ..., "label": ... }`. This replaces such
+    /// envelopes by their values, so that the result has the same shape as when querying the
+    /// `value` field. Other elements are kept unchanged.
+    fn unwrap_metadata(&self, value: Value) -> Value {
+        fn unwrap(element: Value) -> Value {
+            match element {
+                Value::Object(mut envelope)
+                    if envelope.get("func").and_then(Value::as_str) == Some("metadata")
+                        && envelope.contains_key("value") =>
+                {
+                    envelope
+                        .remove("value")
+                        .expect("the value should be present")
+                }
+                element => element,
+            }
+        }
+
+        match value {
+            value if self.one => unwrap(value),
+            Value::Array(elements) => Value::Array(elements.into_iter().map(unwrap).collect()),
+            value => value,
+        }
+    }
+}
+
+/// The input path that stands for a document read from stdin
+pub const STDIN: &str = "-";
+
+/// The file the document read from stdin was written to, see [stdin_document]
+static STDIN_DOCUMENT: OnceCell<PathBuf> = OnceCell::const_new();
+
+/// Reads the document from stdin and writes it to a file in the project root, so that it can
+/// import other files of the project. Stdin can only be read once, so this happens on first use,
+/// and all queries of the run share the same file; it is removed by [remove_stdin_document].
+async fn stdin_document() -> Result<&'static Path> {
+    let input = STDIN_DOCUMENT
+        .get_or_try_init(|| async {
+            let mut source = String::new();
+            tokio::io::stdin()
+                .read_to_string(&mut source)
+                .await
+                .map_err(Error::Stdin)?;
+            let file_name = format!(".prequery-source-{}-stdin.typ", std::process::id());
+            let input = ARGS.resolve_root().join(file_name);
+            fs::write(&input, source).await?;
+            Ok::<_, Error>(input)
+        })
+        .await?;
+    Ok(input)
+}
+
+/// Removes the file the document read from stdin was written to, if any.
+pub async fn remove_stdin_document() {
+    if let Some(input) = STDIN_DOCUMENT.get() {
+        // the run's results are more relevant than a failure to clean up
+        let _ = fs::remove_file(input).await;
+    }
+}
+
+/// Validates the query result against the JSON Schema in the given file.
+async fn validate(schema_path: &Path, value: &Value) -> Result<()> {
+    let path = schema_path.to_path_buf();
+    let schema = fs::read_to_string(schema_path)
+        .await
+        .map_err(|source| Error::SchemaIo {
+            path: path.clone(),
+            source,
+        })?;
+    let schema: Value = serde_json::from_str(&schema).map_err(|source| Error::SchemaJson {
+        path: path.clone(),
+        source,
+    })?;
+    let schema =
+        jsonschema::JSONSchema::compile(&schema).map_err(|error| Error::SchemaInvalid {
+            path: path.clone(),
+            message: error.to_string(),
+        })?;
+    if let Err(errors) = schema.validate(value) {
+        let errors = errors
+            .map(|error| {
+                // the root of the query result is an empty JSON pointer
+                let pointer = error.instance_path.to_string();
+                let pointer = if pointer.is_empty() { "/" } else { &pointer };
+                format!("{pointer}: {error}")
+            })
+            .collect();
+        return Err(Error::Validation(ValidationErrors::new(errors)));
+    }
+    Ok(())
 }
 
 /// A query builder. Default values for the various configs can be set. If a setting is missing from
 /// the [config::Query], that default will be used.
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct QueryBuilder {
-    /// The selector to be queried, e.g. `<label>`
-    pub selector: Option<String>,
+    /// The selectors to be queried, e.g. `<label>`
+    pub selector: Option<Vec<String>>,
     /// The field (`--field`) to be queried from the selector (with metadata elements, this is
     /// usually `value`)
     pub field: Option<Option<String>>,
     /// Whether only one (`--one`) query result is expected and should be returned
     pub one: Option<bool>,
+    /// The only value of `one` the preprocessor supports, if it doesn't support both
+    pub supported_one: Option<bool>,
+    /// Inputs (`--input`) given to the queried document unless the config sets the same key
+    pub inputs: HashMap<String, String>,
+    /// The name of the job running the query, used in status messages
+    pub job: Option<String>,
 }
 
 impl QueryBuilder {
+    /// Set the name of the job running the query, used in status messages
+    pub fn job(mut self, name: &str) -> Self {
+        self.job = Some(name.to_string());
+        self
+    }
+
     /// Set the selector to be queried, e.g. `<label>`
     pub fn default_selector(mut self, selector: String) -> Self {
-        self.selector = Some(selector);
+        self.selector = Some(vec![selector]);
         self
     }
 
@@ -111,31 +748,129 @@ impl QueryBuilder {
         self
     }
 
+    /// Set inputs (`--input`) to be given to the queried document. Inputs in the config take
+    /// precedence over these if they have the same key, and these over inputs given on the
+    /// command line.
+    pub fn default_inputs(mut self, inputs: HashMap<String, String>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// Set the only supported value of `one` (`--one`), which is also used as the default. A
+    /// configuration with a different value is rejected.
+    pub fn fixed_one(mut self, one: bool) -> Self {
+        self.one = Some(one);
+        self.supported_one = Some(one);
+        self
+    }
+
     /// build a [Query] using the given defaults. If the [config::Query] doesn't contain a field
     /// that also doesn't have a default value, this will fail.
     pub fn build(self, config: manifest::Query) -> Result<Query, QueryBuilderError> {
+        let input = match config.input {
+            Some(input) => ARGS
+                .resolve(&input)
+                .ok_or(QueryBuilderError::Input(input))?,
+            None => ARGS.input.clone(),
+        };
+        let zip = config.zip;
+        // when zipping, the selector is not queried
         let selector = config
             .selector
             .or(self.selector)
+            .or_else(|| (!zip.is_empty()).then(Vec::new))
+            .filter(|selector| !selector.is_empty() || !zip.is_empty())
             .ok_or(QueryBuilderError::Selector)?;
         let field = config
             .field
             .or(self.field)
             .ok_or(QueryBuilderError::Field)?;
+        // only the leading segment of a dotted field is passed to `typst query`
+        let (field, field_path) = match field {
+            Some(field) => {
+                let mut segments = field.split('.').map(str::to_string);
+                let field = segments.next().expect("split returns at least one segment");
+                (Some(field), segments.collect())
+            }
+            None => (None, Vec::new()),
+        };
         let one = config.one.or(self.one).ok_or(QueryBuilderError::One)?;
-        let inputs = config.inputs;
+        if self.supported_one.is_some_and(|supported| supported != one) {
+            return Err(QueryBuilderError::UnsupportedOne(one));
+        }
+        if one && !zip.is_empty() {
+            return Err(QueryBuilderError::ZipOne);
+        }
+        if one && selector.len() > 1 {
+            return Err(QueryBuilderError::SelectorsOne);
+        }
+        let min_results = config.min_results;
+        if one && min_results.is_some() {
+            return Err(QueryBuilderError::MinResultsOne);
+        }
+        // inputs from the command line have the lowest precedence
+        let mut inputs: HashMap<_, _> = ARGS.inputs.iter().cloned().collect();
+        inputs.extend(self.inputs);
+        inputs.extend(config.inputs.values);
+        let fallback = config
+            .fallback
+            .unwrap_or_else(|| Some(FallbackInput::default()));
+        if let Some(FallbackInput { key, .. }) = &fallback {
+            if !ARGS.no_default_fallback && inputs.contains_key(key) {
+                eprintln!("warning: the input `{key}` is overridden by the fallback input");
+            }
+        }
+        let package_path = config.package_path.or_else(|| ARGS.package_path.clone());
+        let package_cache_path = config
+            .package_cache_path
+            .or_else(|| ARGS.package_cache_path.clone());
+        let mut font_paths = config.font_paths;
+        font_paths.extend(ARGS.font_paths.iter().cloned());
+        let creation_timestamp = config.creation_timestamp;
+        let number_mode = config.number_mode;
+        let format = config.format.unwrap_or_default();
+        let env = config.env;
+        let clear_env = config.clear_env;
+        let schema = config.schema;
+        let timeout = config.timeout.map(Duration::from_secs);
+        let retries = config.retries;
+        let retry_delay = config
+            .retry_delay
+            .map_or(DEFAULT_RETRY_DELAY, Duration::from_millis);
+        let job = self.job.unwrap_or_default();
         Ok(Query {
+            job,
+            input,
             selector,
             field,
+            field_path,
             one,
             inputs,
+            fallback,
+            package_path,
+            package_cache_path,
+            font_paths,
+            creation_timestamp,
+            number_mode,
+            format,
+            env,
+            clear_env,
+            zip,
+            schema,
+            timeout,
+            retries,
+            retry_delay,
+            min_results,
         })
     }
 }
 
 mod error {
+    use std::fmt;
     use std::io;
+    use std::path::PathBuf;
     use std::process::ExitStatus;
+    use std::time::Duration;
 
     use thiserror::Error;
     use tokio::process::Command;
@@ -143,20 +878,189 @@ mod error {
     /// Error while executing the query
     #[derive(Error, Debug)]
     pub enum Error {
+        /// The schema file could not be read
+        #[error("the schema file {} could not be read", path.display())]
+        SchemaIo {
+            /// The schema file
+            path: PathBuf,
+            /// The I/O error
+            #[source]
+            source: io::Error,
+        },
+        /// The schema file is not valid JSON
+        #[error("the schema file {} is not valid JSON", path.display())]
+        SchemaJson {
+            /// The schema file
+            path: PathBuf,
+            /// The parsing error
+            #[source]
+            source: serde_json::Error,
+        },
+        /// The schema file is not a valid JSON Schema
+        #[error("the schema file {} is not a valid schema: {message}", path.display())]
+        SchemaInvalid {
+            /// The schema file
+            path: PathBuf,
+            /// The reason the schema is invalid
+            message: String,
+        },
+        /// The query result does not match the schema
+        #[error(transparent)]
+        Validation(ValidationErrors),
+        /// The result of one of several selectors is not an array
+        #[error("the result of the query of `{0}` is not an array")]
+        SelectorShape(String),
+        /// The result of a zipped query is not an array
+        #[error("the result of the zipped query `{0}` is not an array")]
+        ZipShape(String),
+        /// The results of zipped queries have different lengths
+        #[error(
+            "the zipped query `{name}` has {length} results, \
+            but `{expected_name}` has {expected_length}"
+        )]
+        ZipLength {
+            /// The query with a different number of results
+            name: String,
+            /// The query's number of results
+            length: usize,
+            /// The first zipped query
+            expected_name: String,
+            /// The first query's number of results
+            expected_length: usize,
+        },
+        /// A nested field of a dotted `field` could not be selected from a result
+        #[error(
+            "the field `{segment}` of `{path}` {}",
+            if *missing { "is missing" } else { "can't be selected from a non-object value" }
+        )]
+        FieldPath {
+            /// The whole dotted field
+            path: String,
+            /// The segment that could not be selected
+            segment: String,
+            /// Whether the segment was missing, instead of its parent not being an object
+            missing: bool,
+        },
+        /// The query found fewer results than required by `min_results`
+        #[error(
+            "the query found {found} results, but at least {min_results} were expected; \
+            check that the selector matches the document"
+        )]
+        MinResults {
+            /// The number of results found
+            found: usize,
+            /// The required number of results
+            min_results: usize,
+        },
         /// Reading command output failed
         #[error("reading from the `typst query` child process failed")]
         Io(#[from] io::Error),
+        /// Reading the document from stdin failed
+        #[error("the document could not be read from stdin")]
+        Stdin(#[source] io::Error),
         /// The subprocess failed
-        #[error("query command failed: {status}\n\n\t{command:?}")]
+        #[error("query command failed: {status}\n\n\t{command:?}{}", indent(stderr))]
         Failure {
             /// The command that was executed
-            command: Command,
+            command: Box<Command>,
             /// The status code with which the command failed
             status: ExitStatus,
+            /// The diagnostics the command printed to stderr
+            stderr: String,
+        },
+        /// The subprocess didn't finish in time and was killed
+        #[error("query command timed out after {elapsed:.1?}\n\n\t{command:?}")]
+        Timeout {
+            /// The command that was executed
+            command: Box<Command>,
+            /// How long the command ran before it was killed
+            elapsed: Duration,
         },
         /// The response to the query was not valid
         #[error("query response was not valid JSON or did not fit the expected schema")]
         Json(#[from] serde_json::Error),
+        /// The YAML response to the query was not valid
+        #[error("query response was not valid YAML or did not fit the expected schema")]
+        Yaml(#[from] serde_yaml::Error),
+        /// The output of `typst --version` could not be parsed
+        #[error("the Typst version could not be read from `{0}`")]
+        Version(String),
+        /// A line of a newline-delimited response was not valid
+        #[error("line {line} of the query response was not valid JSON")]
+        Line {
+            /// The line number, starting at 1
+            line: usize,
+            /// The parsing error
+            #[source]
+            source: serde_json::Error,
+        },
+    }
+
+    /// Indents the lines of a failed command's diagnostics, so that they appear under the command.
+    fn indent(stderr: &str) -> String {
+        let mut result = String::new();
+        for line in stderr.trim_end().lines() {
+            result.push_str("\n\t");
+            result.push_str(line);
+        }
+        if !result.is_empty() {
+            result.insert(0, '\n');
+        }
+        result
+    }
+
+    impl Error {
+        /// Checks whether running the query again could succeed. Only failures of the process are
+        /// considered transient; invalid responses and schema violations would occur again.
+        pub fn is_retryable(&self) -> bool {
+            matches!(
+                self,
+                Self::Io(_) | Self::Failure { .. } | Self::Timeout { .. }
+            )
+        }
+
+        /// A short identifier of the kind of error, e.g. for machine-readable output
+        pub fn kind(&self) -> &'static str {
+            match self {
+                Self::Io(_) | Self::Stdin(_) => "query-io",
+                Self::Failure { .. } => "query-failure",
+                Self::Timeout { .. } => "query-timeout",
+                Self::Json(_) | Self::Yaml(_) | Self::Line { .. } => "query-response",
+                Self::SchemaIo { .. } | Self::SchemaJson { .. } | Self::SchemaInvalid { .. } => {
+                    "schema"
+                }
+                Self::Validation(_) => "validation",
+                Self::SelectorShape(_) => "selector",
+                Self::ZipShape(_) | Self::ZipLength { .. } => "zip",
+                Self::Version(_) => "typst-version",
+                Self::MinResults { .. } => "min-results",
+                Self::FieldPath { .. } => "field",
+            }
+        }
+    }
+
+    /// The errors of validating a query result against a schema
+    #[derive(Error, Debug)]
+    pub struct ValidationErrors {
+        errors: Vec<String>,
+    }
+
+    impl ValidationErrors {
+        /// Creates a new error from the individual validation errors
+        pub fn new(errors: Vec<String>) -> Self {
+            Self { errors }
+        }
+    }
+
+    impl fmt::Display for ValidationErrors {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "the query result does not match the schema:")?;
+            for error in &self.errors {
+                writeln!(f)?;
+                write!(f, "  {error}")?;
+            }
+            Ok(())
+        }
     }
 
     /// Error in the query builder: a required ocnfiguration is missing
@@ -171,6 +1075,21 @@ mod error {
         /// `one` is missing
         #[error("`one` was not specified but is required")]
         One,
+        /// `one` has a value that the preprocessor doesn't support
+        #[error("`one = {0}` is not supported by this preprocessor")]
+        UnsupportedOne(bool),
+        /// `one` and `zip` were both given
+        #[error("`one = true` can't be combined with `zip`")]
+        ZipOne,
+        /// `one` and multiple selectors were both given
+        #[error("`one = true` can't be combined with multiple selectors")]
+        SelectorsOne,
+        /// `one` and `min_results` were both given
+        #[error("`one = true` can't be combined with `min_results`")]
+        MinResultsOne,
+        /// `input` is outside the project root
+        #[error("the input `{}` is outside the project root", .0.display())]
+        Input(PathBuf),
     }
 
     /// Result type alias that defaults error to [Error].
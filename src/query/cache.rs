@@ -0,0 +1,233 @@
+//! A cache of query results on disk (`--query-cache`), so that queries don't need to be run again
+//! in later runs while the document is unchanged.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::sync::Mutex;
+use typst_syntax::ast;
+use typst_syntax::SyntaxNode;
+
+use crate::args::{QueryCacheMode, ARGS};
+
+/// The name of the cache file in the project root
+const FILE_NAME: &str = ".prequery-query-cache.json";
+
+/// Functions whose first argument is the path of a file the document depends on
+const LOADING_FUNCTIONS: &[&str] = &[
+    "read", "json", "yaml", "toml", "csv", "xml", "cbor", "image", "plugin",
+];
+
+/// A query result, together with the fingerprint of the document it was queried from
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry {
+    fingerprint: String,
+    result: Value,
+}
+
+/// The cache's state during a run
+#[derive(Default, Debug)]
+struct Cache {
+    /// The cached results by the hash of their query's command line; read on first use
+    entries: Option<BTreeMap<String, Entry>>,
+    /// The fingerprints of the documents queried in the current run
+    fingerprints: HashMap<PathBuf, String>,
+}
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(Default::default);
+
+/// Forgets the fingerprints computed so far, as the document may have changed since.
+pub(crate) async fn reset() {
+    CACHE.lock().await.fingerprints.clear();
+}
+
+/// Returns the cached result of the query with the given key against the given document, if the
+/// document hasn't changed since it was cached.
+pub(crate) async fn get(key: &str, input: &Path) -> Option<Value> {
+    if ARGS.query_cache == QueryCacheMode::Off || ARGS.no_query_cache {
+        return None;
+    }
+    let mut cache = CACHE.lock().await;
+    let fingerprint = cache.fingerprint(input).await;
+    let entries = cache.entries().await;
+    let entry = entries.get(&hash_key(key))?;
+    (entry.fingerprint == fingerprint).then(|| entry.result.clone())
+}
+
+/// Caches the result of the query with the given key against the given document. Failing to
+/// write the cache is not an error; the query is simply run again next time.
+pub(crate) async fn insert(key: &str, input: &Path, result: &Value) {
+    if ARGS.query_cache == QueryCacheMode::Off {
+        return;
+    }
+    let mut cache = CACHE.lock().await;
+    let fingerprint = cache.fingerprint(input).await;
+    let entries = cache.entries().await;
+    let result = result.clone();
+    entries.insert(
+        hash_key(key),
+        Entry {
+            fingerprint,
+            result,
+        },
+    );
+    let location = ARGS.resolve_root().join(FILE_NAME);
+    let written = match serde_json::to_vec(entries) {
+        Ok(content) => fs::write(&location, content)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(error) => Err(error.to_string()),
+    };
+    if let Err(error) = written {
+        eprintln!("warning: writing the query cache failed: {error}");
+    }
+}
+
+impl Cache {
+    async fn entries(&mut self) -> &mut BTreeMap<String, Entry> {
+        if self.entries.is_none() {
+            let location = ARGS.resolve_root().join(FILE_NAME);
+            // a missing or unreadable cache is treated as empty
+            let entries = fs::read(&location)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_slice(&content).ok())
+                .unwrap_or_default();
+            self.entries = Some(entries);
+        }
+        self.entries.as_mut().expect("the entries were read")
+    }
+
+    async fn fingerprint(&mut self, input: &Path) -> String {
+        if let Some(fingerprint) = self.fingerprints.get(input) {
+            return fingerprint.clone();
+        }
+        let fingerprint = match ARGS.query_cache {
+            QueryCacheMode::Off | QueryCacheMode::Imports => imports_fingerprint(input).await,
+            QueryCacheMode::Directory => directory_fingerprint().await,
+        };
+        self.fingerprints
+            .insert(input.to_path_buf(), fingerprint.clone());
+        fingerprint
+    }
+}
+
+/// The cache key is hashed, as the command line may contain e.g. secrets in environment variables.
+fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key))
+}
+
+/// Hashes the contents of the document and all files it depends on according to its source.
+async fn imports_fingerprint(input: &Path) -> String {
+    let mut hasher = Sha256::new();
+    for file in dependencies(input).await {
+        hasher.update(file.to_string_lossy().as_bytes());
+        match fs::read(&file).await {
+            Ok(content) => {
+                hasher.update(b"\0");
+                hasher.update(Sha256::digest(content));
+            }
+            Err(_) => hasher.update(b"\0missing"),
+        }
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Finds the files the document depends on by parsing it and all Typst files it imports or
+/// includes. Only paths given as string literals are found; package imports are ignored, as
+/// packages are versioned.
+async fn dependencies(input: &Path) -> BTreeSet<PathBuf> {
+    let root = ARGS.resolve_root();
+    let mut files = BTreeSet::new();
+    let mut pending = vec![input.to_path_buf()];
+    while let Some(file) = pending.pop() {
+        if !files.insert(file.clone()) {
+            continue;
+        }
+        if file.extension().is_some_and(|ext| ext != "typ") {
+            continue;
+        }
+        let Ok(source) = fs::read_to_string(&file).await else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new("."));
+        let mut paths = Vec::new();
+        collect_paths(&typst_syntax::parse(&source), &mut paths);
+        for path in paths {
+            if path.starts_with('@') {
+                continue;
+            }
+            let path = match path.strip_prefix('/') {
+                Some(path) => root.join(path),
+                None => dir.join(path),
+            };
+            pending.push(path);
+        }
+    }
+    files
+}
+
+/// Collects the string literal paths of imports, includes and data loading calls in the syntax
+/// tree.
+fn collect_paths(node: &SyntaxNode, paths: &mut Vec<String>) {
+    let source = if let Some(import) = node.cast::<ast::ModuleImport>() {
+        Some(import.source())
+    } else if let Some(include) = node.cast::<ast::ModuleInclude>() {
+        Some(include.source())
+    } else if let Some(call) = node.cast::<ast::FuncCall>() {
+        match call.callee() {
+            ast::Expr::Ident(ident) if LOADING_FUNCTIONS.contains(&ident.as_str()) => {
+                call.args().items().find_map(|arg| match arg {
+                    ast::Arg::Pos(expr) => Some(expr),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    } else {
+        None
+    };
+    if let Some(ast::Expr::Str(path)) = source {
+        paths.push(path.get().to_string());
+    }
+    for child in node.children() {
+        collect_paths(child, paths);
+    }
+}
+
+/// Hashes the paths, sizes and modification times of all files in the project root. Hidden files
+/// and directories, such as the cache itself, are skipped.
+async fn directory_fingerprint() -> String {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![ARGS.resolve_root().to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                let modified = metadata.modified().ok();
+                files.insert(entry.path(), (metadata.len(), modified));
+            }
+        }
+    }
+    let mut hasher = Sha256::new();
+    for (path, (len, modified)) in files {
+        hasher.update(format!("{}\0{len}\0{modified:?}\n", path.display()));
+    }
+    format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,312 @@
+//! Reports on the results of running jobs
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::fs;
+
+use crate::args::ReportFormat;
+use crate::preprocessor;
+use crate::query;
+use crate::web_resource;
+
+pub use error::*;
+
+/// A report on the results of all jobs in a run
+#[derive(Serialize, Default, Debug, Clone, PartialEq)]
+pub struct RunReport {
+    /// The reports of the individual jobs
+    pub jobs: Vec<JobReport>,
+}
+
+/// A report on the result of a single job
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct JobReport {
+    /// The job's name
+    pub name: String,
+    /// Whether the job succeeded
+    pub status: JobStatus,
+    /// How long the job took to run, in seconds
+    pub duration: f64,
+    /// Counts of the items the job processed
+    pub counts: Counts,
+    /// The concurrency chosen for each host by adaptive tuning
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub host_concurrency: BTreeMap<String, usize>,
+    /// The error that made the job fail, including all its causes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The final status of a job
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobStatus {
+    /// The job finished successfully
+    Success,
+    /// The job returned an error
+    Failed,
+    /// The job exceeded its `job_timeout` and was cancelled
+    TimedOut,
+    /// The job panicked or was aborted
+    Panicked,
+}
+
+/// Counts of the items (e.g. resources) a job processed
+#[derive(Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    /// Items that were processed, e.g. files that were downloaded
+    pub processed: usize,
+    /// Items that did not need processing, e.g. files that already existed
+    pub skipped: usize,
+    /// Items whose processing failed
+    pub failed: usize,
+}
+
+/// Counters for building [Counts] while a job is running, possibly concurrently
+#[derive(Default, Debug)]
+pub struct AtomicCounts {
+    processed: AtomicUsize,
+    skipped: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl AtomicCounts {
+    /// Counts an item that was processed
+    pub fn processed(&self) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts an item that did not need processing
+    pub fn skipped(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts an item whose processing failed
+    pub fn failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sets all counters back to zero, e.g. before running a job again
+    pub fn reset(&self) {
+        self.processed.store(0, Ordering::Relaxed);
+        self.skipped.store(0, Ordering::Relaxed);
+        self.failed.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the current counts
+    pub fn get(&self) -> Counts {
+        Counts {
+            processed: self.processed.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl RunReport {
+    /// Renders the report in the given format.
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        let report = match format {
+            ReportFormat::Json => serde_json::to_string_pretty(self)?,
+            ReportFormat::Toml => toml::to_string(self)?,
+            ReportFormat::Markdown => self.to_markdown(),
+        };
+        Ok(report)
+    }
+
+    /// Renders the report as a Markdown table, e.g. for a GitHub step summary.
+    pub fn to_markdown(&self) -> String {
+        // table cells can't contain pipes or line breaks
+        let escape = |cell: &str| cell.replace('|', "\\|").replace('\n', " ");
+
+        let mut table = String::new();
+        let mut line = |line: String| {
+            table.push_str(&line);
+            table.push('\n');
+        };
+        line("| Job | Status | Duration | Processed | Skipped | Failed | Error |".to_string());
+        line("|---|---|---:|---:|---:|---:|---|".to_string());
+        for job in &self.jobs {
+            let JobReport {
+                name,
+                status,
+                duration,
+                counts,
+                error,
+                ..
+            } = job;
+            let status = match status {
+                JobStatus::Success => "success",
+                JobStatus::Failed => "failed",
+                JobStatus::TimedOut => "timed out",
+                JobStatus::Panicked => "panicked",
+            };
+            let Counts {
+                processed,
+                skipped,
+                failed,
+            } = counts;
+            let name = escape(name);
+            let error = escape(error.as_deref().unwrap_or_default());
+            line(format!(
+                "| {name} | {status} | {duration:.2}s | {processed} | {skipped} | {failed} | {error} |"
+            ));
+        }
+        table
+    }
+
+    /// Writes the report to the given file in the given format.
+    pub async fn write<P: AsRef<Path>>(&self, path: P, format: ReportFormat) -> Result<()> {
+        let report = self.render(format)?;
+        fs::write(path, report).await?;
+        Ok(())
+    }
+}
+
+impl JobReport {
+    /// Creates the report for a job that finished, successfully or not
+    pub fn new(
+        name: String,
+        duration: Duration,
+        counts: Counts,
+        error: Option<&(dyn StdError + 'static)>,
+    ) -> Self {
+        let status = match error {
+            Some(error) if is_job_timeout(error) => JobStatus::TimedOut,
+            Some(_) => JobStatus::Failed,
+            None => JobStatus::Success,
+        };
+        let error = error.map(error_chain);
+        let duration = duration.as_secs_f64();
+        Self {
+            name,
+            status,
+            duration,
+            counts,
+            host_concurrency: BTreeMap::new(),
+            error,
+        }
+    }
+
+    /// Creates the report for a job that panicked
+    pub fn panicked(name: String, duration: Duration, error: &(dyn StdError + 'static)) -> Self {
+        Self {
+            status: JobStatus::Panicked,
+            ..Self::new(name, duration, Counts::default(), Some(error))
+        }
+    }
+}
+
+/// A single error, as written to the `--errors` file
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorRecord {
+    /// The name of the job the error occurred in, if it is specific to a job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    /// The resource whose download failed, if the error is specific to a resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<PathBuf>,
+    /// A short identifier of the kind of error, e.g. `network` or `query-failure`
+    pub kind: String,
+    /// The error message, including all its causes
+    pub message: String,
+}
+
+impl ErrorRecord {
+    /// Creates a record of an error of the given kind.
+    pub fn new(job: Option<&str>, kind: &str, error: &(dyn StdError + 'static)) -> Self {
+        Self {
+            job: job.map(str::to_string),
+            resource: None,
+            kind: kind.to_string(),
+            message: error_chain(error),
+        }
+    }
+
+    /// Creates the records for a failed job. Failed downloads of a web-resource job are recorded
+    /// individually; for other errors, the kind is determined from the first error in the chain
+    /// that is known.
+    pub fn from_job_error(job: &str, error: &(dyn StdError + 'static)) -> Vec<Self> {
+        if is_job_timeout(error) {
+            return vec![Self::new(Some(job), "job-timeout", error)];
+        }
+        if let Some(preprocessor::ExecutionError::Dependency(_)) = error.downcast_ref() {
+            return vec![Self::new(Some(job), "dependency", error)];
+        }
+        let mut current = Some(error);
+        while let Some(cause) = current {
+            if let Some(cause) = cause.downcast_ref::<web_resource::ExecutionError>() {
+                let web_resource::ExecutionError::Download(downloads) = cause else {
+                    return vec![Self::new(Some(job), cause.kind(), cause)];
+                };
+                return downloads
+                    .errors()
+                    .iter()
+                    .map(|download| Self {
+                        resource: download.path.clone(),
+                        ..Self::new(Some(job), download.error.kind(), &download.error)
+                    })
+                    .collect();
+            }
+            if let Some(cause) = cause.downcast_ref::<query::Error>() {
+                return vec![Self::new(Some(job), cause.kind(), cause)];
+            }
+            current = cause.source();
+        }
+        vec![Self::new(Some(job), "execution", error)]
+    }
+
+    /// Writes the records as a JSON list to the given file.
+    pub async fn write_all<P: AsRef<Path>>(records: &[Self], path: P) -> Result<()> {
+        let records = serde_json::to_string_pretty(records)?;
+        fs::write(path, records).await?;
+        Ok(())
+    }
+}
+
+/// Whether the job failed because it exceeded its `job_timeout`
+fn is_job_timeout(error: &(dyn StdError + 'static)) -> bool {
+    error
+        .downcast_ref::<preprocessor::ExecutionError>()
+        .is_some_and(preprocessor::ExecutionError::is_timeout)
+}
+
+/// Formats an error and all its sources as a single line
+pub(crate) fn error_chain(error: &(dyn StdError + 'static)) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        write!(&mut message, ": {error}").expect("writing to a string failed");
+        source = error.source();
+    }
+    message
+}
+
+mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    /// Error while writing a report
+    #[derive(Error, Debug)]
+    pub enum Error {
+        /// The report file could not be written
+        #[error("the report file could not be written")]
+        Io(#[from] io::Error),
+        /// The report could not be rendered as JSON
+        #[error("the report could not be rendered as JSON")]
+        Json(#[from] serde_json::Error),
+        /// The report could not be rendered as TOML
+        #[error("the report could not be rendered as TOML")]
+        Toml(#[from] toml::ser::Error),
+    }
+
+    /// Result type alias that defaults error to [Error].
+    pub type Result<T, E = Error> = std::result::Result<T, E>;
+}
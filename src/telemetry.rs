@@ -0,0 +1,45 @@
+//! Exporting traces of runs via OTLP
+//!
+//! Jobs, queries and downloads are instrumented with [tracing] spans. If an OTLP endpoint is
+//! configured, these spans are exported as OpenTelemetry traces, so that preprocessing can be
+//! monitored alongside the rest of a build.
+
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Keeps the trace exporter running; when dropped, remaining spans are exported.
+#[derive(Debug)]
+pub struct Telemetry {
+    _private: (),
+}
+
+impl Telemetry {
+    /// Starts exporting spans to the given OTLP endpoint.
+    pub fn init(endpoint: &str) -> Result<Self, TraceError> {
+        let exporter = opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(endpoint);
+        let resource = Resource::new([KeyValue::new("service.name", "prequery-preprocess")]);
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(exporter)
+            .with_trace_config(trace::config().with_resource(resource))
+            .install_batch(runtime::Tokio)?;
+
+        let layer = tracing_opentelemetry::layer().with_tracer(tracer);
+        if let Err(error) = tracing_subscriber::registry().with(layer).try_init() {
+            eprintln!("warning: traces are not exported: {error}");
+        }
+        Ok(Self { _private: () })
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
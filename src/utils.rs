@@ -1,25 +1,220 @@
+use std::fmt;
 use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::de::{self, Visitor};
+use serde::Deserializer;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Mutex};
 use tokio::task::{JoinError, JoinSet};
 
-pub async fn spawn_set<I, F, E>(futures: I) -> Vec<E>
+use crate::args::ARGS;
+
+/// Processes the given items with a fixed number of worker tasks. The items are fed to the workers
+/// through a bounded channel, so that memory use doesn't grow with the number of items. Returns
+/// the errors of all failed items.
+pub async fn worker_pool<I, W, F, E>(items: I, workers: usize, work: W) -> Vec<E>
 where
-    I: Iterator<Item = F>,
+    I: IntoIterator,
+    I::Item: Send + 'static,
+    W: Fn(I::Item) -> F + Clone + Send + 'static,
     F: Future<Output = Result<(), E>> + Send + 'static,
     E: From<JoinError> + Send + 'static,
 {
+    let workers = workers.max(1);
+    let (sender, receiver) = mpsc::channel(workers);
+    let receiver = Arc::new(Mutex::new(receiver));
+
     let mut set = JoinSet::new();
-    for future in futures {
-        set.spawn(future);
+    for _ in 0..workers {
+        let receiver = Arc::clone(&receiver);
+        let work = work.clone();
+        set.spawn(async move {
+            let mut errors = Vec::new();
+            loop {
+                // only hold the lock while waiting for the next item, not while working on it
+                let item = receiver.lock().await.recv().await;
+                let Some(item) = item else {
+                    break;
+                };
+                if let Err(error) = work(item).await {
+                    errors.push(error);
+                }
+            }
+            errors
+        });
+    }
+
+    for item in items {
+        if sender.send(item).await.is_err() {
+            // all workers have stopped; they can only have panicked
+            break;
+        }
     }
+    drop(sender);
 
     let mut errors = Vec::new();
     while let Some(result) = set.join_next().await {
         match result {
             Err(error) => errors.push(error.into()),
-            Ok(Err(error)) => errors.push(error),
-            Ok(Ok(())) => {}
+            Ok(worker_errors) => errors.extend(worker_errors),
         }
     }
     errors
 }
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Returns the time in seconds since the Unix epoch that values written by this run are based on.
+/// With `--reproducible`, this is the fixed time configured by `SOURCE_DATE_EPOCH`, otherwise the
+/// current time. Written values are still checked against the current time, see [unix_time].
+pub fn recorded_time() -> u64 {
+    ARGS.reproducible_time().unwrap_or_else(unix_time)
+}
+
+/// Writes an index file. The content is written to a temporary file first, which then replaces the
+/// actual file so that it is never left partially written. Afterwards, a backup of the content is
+/// saved for restoring the file if it gets corrupted anyway, see [backup_path].
+pub async fn write_with_backup(location: &Path, content: &[u8]) -> io::Result<()> {
+    let temp = path_with_suffix(location, ".tmp");
+    let mut file = fs::File::create(&temp).await?;
+    file.write_all(content).await?;
+    file.sync_all().await?;
+    drop(file);
+    fs::rename(&temp, location).await?;
+
+    fs::write(backup_path(location), content).await?;
+    Ok(())
+}
+
+/// Returns the location of the given index file's backup
+pub fn backup_path(location: &Path) -> PathBuf {
+    path_with_suffix(location, ".bak")
+}
+
+/// Appends a suffix to the file name of the given path, e.g. `index.toml` -> `index.toml.bak`
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// An index file that could not be parsed, and whose backup was read instead
+#[derive(Debug)]
+pub struct Restored<E> {
+    /// The location of the index file
+    pub location: PathBuf,
+    /// The location of the backup
+    pub backup: PathBuf,
+    /// The error that occurred parsing the index file
+    pub error: E,
+}
+
+impl<E: fmt::Display> fmt::Display for Restored<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "index {} was corrupt and was restored from {}: {}",
+            self.location.display(),
+            self.backup.display(),
+            self.error
+        )
+    }
+}
+
+/// Resolves a path relative to the directory containing `typst.toml`.
+pub async fn resolve_manifest_path<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let mut resolved = ARGS.resolve_typst_toml().await?;
+    let result = resolved.pop();
+    assert!(
+        result,
+        "the path should have had a final filename component"
+    );
+    resolved.push(path);
+    Ok(resolved)
+}
+
+/// Deserializes an `index` config: if given, must be either a boolean or string. `true` enables
+/// the index at the given default path.
+pub fn deserialize_index<'de, D>(
+    deserializer: D,
+    default: &str,
+) -> Result<Option<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct IndexVisitor<'a>(&'a str);
+
+    impl<'de> Visitor<'de> for IndexVisitor<'_> {
+        type Value = Option<PathBuf>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a boolean or string`")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(v.then(|| self.0.into()))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.visit_string(v.to_owned())
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(Some(v.into()))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(IndexVisitor(default))
+}
+
+/// Quotes an argument for use in a POSIX shell command line. Arguments consisting only of safe
+/// characters are not quoted.
+pub fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+%@".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Prints a status message. Status messages go to stdout, unless that is reserved for
+/// machine-readable output; then they go to stderr. With `--quiet`, they are not printed.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::args::ARGS.quiet == 0 {
+            if $crate::args::ARGS.is_stdout_reserved() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        }
+    };
+}
+
+pub(crate) use status;
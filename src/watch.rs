@@ -0,0 +1,114 @@
+//! Watching the files jobs depend on, and running affected jobs again when these change
+
+use std::collections::HashSet;
+use std::path::{self, PathBuf};
+use std::time::Duration;
+
+use notify::{Event, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time;
+
+use crate::args::ARGS;
+use crate::entry;
+use crate::error::Result;
+use crate::manifest;
+use crate::preprocessor::BoxedPreprocessor;
+use crate::utils::status;
+
+/// How long to wait for further changes before running jobs again
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the files the given jobs depend on, and runs the jobs affected by changes again. Changes
+/// to the input document affect all jobs; changes to the manifest cause all jobs to be configured
+/// anew. Changes made while jobs are running, including the ones made by the jobs themselves, are
+/// ignored. This function only returns if watching fails.
+pub async fn watch(mut jobs: Vec<BoxedPreprocessor>) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        // errors are ignored; if the receiver is gone, the watcher is about to be dropped as well
+        if let Ok(event) = event {
+            if !event.kind.is_access() {
+                let _ = tx.send(event);
+            }
+        }
+    })?;
+    let mut watched = HashSet::new();
+
+    let input = path::absolute(&ARGS.input).map_err(notify::Error::io)?;
+    let manifest = ARGS
+        .resolve_typst_toml()
+        .await
+        .map_err(manifest::Error::from)?;
+
+    status!("watching for changes...");
+    loop {
+        let dependencies = jobs
+            .iter()
+            .map(|job| {
+                job.dependencies()
+                    .into_iter()
+                    .map(path::absolute)
+                    .collect::<std::io::Result<HashSet<_>>>()
+            })
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(notify::Error::io)?;
+
+        // watch the directories containing the files, so that files that are replaced or don't
+        // exist yet are noticed as well. Directories that don't exist yet are tried again later.
+        let files = dependencies.iter().flatten().chain([&input, &manifest]);
+        for dir in files.filter_map(|file| file.parent()) {
+            if !watched.contains(dir) && watcher.watch(dir, RecursiveMode::NonRecursive).is_ok() {
+                watched.insert(dir.to_path_buf());
+            }
+        }
+
+        let Some(changed) = next_changes(&mut rx).await else {
+            return Ok(());
+        };
+
+        let (affected, unaffected) = if changed.contains(&manifest) {
+            match entry::configure_jobs().await {
+                Ok(new_jobs) => (new_jobs, Vec::new()),
+                Err(error) => {
+                    eprintln!("configuring jobs failed: {error:?}");
+                    (Vec::new(), jobs)
+                }
+            }
+        } else {
+            let all = changed.contains(&input);
+            let (affected, unaffected): (Vec<_>, Vec<_>) = jobs
+                .into_iter()
+                .zip(&dependencies)
+                .partition(|(_, dependencies)| all || is_affected(dependencies, &changed));
+            let strip = |jobs: Vec<(BoxedPreprocessor, _)>| jobs.into_iter().map(|(job, _)| job);
+            (strip(affected).collect(), strip(unaffected).collect())
+        };
+
+        jobs = unaffected;
+        if !affected.is_empty() {
+            // errors have already been reported
+            let outcome = entry::run_jobs(affected).await;
+            jobs.extend(outcome.jobs);
+            // discard the changes made by the jobs themselves
+            while let Ok(Some(_)) = time::timeout(DEBOUNCE, rx.recv()).await {}
+            // unrelated changes in the watched directories don't warrant announcing this again
+            status!("watching for changes...");
+        }
+    }
+}
+
+/// Checks whether any of the changed paths is one of the dependencies.
+fn is_affected(dependencies: &HashSet<PathBuf>, changed: &HashSet<PathBuf>) -> bool {
+    !dependencies.is_disjoint(changed)
+}
+
+/// Waits for changes, and returns the changed paths once no further changes were made for the
+/// debounce duration. Returns `None` if no more changes can be received.
+async fn next_changes(rx: &mut UnboundedReceiver<Event>) -> Option<HashSet<PathBuf>> {
+    let event = rx.recv().await?;
+    let mut changed: HashSet<_> = event.paths.into_iter().collect();
+    while let Ok(Some(event)) = time::timeout(DEBOUNCE, rx.recv()).await {
+        changed.extend(event.paths);
+    }
+    Some(changed)
+}
@@ -0,0 +1,57 @@
+"""Downloads many small resources from a local server, and reports how long the run took and how
+much memory it used at most. The resources are generated by fake-typst.sh; the run is made twice,
+first downloading everything, then finding everything up to date."""
+
+import os
+import shutil
+import subprocess
+import sys
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from pathlib import Path
+
+DIR = Path(__file__).parent
+
+
+class Tiny(BaseHTTPRequestHandler):
+    protocol_version = "HTTP/1.1"
+
+    def do_GET(self):
+        body = self.path.encode()
+        self.send_response(200)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def log_message(self, format, *args):
+        pass
+
+
+def run(label, command, env):
+    start = time.monotonic()
+    process = subprocess.Popen(command, env=env, stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL)
+    # `cargo run` replaces itself with the preprocessor, so this is the preprocessor's usage
+    _, status, usage = os.wait4(process.pid, 0)
+    elapsed = time.monotonic() - start
+    if status != 0:
+        sys.exit(f"{label}: the preprocessor failed")
+    print(f"{label}: {elapsed:.1f}s, max RSS {usage.ru_maxrss // 1024} MiB", flush=True)
+
+
+count = sys.argv[1]
+shutil.rmtree(DIR / "assets", ignore_errors=True)
+for index in DIR.glob("web-resource-index*"):
+    index.unlink()
+
+server = ThreadingHTTPServer(("127.0.0.1", 8790), Tiny)
+threading.Thread(target=server.serve_forever, daemon=True).start()
+
+env = {**os.environ, "BENCH_COUNT": count}
+command = ["cargo", "run", "--release", "-q", "--"]
+command += ["--typst", str(DIR / "fake-typst.sh"), str(DIR / "main.typ")]
+subprocess.run(["cargo", "build", "--release", "-q"], check=True)
+print(f"{count} resources", flush=True)
+run("download", command, env)
+run("up to date", command, env)
+server.shutdown()
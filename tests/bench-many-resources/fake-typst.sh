@@ -0,0 +1,10 @@
+#!/bin/sh
+# stands in for a `typst query` of a document with $BENCH_COUNT resources
+exec python3 -c '
+import json, os
+count = int(os.environ["BENCH_COUNT"])
+print(json.dumps([
+    {"url": f"http://127.0.0.1:8790/{i}.txt", "path": f"assets/{i // 1000}/{i}.txt"}
+    for i in range(count)
+]))
+'
@@ -0,0 +1 @@
+// the resources are generated by `fake-typst.sh`
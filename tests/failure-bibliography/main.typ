@@ -0,0 +1,3 @@
+// resolved from the `served` directory by `python3 -m http.server`
+#metadata("https://doi.org/10.1000/lp") <doi>
+#metadata("10.1000/missing") <doi>
@@ -0,0 +1,4 @@
+// nothing is downloaded when checking for completeness
+#metadata((url: "http://127.0.0.1:9/present.txt", path: "files/present.txt")) <complete>
+#metadata((url: "http://127.0.0.1:9/present.txt", path: "files/present.txt")) <incomplete>
+#metadata((url: "http://127.0.0.1:9/missing.txt", path: "files/missing.txt")) <incomplete>
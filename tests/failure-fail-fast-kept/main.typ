@@ -0,0 +1,4 @@
+// nothing listens on the discard port, so the first download fails while the second one is still
+// waiting for the slow server to respond
+#metadata((url: "http://127.0.0.1:9/first.txt", path: "assets/first.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8793/second.txt", path: "assets/second.txt")) <web-resource>
@@ -0,0 +1,3 @@
+// nothing listens on the discard port, so the first download fails and the second is cancelled
+#metadata((url: "http://127.0.0.1:9/first.svg", path: "assets/first.svg")) <web-resource>
+#metadata((url: "http://127.0.0.1:9/second.svg", path: "assets/second.svg")) <web-resource>
@@ -0,0 +1,2 @@
+// an image is not a font, even when saved with a font extension
+#metadata((url: "https://upload.wikimedia.org/wikipedia/commons/a/af/Cc-public_domain_mark.svg", path: "mark.ttf")) <font>
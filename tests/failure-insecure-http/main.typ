@@ -0,0 +1,3 @@
+// nothing is served; the downloads fail either way
+#metadata((url: "http://127.0.0.1:9/warn.txt", path: "assets/warn.txt")) <warn>
+#metadata((url: "http://127.0.0.1:9/enforce.txt", path: "assets/enforce.txt")) <enforce>
@@ -0,0 +1,3 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8771/logo.svg", path: "assets/logo.svg")) <web-resource>
+#metadata((url: "http://127.0.0.1:8771/missing.svg", path: "assets/missing.svg")) <web-resource>
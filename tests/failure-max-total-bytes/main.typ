@@ -0,0 +1,4 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8780/a.txt", path: "assets/a.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8780/b.txt", path: "assets/b.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8780/c.txt", path: "assets/c.txt")) <web-resource>
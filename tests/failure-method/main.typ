@@ -0,0 +1 @@
+#metadata((url: "http://127.0.0.1:8786/a.txt", path: "assets/a.txt")) <web-resource>
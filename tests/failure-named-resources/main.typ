@@ -0,0 +1 @@
+#metadata((url: "https://example.com/data.txt", path: "assets/data.txt")) <web-resource>
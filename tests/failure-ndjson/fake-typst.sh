@@ -0,0 +1,3 @@
+#!/bin/sh
+# stands in for a `typst` wrapper whose output is not valid newline-delimited JSON
+printf '"World"\n["Typst",\n]\n'
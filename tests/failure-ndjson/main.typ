@@ -0,0 +1,2 @@
+#metadata("World") <greeting>
+#metadata((name: "Typst")) <greeting>
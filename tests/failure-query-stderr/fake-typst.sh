@@ -0,0 +1,4 @@
+#!/bin/sh
+# stands in for a `typst query` that fails with diagnostics
+echo "error: unknown variable: greting" >&2
+exit 1
@@ -0,0 +1,2 @@
+// served by server.py, which redirects back and forth between /a and /b
+#metadata((url: "http://127.0.0.1:8765/a", path: "assets/loop.svg")) <web-resource>
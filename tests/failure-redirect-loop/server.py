@@ -0,0 +1,16 @@
+"""A mock server whose paths /a and /b redirect to each other"""
+
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+
+class RedirectLoop(BaseHTTPRequestHandler):
+    def do_GET(self):
+        self.send_response(302)
+        self.send_header("Location", "/b" if self.path == "/a" else "/a")
+        self.end_headers()
+
+    def log_message(self, format, *args):
+        pass
+
+
+HTTPServer(("127.0.0.1", 8765), RedirectLoop).serve_forever()
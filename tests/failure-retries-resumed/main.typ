@@ -0,0 +1,3 @@
+// nothing listens on the discard port, so this download fails; when resuming, the retries
+// recorded in the work queue are used
+#metadata((url: "http://127.0.0.1:9/flaky.svg", path: "assets/flaky.svg")) <web-resource>
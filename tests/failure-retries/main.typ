@@ -0,0 +1,3 @@
+// nothing listens on the discard port, so these downloads fail
+#metadata((url: "http://127.0.0.1:9/steady.svg", path: "assets/steady.svg")) <web-resource>
+#metadata((url: "http://127.0.0.1:9/flaky.svg", path: "assets/flaky.svg", retries: 2)) <web-resource>
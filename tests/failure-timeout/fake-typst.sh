@@ -0,0 +1,3 @@
+#!/bin/sh
+# stands in for a `typst query` that hangs, e.g. on a slow package download
+exec sleep 30
@@ -0,0 +1,3 @@
+#!/bin/sh
+# stands in for an outdated Typst installation
+echo "typst 0.10.0 (70ca0d25)"
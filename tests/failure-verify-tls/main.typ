@@ -0,0 +1,4 @@
+// served by `server.py` with a self-signed certificate
+#metadata((url: "https://127.0.0.1:8785/logo.svg", path: "assets/logo.svg")) <web-resource>
+// not downloaded via HTTPS, so not verified
+#metadata((url: "http://127.0.0.1:8786/logo.svg", path: "assets/plain.svg")) <web-resource>
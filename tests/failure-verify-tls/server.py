@@ -0,0 +1,23 @@
+"""A mock HTTPS server presenting the self-signed certificate generated next to this script"""
+
+import ssl
+from http.server import BaseHTTPRequestHandler, HTTPServer
+from pathlib import Path
+
+
+class Empty(BaseHTTPRequestHandler):
+    def do_HEAD(self):
+        self.send_response(200)
+        self.end_headers()
+
+    def log_message(self, format, *args):
+        pass
+
+
+directory = Path(__file__).parent
+context = ssl.SSLContext(ssl.PROTOCOL_TLS_SERVER)
+context.load_cert_chain(directory / "cert.pem", directory / "key.pem")
+
+server = HTTPServer(("127.0.0.1", 8785), Empty)
+server.socket = context.wrap_socket(server.socket, server_side=True)
+server.serve_forever()
@@ -0,0 +1,3 @@
+// served by `server.py`
+#metadata((url: "http://127.0.0.1:8796/a.txt", path: "assets/a.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8796/b.txt", path: "assets/b.txt")) <web-resource>
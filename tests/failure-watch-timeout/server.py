@@ -0,0 +1,21 @@
+"""A mock server that starts sending its response, but doesn't finish it for a long time"""
+
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+
+class Slow(BaseHTTPRequestHandler):
+    def do_GET(self):
+        self.send_response(200)
+        self.send_header("Content-Length", "1000")
+        self.end_headers()
+        self.wfile.write(b"partial")
+        self.wfile.flush()
+        time.sleep(30)
+
+    def log_message(self, format, *args):
+        pass
+
+
+ThreadingHTTPServer.daemon_threads = True
+ThreadingHTTPServer(("127.0.0.1", 8796), Slow).serve_forever()
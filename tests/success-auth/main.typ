@@ -0,0 +1,2 @@
+// served by server.py, which redirects to another host needing different authorization
+#metadata((url: "http://127.0.0.1:8767/a", path: "assets/private.svg")) <web-resource>
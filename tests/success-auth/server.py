@@ -0,0 +1,35 @@
+"""A mock server that checks authorization: /a on 127.0.0.1 redirects to /b on localhost"""
+
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+
+class Auth(BaseHTTPRequestHandler):
+    def do_GET(self):
+        host = self.headers.get("Host", "").split(":")[0]
+        if self.path == "/a" and host == "127.0.0.1":
+            if self.headers.get("X-Api-Key") != "one":
+                return self.reply(401)
+            self.send_response(302)
+            self.send_header("Location", "http://localhost:8767/b")
+            self.end_headers()
+        elif self.path == "/b" and host == "localhost":
+            # the other host's key must not be sent here
+            if self.headers.get("X-Api-Key") is not None:
+                return self.reply(403)
+            if self.headers.get("Authorization") != "Bearer two":
+                return self.reply(401)
+            self.reply(200, b"<svg xmlns='http://www.w3.org/2000/svg'/>")
+        else:
+            self.reply(404)
+
+    def reply(self, status, body=b""):
+        self.send_response(status)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def log_message(self, format, *args):
+        pass
+
+
+HTTPServer(("127.0.0.1", 8767), Auth).serve_forever()
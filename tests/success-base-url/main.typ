@@ -0,0 +1,3 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "abc.txt", path: "assets/abc.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8772/other/def.txt", path: "assets/def.txt")) <web-resource>
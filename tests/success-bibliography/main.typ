@@ -0,0 +1,3 @@
+// resolved from the `served` directory by `python3 -m http.server`
+#metadata(("10.1000/lp", "10.1000/taocp")) <doi>
+#metadata("10.1000/tex#1") <doi>
@@ -0,0 +1 @@
+#metadata(sys.inputs.at("name", default: "nobody")) <greeting>
@@ -0,0 +1,12 @@
+#metadata((
+  kind: "web-resource",
+  url: "https://upload.wikimedia.org/wikipedia/commons/a/af/Cc-public_domain_mark.svg",
+  path: "assets/public_domain.svg",
+)) <web-resource>
+
+// this element would fail to download if it was not filtered out
+#metadata((
+  kind: "other",
+  url: "https://example.invalid/other.svg",
+  path: "assets/other.svg",
+)) <web-resource>
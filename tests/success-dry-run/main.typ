@@ -0,0 +1,4 @@
+// nothing is downloaded in a dry run, so the URLs don't need to be reachable
+#metadata((url: "http://127.0.0.1:8781/unchanged.txt", path: "files/unchanged.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8781/changed-v2.txt", path: "files/changed.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8781/new.txt", path: "files/new.txt")) <web-resource>
@@ -0,0 +1,3 @@
+// the same path is given twice with different URLs; only the last one is used
+#metadata((url: "https://example.invalid/old.svg", path: "assets/logo.svg")) <web-resource>
+#metadata((url: "https://example.invalid/new.svg", path: "assets/logo.svg")) <web-resource>
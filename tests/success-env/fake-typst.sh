@@ -0,0 +1,3 @@
+#!/bin/sh
+# stands in for `typst query`, returning the environment variable the job configured
+printf '["%s"]\n' "$GREETING"
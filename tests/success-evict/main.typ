@@ -0,0 +1,2 @@
+// the kept file exists and is up to date, so the URL doesn't need to be reachable
+#metadata((url: "http://127.0.0.1:8797/kept.txt", path: "files/kept.txt")) <web-resource>
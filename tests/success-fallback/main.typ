@@ -0,0 +1 @@
+#metadata(sys.inputs.at("custom-fallback", default: "missing")) <greeting>
@@ -0,0 +1,2 @@
+#metadata((title: "Typst", target: (url: "https://typst.app/"))) <link>
+#metadata((title: "Universe", target: (url: "https://typst.app/universe/"))) <link>
@@ -0,0 +1,8 @@
+// served by `server.py`
+#metadata((url: "http://127.0.0.1:8791/fresh.txt", path: "always/fresh.txt")) <always>
+#metadata((url: "http://127.0.0.1:8791/changed.txt", path: "never/changed.txt")) <never>
+// the file's hash and the resource's ETag each either match or not
+#metadata((url: "http://127.0.0.1:8791/fresh.txt", path: "smart/fresh.txt")) <smart>
+#metadata((url: "http://127.0.0.1:8791/fresh.txt", path: "smart/modified.txt")) <smart>
+#metadata((url: "http://127.0.0.1:8791/changed.txt", path: "smart/changed.txt")) <smart>
+#metadata((url: "http://127.0.0.1:8791/changed.txt", path: "smart/both.txt")) <smart>
@@ -0,0 +1,40 @@
+"""A mock server with ETags: /fresh.txt never changes, while /changed.txt changes with the version
+given as argument"""
+
+import sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+VERSION = sys.argv[1]
+RESOURCES = {
+    "/fresh.txt": ('"v1"', b"fresh"),
+    "/changed.txt": (f'"v{VERSION}"', f"changed v{VERSION}".encode()),
+}
+
+
+class Versioned(BaseHTTPRequestHandler):
+    def do_GET(self):
+        body = self.reply()
+        if body is not None:
+            self.wfile.write(body)
+
+    def do_HEAD(self):
+        self.reply()
+
+    def reply(self):
+        if self.path not in RESOURCES:
+            self.send_response(404)
+            self.send_header("Content-Length", "0")
+            self.end_headers()
+            return None
+        etag, body = RESOURCES[self.path]
+        self.send_response(200)
+        self.send_header("ETag", etag)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        return body
+
+    def log_message(self, format, *args):
+        pass
+
+
+HTTPServer(("127.0.0.1", 8791), Versioned).serve_forever()
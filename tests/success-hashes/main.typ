@@ -0,0 +1,2 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8770/abc.txt", path: "assets/abc.txt")) <web-resource>
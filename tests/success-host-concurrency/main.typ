@@ -0,0 +1,9 @@
+// served by `server.py`, addressed by two host names of which only the first is limited
+#metadata((url: "http://127.0.0.1:8789/1.txt", path: "assets/127.0.0.1/1.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8789/2.txt", path: "assets/127.0.0.1/2.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8789/3.txt", path: "assets/127.0.0.1/3.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8789/4.txt", path: "assets/127.0.0.1/4.txt")) <web-resource>
+#metadata((url: "http://localhost:8789/1.txt", path: "assets/localhost/1.txt")) <web-resource>
+#metadata((url: "http://localhost:8789/2.txt", path: "assets/localhost/2.txt")) <web-resource>
+#metadata((url: "http://localhost:8789/3.txt", path: "assets/localhost/3.txt")) <web-resource>
+#metadata((url: "http://localhost:8789/4.txt", path: "assets/localhost/4.txt")) <web-resource>
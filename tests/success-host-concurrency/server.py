@@ -0,0 +1,37 @@
+"""A mock server with slow responses that records the highest number of concurrent requests for
+each host name it was addressed by in max-concurrency.txt"""
+
+import threading
+import time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from pathlib import Path
+
+OUTPUT = Path(__file__).parent / "max-concurrency.txt"
+
+lock = threading.Lock()
+active = {}
+highest = {}
+
+
+class Slow(BaseHTTPRequestHandler):
+    def do_GET(self):
+        host = self.headers["Host"].split(":")[0]
+        with lock:
+            active[host] = active.get(host, 0) + 1
+            highest[host] = max(highest.get(host, 0), active[host])
+        time.sleep(0.5)
+        with lock:
+            active[host] -= 1
+            OUTPUT.write_text("".join(f"{host} {count}\n" for host, count in sorted(highest.items())))
+
+        body = self.path.encode()
+        self.send_response(200)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def log_message(self, format, *args):
+        pass
+
+
+ThreadingHTTPServer(("127.0.0.1", 8789), Slow).serve_forever()
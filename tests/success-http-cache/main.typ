@@ -0,0 +1,2 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8778/a.txt", path: "files/a.txt")) <web-resource>
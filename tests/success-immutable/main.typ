@@ -0,0 +1,5 @@
+#metadata((
+  url: "https://upload.wikimedia.org/wikipedia/commons/a/af/Cc-public_domain_mark.svg",
+  path: "assets/public_domain.svg",
+  immutable: true,
+)) <web-resource>
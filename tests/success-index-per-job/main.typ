@@ -0,0 +1,3 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8776/abc.txt", path: "assets/abc.txt")) <image>
+#metadata((url: "http://127.0.0.1:8776/def.txt", path: "assets/def.txt")) <data>
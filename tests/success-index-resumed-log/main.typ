@@ -0,0 +1,3 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8783/a.txt", path: "assets/a.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8783/b.txt", path: "assets/b.txt")) <web-resource>
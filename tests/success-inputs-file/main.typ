@@ -0,0 +1,3 @@
+#metadata(sys.inputs.at("name", default: "nobody")) <greeting>
+#metadata(sys.inputs.at("count", default: "none")) <greeting>
+#metadata(sys.inputs.at("punctuation", default: "none")) <greeting>
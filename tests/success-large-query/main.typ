@@ -0,0 +1,4 @@
+// produces a query result of several megabytes
+#for i in range(20000) [
+  #metadata((index: i, text: "lorem ipsum dolor sit amet " * 4)) <entry>
+]
@@ -0,0 +1,3 @@
+// served from the `served` directory by `python3 -m http.server`; both files have the same content
+#metadata((url: "http://127.0.0.1:8788/a.txt", path: "assets/a.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8788/b.txt", path: "assets/nested/b.txt")) <web-resource>
@@ -0,0 +1,2 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((listing: "http://127.0.0.1:8782/listing.json", path: "assets")) <web-resource>
@@ -0,0 +1,7 @@
+// the listing is served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8795/files/plain.txt", path: "assets/plain.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8795/files/options.txt", path: "assets/options.txt", immutable: true, retries: 2)) <web-resource>
+// a resource given twice is only downloaded once
+#metadata((url: "http://127.0.0.1:8795/files/twice.txt", path: "assets/twice.txt")) <web-resource>
+#metadata((url: "http://127.0.0.1:8795/files/twice.txt", path: "assets/twice.txt", max_size: 1024)) <web-resource>
+#metadata((listing: "http://127.0.0.1:8795/listing.json", path: "assets/listed")) <web-resource>
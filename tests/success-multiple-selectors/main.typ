@@ -0,0 +1,3 @@
+#metadata("Marie") <physicist>
+#metadata("Ada") <mathematician>
+#metadata("Alan") <mathematician>
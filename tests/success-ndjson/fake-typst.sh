@@ -0,0 +1,3 @@
+#!/bin/sh
+# stands in for a `typst` wrapper that converts the query result to newline-delimited JSON
+printf '"World"\n\n{"name": "Typst"}\n'
@@ -0,0 +1,4 @@
+// the absolute import only resolves if the project root was detected
+#import "/names.typ": names
+
+#for name in names [#metadata(name) <greeting>]
@@ -0,0 +1 @@
+#let names = ("World", "Typst")
@@ -0,0 +1,3 @@
+#!/bin/sh
+# stands in for a `typst query` returning numbers that don't fit 64 bit integers or floats
+echo '[123456789012345678901234567890, 0.1000000000000000000001]'
@@ -0,0 +1,2 @@
+#metadata(123456789012345678901234567890) <number>
+#metadata(0.1000000000000000000001) <number>
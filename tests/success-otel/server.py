@@ -0,0 +1,20 @@
+"""A mock OTLP collector that records how the exporter connected: gRPC starts with the HTTP/2
+connection preface"""
+
+import socket
+
+PREFACE = b"PRI * HTTP/2.0"
+
+with socket.create_server(("127.0.0.1", 8794)) as server:
+    received = b""
+    # connections that are closed without sending anything only check that the server is up
+    while not received:
+        connection, _ = server.accept()
+        with connection:
+            while len(received) < len(PREFACE):
+                chunk = connection.recv(1024)
+                if not chunk:
+                    break
+                received += chunk
+    with open("success-otel/preface.txt", "wb") as file:
+        file.write(received[: len(PREFACE)])
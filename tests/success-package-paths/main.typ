@@ -0,0 +1,4 @@
+// the package is only found in the job's package path
+#import "@local/greeting:0.1.0": greet
+#greet("World")
+#greet("Typst")
@@ -0,0 +1 @@
+#let greet(name) = [#metadata(name) <greeting>]
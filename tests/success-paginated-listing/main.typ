@@ -0,0 +1,2 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((listing: "http://127.0.0.1:8775/api/page1.json", path: "assets")) <web-resource>
@@ -0,0 +1,46 @@
+/* A minimal plugin that writes the query result of its jobs to the file given as `output` */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+struct PluginDeclaration {
+    uint32_t abi_version;
+    const char *kind;
+    const char *default_selector;
+    char *(*run)(const char *name, const char *config, const char *data);
+    void (*free_error)(char *error);
+};
+
+static char *run(const char *name, const char *config, const char *data) {
+    const char *key = "\"output\":\"";
+    const char *start = strstr(config, key);
+    if (!start) {
+        return strdup("`output` is missing");
+    }
+    start += strlen(key);
+    size_t len = strcspn(start, "\"");
+
+    char *path = strndup(start, len);
+    FILE *file = fopen(path, "w");
+    free(path);
+    if (!file) {
+        return strdup("`output` could not be opened");
+    }
+    fputs(data, file);
+    fclose(file);
+    return NULL;
+}
+
+static void free_error(char *error) {
+    free(error);
+}
+
+const struct PluginDeclaration prequery_plugin_declaration = {
+    .abi_version = 1,
+    .kind = "echo",
+    .default_selector = "<echo>",
+    .run = run,
+    .free_error = free_error,
+};
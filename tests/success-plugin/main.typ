@@ -0,0 +1 @@
+#metadata("World") <echo>
@@ -0,0 +1,7 @@
+#!/bin/sh
+# stands in for `typst query`, failing if another instance is running at the same time
+lock="$(dirname "$0")/running"
+mkdir "$lock" || exit 1
+sleep 1
+rmdir "$lock"
+printf '["Serial"]\n'
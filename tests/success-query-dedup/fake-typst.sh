@@ -0,0 +1,5 @@
+#!/bin/sh
+# stands in for `typst query`, recording each invocation so that shared queries can be counted
+echo "$@" >> "$(dirname "$0")/invocations"
+sleep 1
+printf '["Dedup"]\n'
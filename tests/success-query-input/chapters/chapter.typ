@@ -0,0 +1 @@
+#metadata("Chapter") <greeting>
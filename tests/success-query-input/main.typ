@@ -0,0 +1 @@
+#include "chapters/chapter.typ"
@@ -0,0 +1,9 @@
+#!/bin/sh
+# stands in for `typst query` that fails on the first attempt, like a flaky package download
+marker="$(dirname "$0")/attempted"
+if [ ! -e "$marker" ]; then
+    touch "$marker"
+    exit 1
+fi
+rm "$marker"
+printf '["Retry"]\n'
@@ -0,0 +1,5 @@
+#metadata("World") <greeting>
+#metadata("Typst") <greeting>
+
+// served by `server.py`
+#metadata((url: "http://127.0.0.1:8787/data.txt", path: "assets/data.txt")) <web-resource>
@@ -0,0 +1,20 @@
+"""A mock server whose responses may be cached for a minute"""
+
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+BODY = b"data"
+
+
+class Cacheable(BaseHTTPRequestHandler):
+    def do_GET(self):
+        self.send_response(200)
+        self.send_header("Cache-Control", "max-age=60")
+        self.send_header("Content-Length", str(len(BODY)))
+        self.end_headers()
+        self.wfile.write(BODY)
+
+    def log_message(self, format, *args):
+        pass
+
+
+HTTPServer(("127.0.0.1", 8787), Cacheable).serve_forever()
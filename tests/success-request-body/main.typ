@@ -0,0 +1,2 @@
+// served by `server.py`
+#metadata((url: "http://127.0.0.1:8792/export", path: "assets/export.json")) <web-resource>
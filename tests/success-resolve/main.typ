@@ -0,0 +1,2 @@
+// served from the `served` directory by `python3 -m http.server`; the host doesn't exist in DNS
+#metadata((url: "http://mirror.prequery.test:8774/data.txt", path: "assets/data.txt")) <web-resource>
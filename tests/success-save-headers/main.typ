@@ -0,0 +1,2 @@
+// served from the `served` directory by `python3 -m http.server`
+#metadata((url: "http://127.0.0.1:8768/logo.svg", path: "assets/logo.svg")) <web-resource>
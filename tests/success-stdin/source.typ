@@ -0,0 +1,3 @@
+// piped into the preprocessor instead of being passed as a file
+#metadata("Stdin") <greeting>
+#metadata("Stdin") <farewell>
@@ -0,0 +1 @@
+#metadata(9223372036854775807) <id>
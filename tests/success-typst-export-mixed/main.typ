@@ -0,0 +1,8 @@
+// query results don't need to have a common shape
+#metadata("text") <value>
+#metadata(42) <value>
+#metadata(1.5) <value>
+#metadata(true) <value>
+#metadata(none) <value>
+#metadata((1, "two")) <value>
+#metadata((key: (nested: "value"))) <value>
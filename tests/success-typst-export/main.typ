@@ -0,0 +1,2 @@
+#metadata((name: "Ada \"the Countess\"", born: 1815, tags: ("math",))) <person>
+#metadata((name: "Alan", born: 1912, tags: (), extra: none)) <person>
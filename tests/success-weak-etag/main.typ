@@ -0,0 +1,3 @@
+// served by `server.py`
+#metadata((url: "http://127.0.0.1:8769/strong.svg", path: "assets/strong.svg")) <web-resource>
+#metadata((url: "http://127.0.0.1:8769/weak.svg", path: "assets/weak.svg")) <web-resource>
@@ -0,0 +1,36 @@
+"""A mock server whose ETags differ between GET and HEAD: /strong.svg is weakened for HEAD like by a
+compressing proxy, while the weak ETag of /weak.svg changes"""
+
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+BODY = b"<svg xmlns='http://www.w3.org/2000/svg'/>"
+ETAGS = {
+    "/strong.svg": ('"v1"', 'W/"v1"'),
+    "/weak.svg": ('W/"v1"', 'W/"v2"'),
+}
+
+
+class ETags(BaseHTTPRequestHandler):
+    def do_GET(self):
+        self.reply(0)
+        self.wfile.write(BODY)
+
+    def do_HEAD(self):
+        self.reply(1)
+
+    def reply(self, method):
+        if self.path not in ETAGS:
+            self.send_response(404)
+            self.send_header("Content-Length", "0")
+            self.end_headers()
+            return
+        self.send_response(200)
+        self.send_header("ETag", ETAGS[self.path][method])
+        self.send_header("Content-Length", str(len(BODY)))
+        self.end_headers()
+
+    def log_message(self, format, *args):
+        pass
+
+
+HTTPServer(("127.0.0.1", 8769), ETags).serve_forever()
@@ -0,0 +1,4 @@
+#metadata("Ada") <name>
+#metadata(1815) <born>
+#metadata("Alan") <name>
+#metadata(1912) <born>